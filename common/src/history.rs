@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct History {
     pub values: Vec<f32>,
 }
@@ -20,6 +23,10 @@ impl History {
         *self.values.last_mut().unwrap() = value;
     }
 
+    pub fn last(&self) -> f32 {
+        *self.values.last().unwrap()
+    }
+
     pub fn avg(&self) -> f32 {
         self.values.iter().sum::<f32>() / (self.values.len() as f32)
     }
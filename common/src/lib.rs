@@ -18,6 +18,10 @@ macro_rules! unwrap_orr {
     };
 }
 
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::BuildHasherDefault;
+
 pub mod config;
 pub mod history;
 pub mod rand;
@@ -30,6 +34,10 @@ pub use history::*;
 pub use time::*;
 pub use z::*;
 
+/// A HashMap whose iteration order only depends on the operations done on it and not on a random seed.
+/// Used by the simulation so that it stays deterministic.
+pub type DetHashMap<K, V> = HashMap<K, V, BuildHasherDefault<DefaultHasher>>;
+
 #[derive(Copy, Clone)]
 pub enum AudioKind {
     Music,
@@ -1,10 +1,12 @@
 use bincode::{DefaultOptions, Options};
 use serde::de::{DeserializeOwned, DeserializeSeed};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+pub use serde_json::Value as JsonValue;
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+use std::hash::Hasher;
+use std::io::{BufReader, BufWriter, Read, Write};
 
-fn filename(name: &'static str) -> String {
+fn filename(name: &str) -> String {
     format!("world/{}.bc", name)
 }
 
@@ -35,6 +37,26 @@ pub fn save_silent<T: Serialize>(x: &T, name: &'static str) -> Option<()> {
     Some(())
 }
 
+struct HashWriter<'a>(&'a mut dyn Hasher);
+
+impl<'a> Write for HashWriter<'a> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Feeds the serialized representation of x to the hasher
+pub fn hash_into<T: Serialize>(x: &T, hasher: &mut dyn Hasher) -> Option<()> {
+    bincode::serialize_into(HashWriter(hasher), x)
+        .map_err(|e| log::error!("failed serializing for hashing: {}", e))
+        .ok()
+}
+
 pub fn load_or_default<T: DeserializeOwned + Default>(name: &'static str) -> T {
     load(name).unwrap_or_default()
 }
@@ -69,19 +91,16 @@ pub fn load_reader(name: &'static str) -> Option<BufReader<File>> {
     Some(BufReader::new(file))
 }
 
-fn filename_json(name: &'static str) -> String {
+fn filename_json(name: &str) -> String {
     format!("world/{}.json", name)
 }
 
-pub fn load_reader_json(name: &'static str) -> Option<BufReader<File>> {
+pub fn load_reader_json(name: &str) -> Option<BufReader<File>> {
     let file = open_file(&filename_json(name))?;
     Some(BufReader::new(file))
 }
 
-pub fn load_seed_json<S: DeserializeSeed<'static>>(
-    name: &'static str,
-    seed: S,
-) -> Option<S::Value> {
+pub fn load_seed_json<S: DeserializeSeed<'static>>(name: &str, seed: S) -> Option<S::Value> {
     seed.deserialize(&mut serde_json::Deserializer::from_reader(
         load_reader_json(name)?,
     ))
@@ -93,13 +112,13 @@ pub fn load_seed_json<S: DeserializeSeed<'static>>(
     .ok()
 }
 
-pub fn save_json<T: Serialize>(x: &T, name: &'static str) -> Option<()> {
+pub fn save_json<T: Serialize>(x: &T, name: &str) -> Option<()> {
     save_silent_json(x, name);
     log::info!("successfully saved {}", name);
     Some(())
 }
 
-pub fn save_silent_json<T: Serialize>(x: &T, name: &'static str) -> Option<()> {
+pub fn save_silent_json<T: Serialize>(x: &T, name: &str) -> Option<()> {
     let _ = std::fs::create_dir("world");
 
     let file = create_file(&filename_json(name))?;
@@ -112,7 +131,7 @@ pub fn save_silent_json<T: Serialize>(x: &T, name: &'static str) -> Option<()> {
     Some(())
 }
 
-pub fn load_json<T: DeserializeOwned>(name: &'static str) -> Option<T> {
+pub fn load_json<T: DeserializeOwned>(name: &str) -> Option<T> {
     serde_json::from_reader(load_reader_json(name)?)
         .map_err(|err| log::error!("failed deserializing {}: {}", name, err))
         .map(|x| {
@@ -121,3 +140,381 @@ pub fn load_json<T: DeserializeOwned>(name: &'static str) -> Option<T> {
         })
         .ok()
 }
+
+pub fn delete_json(name: &str) -> Option<()> {
+    std::fs::remove_file(filename_json(name))
+        .map_err(|e| log::error!("failed deleting {}: {}", name, e))
+        .ok()
+}
+
+pub fn to_json_value<T: Serialize>(x: &T, name: &str) -> Option<JsonValue> {
+    serde_json::to_value(x)
+        .map_err(|e| log::error!("failed serializing {}: {}", name, e))
+        .ok()
+}
+
+pub fn from_json_value<T: DeserializeOwned>(v: JsonValue, name: &str) -> Option<T> {
+    serde_json::from_value(v)
+        .map_err(|e| log::error!("failed deserializing {}: {}", name, e))
+        .ok()
+}
+
+const ARCHIVE_MAGIC: [u8; 4] = *b"EGRA";
+
+/// Version of the archive layout itself, the sections are versioned independently.
+pub const ARCHIVE_VERSION: u32 = 1;
+
+const SAVES_DIR: &str = "world/saves";
+const ARCHIVE_EXT: &str = "save";
+
+fn filename_archive(name: &str) -> String {
+    format!("{}/{}.{}", SAVES_DIR, name, ARCHIVE_EXT)
+}
+
+fn checksum(data: &[u8]) -> u32 {
+    let mut h = crc32fast::Hasher::new();
+    h.update(data);
+    h.finalize()
+}
+
+/// A named blob of bincode data inside an archive.
+/// The version is the version of the data layout, used to migrate old saves.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Section {
+    pub name: String,
+    pub version: u32,
+    checksum: u32,
+    data: Vec<u8>,
+}
+
+impl Section {
+    pub fn new(name: &str, version: u32, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            version,
+            checksum: checksum(&data),
+            data,
+        }
+    }
+
+    pub fn from_value<T: Serialize>(name: &str, version: u32, x: &T) -> Option<Self> {
+        let data = bincode::serialize(x)
+            .map_err(|e| log::error!("failed serializing {}: {}", name, e))
+            .ok()?;
+        Some(Self::new(name, version, data))
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Replaces the data of the section, used by migrations.
+    pub fn set_data(&mut self, version: u32, data: Vec<u8>) {
+        self.version = version;
+        self.checksum = checksum(&data);
+        self.data = data;
+    }
+
+    pub fn is_valid(&self) -> bool {
+        checksum(&self.data) == self.checksum
+    }
+
+    pub fn read<T: DeserializeOwned>(&self) -> Option<T> {
+        bincode::deserialize(&self.data)
+            .map_err(|err| log::error!("failed deserializing {}: {}", self.name, err))
+            .ok()
+    }
+
+    pub fn read_seed<'a, S: DeserializeSeed<'a>>(&'a self, seed: S) -> Option<S::Value> {
+        seed.deserialize(&mut bincode::Deserializer::from_slice(
+            &self.data,
+            DefaultOptions::new()
+                .allow_trailing_bytes()
+                .with_fixint_encoding(),
+        ))
+        .map_err(|err| log::error!("failed deserializing {}: {}", self.name, err))
+        .ok()
+    }
+}
+
+/// A single save file made of checksummed sections.
+/// It is written to a temporary file first and then renamed so a crash never leaves a half written save.
+#[derive(Default)]
+pub struct Archive {
+    sections: Vec<Section>,
+}
+
+impl Archive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a section, replacing the one with the same name if any.
+    pub fn add(&mut self, section: Section) {
+        self.sections.retain(|s| s.name != section.name);
+        self.sections.push(section);
+    }
+
+    pub fn add_value<T: Serialize>(&mut self, name: &str, version: u32, x: &T) -> Option<()> {
+        self.add(Section::from_value(name, version, x)?);
+        Some(())
+    }
+
+    pub fn section(&self, name: &str) -> Option<&Section> {
+        self.sections.iter().find(|s| s.name == name)
+    }
+
+    pub fn sections(&self) -> impl Iterator<Item = &Section> {
+        self.sections.iter()
+    }
+
+    pub fn sections_mut(&mut self) -> impl Iterator<Item = &mut Section> {
+        self.sections.iter_mut()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Section> {
+        let i = self.sections.iter().position(|s| s.name == name)?;
+        Some(self.sections.remove(i))
+    }
+
+    pub fn exists(name: &str) -> bool {
+        std::path::Path::new(&filename_archive(name)).exists()
+    }
+
+    /// Names of all the archives in the saves directory, sorted alphabetically
+    pub fn list() -> Vec<String> {
+        let dir = unwrap_orr!(std::fs::read_dir(SAVES_DIR), return vec![]);
+        let mut names: Vec<String> = dir
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != ARCHIVE_EXT {
+                    return None;
+                }
+                Some(path.file_stem()?.to_str()?.to_string())
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    pub fn delete(name: &str) -> Option<()> {
+        std::fs::remove_file(filename_archive(name))
+            .map_err(|e| log::error!("failed deleting archive {}: {}", name, e))
+            .ok()?;
+        log::info!("deleted archive {}", name);
+        Some(())
+    }
+
+    /// Copies the archive `src` to `dst`, overwriting `dst` if it exists
+    pub fn duplicate(src: &str, dst: &str) -> Option<()> {
+        let tmp = format!("{}.tmp", filename_archive(dst));
+        std::fs::copy(filename_archive(src), &tmp)
+            .and_then(|_| std::fs::rename(&tmp, filename_archive(dst)))
+            .map_err(|e| log::error!("failed duplicating archive {} to {}: {}", src, dst, e))
+            .ok()?;
+        Some(())
+    }
+
+    /// Reads a single section without loading the rest of the archive.
+    /// Sections are read in order so this is cheap for the first ones.
+    pub fn load_section(name: &str, section: &str) -> Option<Section> {
+        let mut r = Self::open_reader(name)?;
+
+        let n_sections: u64 = bincode::deserialize_from(&mut r).ok()?;
+        for _ in 0..n_sections {
+            let s: Section = bincode::deserialize_from(&mut r).ok()?;
+            if s.name == section {
+                return if s.is_valid() { Some(s) } else { None };
+            }
+        }
+        None
+    }
+
+    /// Opens the archive and checks the header, the reader is placed right after it
+    fn open_reader(name: &str) -> Option<BufReader<File>> {
+        let path = filename_archive(name);
+        let mut r = BufReader::new(open_file(&path)?);
+
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)
+            .map_err(|e| log::error!("failed reading {}: {}", path, e))
+            .ok()?;
+        if magic != ARCHIVE_MAGIC {
+            log::error!("{} is not a save archive", path);
+            return None;
+        }
+
+        let version: u32 = bincode::deserialize_from(&mut r)
+            .map_err(|e| log::error!("failed reading {}: {}", path, e))
+            .ok()?;
+        if version > ARCHIVE_VERSION {
+            log::error!(
+                "{} has archive version {} but only up to {} is supported",
+                path,
+                version,
+                ARCHIVE_VERSION
+            );
+            return None;
+        }
+
+        Some(r)
+    }
+
+    pub fn save(&self, name: &str) -> Option<()> {
+        let _ = std::fs::create_dir_all(SAVES_DIR);
+
+        let path = filename_archive(name);
+        let tmp = format!("{}.tmp", path);
+
+        {
+            let file = create_file(&tmp)?;
+            let mut w = BufWriter::new(file);
+
+            w.write_all(&ARCHIVE_MAGIC)
+                .map_err(|e| log::error!("failed writing {}: {}", tmp, e))
+                .ok()?;
+            bincode::serialize_into(&mut w, &(ARCHIVE_VERSION, &self.sections))
+                .map_err(|e| log::error!("failed serializing archive {}: {}", name, e))
+                .ok()?;
+            w.flush()
+                .map_err(|e| log::error!("failed writing {}: {}", tmp, e))
+                .ok()?;
+        }
+
+        std::fs::rename(&tmp, &path)
+            .map_err(|e| log::error!("failed renaming {} to {}: {}", tmp, path, e))
+            .ok()?;
+
+        log::info!("successfully saved archive {}", name);
+        Some(())
+    }
+
+    /// Loads an archive, sections whose checksum doesn't match are dropped.
+    pub fn load(name: &str) -> Option<Self> {
+        let path = filename_archive(name);
+        let r = Self::open_reader(name)?;
+
+        let sections: Vec<Section> = bincode::deserialize_from(r)
+            .map_err(|e| log::error!("failed deserializing archive {}: {}", name, e))
+            .ok()?;
+
+        let sections = sections
+            .into_iter()
+            .filter(|s| {
+                let valid = s.is_valid();
+                if !valid {
+                    log::error!("section {} of {} is corrupted, ignoring it", s.name, path);
+                }
+                valid
+            })
+            .collect();
+
+        log::info!("successfully loaded archive {}", name);
+        Some(Self { sections })
+    }
+
+    /// Builds an archive from the files of the old save format (one bincode file per section).
+    /// The sections get version 0.
+    pub fn load_legacy<'a>(names: impl Iterator<Item = &'a str>) -> Self {
+        let mut archive = Self::default();
+        for name in names {
+            let mut data = vec![];
+            let ok = open_file(&filename(name))
+                .and_then(|mut f| f.read_to_end(&mut data).ok())
+                .is_some();
+            if ok {
+                archive.add(Section::new(name, 0, data));
+            }
+        }
+        archive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{filename, filename_archive, save_silent, Archive, Section, ARCHIVE_VERSION};
+
+    fn test_archive() -> Archive {
+        let mut archive = Archive::new();
+        archive.add_value("first", 3, &vec![1u32, 2, 3]).unwrap();
+        archive.add(Section::new("second", 1, b"second section".to_vec()));
+        archive
+    }
+
+    #[test]
+    fn test_archive_round_trip() {
+        let name = "test_archive_round_trip";
+        test_archive().save(name).unwrap();
+
+        let loaded = Archive::load(name).unwrap();
+        let first = loaded.section("first").unwrap();
+        assert_eq!(first.version, 3);
+        assert_eq!(first.read::<Vec<u32>>(), Some(vec![1, 2, 3]));
+        assert_eq!(loaded.sections().count(), 2);
+
+        let second = Archive::load_section(name, "second").unwrap();
+        assert_eq!(second.version, 1);
+        assert_eq!(second.data(), b"second section");
+        assert!(Archive::load_section(name, "third").is_none());
+
+        Archive::delete(name).unwrap();
+        assert!(!Archive::exists(name));
+    }
+
+    #[test]
+    fn test_corrupted_section() {
+        let name = "test_corrupted_section";
+        test_archive().save(name).unwrap();
+
+        let path = filename_archive(name);
+        let mut bytes = std::fs::read(&path).unwrap();
+        let at = bytes
+            .windows(7)
+            .position(|w| w == b"section")
+            .expect("the section data is not in the archive");
+        bytes[at] = b'S';
+        std::fs::write(&path, bytes).unwrap();
+
+        let loaded = Archive::load(name).unwrap();
+        assert!(loaded.section("first").is_some());
+        assert!(loaded.section("second").is_none());
+        assert!(Archive::load_section(name, "second").is_none());
+
+        Archive::delete(name).unwrap();
+    }
+
+    #[test]
+    fn test_archive_version() {
+        let name = "test_archive_version";
+        test_archive().save(name).unwrap();
+
+        let path = filename_archive(name);
+        let mut bytes = std::fs::read(&path).unwrap();
+        // The version comes right after the magic
+        bytes[4..8].copy_from_slice(&(ARCHIVE_VERSION + 1).to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(Archive::load(name).is_none());
+        assert!(Archive::load_section(name, "first").is_none());
+
+        bytes[0] = b'X';
+        bytes[4..8].copy_from_slice(&ARCHIVE_VERSION.to_le_bytes());
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(Archive::load(name).is_none());
+
+        Archive::delete(name).unwrap();
+    }
+
+    #[test]
+    fn test_load_legacy() {
+        let name = "test_load_legacy";
+        save_silent(&vec![4u32, 5], name).unwrap();
+
+        let archive = Archive::load_legacy([name, "test_load_legacy_missing"].iter().copied());
+        assert_eq!(archive.sections().count(), 1);
+        let section = archive.section(name).unwrap();
+        assert_eq!(section.version, 0);
+        assert_eq!(section.read::<Vec<u32>>(), Some(vec![4, 5]));
+
+        std::fs::remove_file(filename(name)).unwrap();
+    }
+}
@@ -1,8 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
 
 pub const SECONDS_PER_HOUR: i32 = 100;
 pub const HOURS_PER_DAY: i32 = 24;
 pub const SECONDS_PER_DAY: i32 = SECONDS_PER_HOUR * HOURS_PER_DAY;
+pub const DAYS_PER_WEEK: i32 = 7;
 
 /// An in-game instant used to measure time differences
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
@@ -141,6 +143,23 @@ impl DayTime {
     pub fn gamesec(&self) -> i32 {
         self.day * SECONDS_PER_DAY + self.daysec()
     }
+
+    /// Day of the week, the game starts on the first one
+    pub fn weekday(&self) -> i32 {
+        self.day.rem_euclid(DAYS_PER_WEEK)
+    }
+
+    /// Minutes elapsed since the start of the hour, as shown on a clock
+    pub fn minute(&self) -> i32 {
+        self.second * 60 / SECONDS_PER_HOUR
+    }
+}
+
+/// Shows the time of the day as hours and minutes
+impl Display for DayTime {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.hour, self.minute())
+    }
 }
 
 impl GameTime {
@@ -0,0 +1,45 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use egregoria::economy::{match_orders, match_orders_naive, BuyRequest, Money, SellOffer};
+use geom::vec2;
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+/// Half of the orders are sells and half are buys, spread over a 10km wide city
+fn orders(n: usize) -> (Vec<SellOffer>, Vec<BuyRequest>) {
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut pos = move || vec2(rng.gen_range(0.0..10000.0), rng.gen_range(0.0..10000.0));
+
+    let sells = (0..n / 2)
+        .map(|_| SellOffer {
+            pos: pos(),
+            qty: 5,
+            price: Money::from_units(9),
+        })
+        .collect();
+    let buys = (0..n / 2)
+        .map(|_| BuyRequest {
+            pos: pos(),
+            qty: 1,
+            price: Money::from_units(11),
+            money: Money::from_units(100),
+        })
+        .collect();
+    (sells, buys)
+}
+
+fn bench_matching(c: &mut Criterion) {
+    let (sells, buys) = orders(10000);
+
+    let mut group = c.benchmark_group("match 10k orders");
+    group.sample_size(10);
+    group.bench_function("grid", |b| {
+        b.iter(|| match_orders(black_box(&sells), black_box(&buys)))
+    });
+    group.bench_function("naive", |b| {
+        b.iter(|| match_orders_naive(black_box(&sells), black_box(&buys)))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_matching);
+criterion_main!(benches);
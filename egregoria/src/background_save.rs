@@ -0,0 +1,104 @@
+use crate::{Egregoria, SaveSnapshot};
+use legion::system;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+register_resource_noserialize!(SaveProgress);
+/// Tracks the save being written in the background by [save_to_disk_background]
+#[derive(Default)]
+pub struct SaveProgress {
+    pending: Option<PendingSave>,
+    /// Slot of the last finished save and whether it succeeded
+    pub last: Option<(String, bool)>,
+}
+
+struct PendingSave {
+    slot: String,
+    /// Bits of a f32 going from 0 to 1
+    progress: Arc<AtomicU32>,
+    done: Arc<AtomicBool>,
+    handle: JoinHandle<Option<()>>,
+}
+
+impl SaveProgress {
+    pub fn is_saving(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn saving_slot(&self) -> Option<&str> {
+        self.pending.as_ref().map(|p| &*p.slot)
+    }
+
+    /// Progress of the save being written, between 0 and 1
+    pub fn progress(&self) -> Option<f32> {
+        self.pending
+            .as_ref()
+            .map(|p| f32::from_bits(p.progress.load(Ordering::Relaxed)))
+    }
+
+    /// Checks if the pending save is done, blocking until it is if `wait` is true
+    pub fn poll(&mut self, wait: bool) {
+        let done = unwrap_or!(&self.pending, return)
+            .done
+            .load(Ordering::Acquire);
+        if !done && !wait {
+            return;
+        }
+
+        let pending = self.pending.take().unwrap();
+        let ok = matches!(pending.handle.join(), Ok(Some(())));
+        if ok {
+            log::info!("finished saving {} in the background", pending.slot);
+        } else {
+            log::error!("failed saving {} in the background", pending.slot);
+        }
+        self.last = Some((pending.slot, ok));
+    }
+}
+
+impl Drop for SaveProgress {
+    fn drop(&mut self) {
+        self.poll(true);
+    }
+}
+
+/// Takes a snapshot of the game and writes it to the slot on another thread.
+/// Returns false if a save is already being written.
+pub fn save_to_disk_background(goria: &mut Egregoria, slot: &str) -> bool {
+    goria.write::<SaveProgress>().poll(false);
+    if goria.read::<SaveProgress>().is_saving() {
+        log::warn!("a save is already being written, not saving {}", slot);
+        return false;
+    }
+
+    let snapshot = SaveSnapshot::new(goria);
+
+    let progress = Arc::new(AtomicU32::new(0.0f32.to_bits()));
+    let done = Arc::new(AtomicBool::new(false));
+
+    let handle = {
+        let progress = progress.clone();
+        let done = done.clone();
+        let slot = slot.to_string();
+        std::thread::spawn(move || {
+            let r = snapshot.write(&slot, |p| progress.store(p.to_bits(), Ordering::Relaxed));
+            done.store(true, Ordering::Release);
+            r
+        })
+    };
+
+    goria.write::<SaveProgress>().pending = Some(PendingSave {
+        slot: slot.to_string(),
+        progress,
+        done,
+        handle,
+    });
+    true
+}
+
+register_system!(save_progress);
+#[system]
+fn save_progress(#[resource] progress: &mut SaveProgress) {
+    progress.poll(false);
+}
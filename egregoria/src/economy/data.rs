@@ -0,0 +1,302 @@
+use crate::economy::{CommodityKind, Money};
+use crate::souls::desire::NeedKind;
+use crate::souls::goods_company::{CompanyKind, GoodsCompanyDescription, Recipe};
+use lazy_static::lazy_static;
+use map_model::{BuildingGen, BuildingKind};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// File describing the commodities and the companies, it can be edited without recompiling
+pub const ECONOMY_DATA_PATH: &str = "assets/economy.json";
+
+/// Used when the data file can't be read, so that the game still has an economy
+const DEFAULT_ECONOMY_DATA: &str = include_str!("../../../assets/economy.json");
+
+/// Name of the commodity bought by humans to eat
+pub const FOOD_COMMODITY: &str = "bread";
+
+const JOB_OPENING: &str = "job_opening";
+
+lazy_static! {
+    static ref ECONOMY_DATA: EconomyData = EconomyData::load(ECONOMY_DATA_PATH);
+}
+
+/// The commodities and the companies loaded from [ECONOMY_DATA_PATH]
+pub fn economy_data() -> &'static EconomyData {
+    &ECONOMY_DATA
+}
+
+pub struct CommodityDescription {
+    /// Used to reference the commodity in the data file and in the saves
+    pub name: String,
+    pub label: String,
+    /// Price a market starts at
+    pub base_price: Money,
+    /// Need of the humans satisfied by buying it
+    pub need: Option<NeedKind>,
+}
+
+pub struct EconomyData {
+    pub commodities: Vec<CommodityDescription>,
+    pub companies: Vec<GoodsCompanyDescription>,
+    pub(crate) kinds: Vec<CommodityKind>,
+    pub(crate) by_name: HashMap<String, CommodityKind>,
+    pub(crate) food: CommodityKind,
+}
+
+#[derive(Deserialize)]
+struct RawEconomyData {
+    commodities: Vec<RawCommodity>,
+    companies: Vec<RawCompany>,
+}
+
+#[derive(Deserialize)]
+struct RawCommodity {
+    name: String,
+    label: String,
+    /// In money units
+    base_price: f64,
+    #[serde(default)]
+    need: Option<NeedKind>,
+}
+
+#[derive(Deserialize)]
+struct RawRecipe {
+    consumption: Vec<(String, i32)>,
+    production: Vec<(String, i32)>,
+    complexity: i32,
+    storage_multiplier: i32,
+}
+
+#[derive(Deserialize)]
+struct RawCompany {
+    id: u32,
+    name: String,
+    bgen: BuildingGen,
+    kind: CompanyKind,
+    recipe: RawRecipe,
+    #[serde(default)]
+    shelf: Vec<(String, i32)>,
+    n_workers: i32,
+    size: f32,
+    asset_location: String,
+}
+
+impl EconomyData {
+    /// Loads the data file, logging the errors found. Falls back to the default data if the file
+    /// can't be read.
+    pub fn load(path: &str) -> Self {
+        let (data, errors) = match std::fs::read_to_string(path) {
+            Ok(json) => Self::parse(&json),
+            Err(e) => (
+                Self::parse(DEFAULT_ECONOMY_DATA).0,
+                vec![format!(
+                    "could not read {}: {}, using the default data",
+                    path, e
+                )],
+            ),
+        };
+
+        for error in errors {
+            log::error!("{}: {}", path, error);
+        }
+        for company in &data.companies {
+            if !Path::new(&company.asset_location).exists() {
+                log::error!(
+                    "{}: company {}: asset {} doesn't exist",
+                    path,
+                    company.name,
+                    company.asset_location
+                );
+            }
+        }
+
+        data
+    }
+
+    /// Parses and validates the data. Commodities and companies with bad references are
+    /// left out and reported in the returned errors.
+    pub fn parse(json: &str) -> (Self, Vec<String>) {
+        let mut errors = vec![];
+
+        let raw: RawEconomyData = match serde_json::from_str(json) {
+            Ok(x) => x,
+            Err(e) => {
+                errors.push(format!("invalid data: {}", e));
+                RawEconomyData {
+                    commodities: vec![],
+                    companies: vec![],
+                }
+            }
+        };
+
+        let mut data = EconomyData {
+            commodities: vec![],
+            companies: vec![],
+            kinds: vec![],
+            by_name: HashMap::new(),
+            food: CommodityKind::JOB_OPENING,
+        };
+
+        data.add_commodity(CommodityDescription {
+            name: JOB_OPENING.to_string(),
+            label: "Job opening".to_string(),
+            base_price: Money::ZERO,
+            need: None,
+        });
+
+        for c in raw.commodities {
+            if data.by_name.contains_key(&c.name) {
+                errors.push(format!("commodity {} is defined twice", c.name));
+                continue;
+            }
+            if c.base_price < 0.0 {
+                errors.push(format!("commodity {} has a negative price", c.name));
+                continue;
+            }
+            data.add_commodity(CommodityDescription {
+                name: c.name,
+                label: c.label,
+                base_price: Money::from_cents((c.base_price * 100.0).round() as i64),
+                need: c.need,
+            });
+        }
+
+        data.food = match data.by_name.get(FOOD_COMMODITY) {
+            Some(&kind) => kind,
+            None => {
+                errors.push(format!("commodity {} is missing", FOOD_COMMODITY));
+                data.add_commodity(CommodityDescription {
+                    name: FOOD_COMMODITY.to_string(),
+                    label: FOOD_COMMODITY.to_string(),
+                    base_price: Money::from_units(10),
+                    need: Some(NeedKind::Food),
+                })
+            }
+        };
+
+        let mut ids = HashSet::new();
+        for c in raw.companies {
+            let mut company_errors = vec![];
+
+            if !ids.insert(c.id) {
+                company_errors.push(format!("id {} is already used", c.id));
+            }
+            if c.n_workers <= 0 {
+                company_errors.push("needs at least one worker".to_string());
+            }
+            if c.recipe.complexity <= 0 || c.recipe.storage_multiplier <= 0 {
+                company_errors
+                    .push("complexity and storage_multiplier must be positive".to_string());
+            }
+            if let CompanyKind::Factory { n_trucks: 0 } = c.kind {
+                company_errors.push("a factory needs at least one truck".to_string());
+            }
+            if c.size <= 0.0 {
+                company_errors.push("size must be positive".to_string());
+            }
+
+            let mut resolve = |list: Vec<(String, i32)>| -> Vec<(CommodityKind, i32)> {
+                list.into_iter()
+                    .filter_map(|(name, qty)| {
+                        if qty <= 0 {
+                            company_errors.push(format!("quantity of {} must be positive", name));
+                        }
+                        let kind = data.by_name.get(&name).copied();
+                        if kind.is_none() {
+                            company_errors.push(format!("unknown commodity {}", name));
+                        }
+                        Some((kind?, qty))
+                    })
+                    .collect()
+            };
+            let consumption = resolve(c.recipe.consumption);
+            let production = resolve(c.recipe.production);
+            let shelf = resolve(c.shelf);
+
+            if !shelf.is_empty() && !matches!(c.kind, CompanyKind::Store) {
+                company_errors.push("only stores can have a shelf".to_string());
+            }
+
+            if !company_errors.is_empty() {
+                for e in company_errors {
+                    errors.push(format!("company {}: {}", c.name, e));
+                }
+                continue;
+            }
+
+            data.companies.push(GoodsCompanyDescription {
+                name: c.name,
+                bkind: BuildingKind::Company(c.id),
+                bgen: c.bgen,
+                kind: c.kind,
+                recipe: Recipe {
+                    consumption,
+                    production,
+                    complexity: c.recipe.complexity,
+                    storage_multiplier: c.recipe.storage_multiplier,
+                },
+                shelf,
+                n_workers: c.n_workers,
+                size: c.size,
+                asset_location: c.asset_location,
+            });
+        }
+
+        (data, errors)
+    }
+
+    fn add_commodity(&mut self, descr: CommodityDescription) -> CommodityKind {
+        let kind = CommodityKind(self.commodities.len() as u16);
+        self.by_name.insert(descr.name.clone(), kind);
+        self.kinds.push(kind);
+        self.commodities.push(descr);
+        kind
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EconomyData, DEFAULT_ECONOMY_DATA};
+
+    #[test]
+    fn test_default_data_valid() {
+        let (data, errors) = EconomyData::parse(DEFAULT_ECONOMY_DATA);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert!(!data.companies.is_empty());
+    }
+
+    #[test]
+    fn test_bad_references() {
+        let (data, errors) = EconomyData::parse(
+            r#"{
+                "commodities": [{ "name": "flour", "label": "Flour", "base_price": 5 }],
+                "companies": [{
+                    "id": 0,
+                    "name": "Bakery",
+                    "bgen": "Farm",
+                    "kind": "Store",
+                    "recipe": {
+                        "consumption": [["flour", 1]],
+                        "production": [["croissant", 1]],
+                        "complexity": 1000,
+                        "storage_multiplier": 5
+                    },
+                    "n_workers": 3,
+                    "size": 10.0,
+                    "asset_location": "assets/bakery.png"
+                }]
+            }"#,
+        );
+
+        assert!(data.companies.is_empty());
+        assert_eq!(
+            errors,
+            vec![
+                "commodity bread is missing".to_string(),
+                "company Bakery: unknown commodity croissant".to_string(),
+            ]
+        );
+    }
+}
@@ -1,27 +1,106 @@
-use crate::economy::CommodityKind;
+use crate::economy::{
+    deserialize_known_kinds, known_kind, match_orders, BuyRequest, CommodityKind, Money, SellOffer,
+};
 use crate::SoulID;
 use geom::Vec2;
-use ordered_float::OrderedFloat;
-use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 
-#[derive(Default, Serialize, Deserialize)]
+/// How much the reference price moves towards the price of a trade
+const PRICE_SMOOTHING: f32 = 0.2;
+
+/// How much the reference price moves each time the prices are updated when offer and
+/// demand don't match
+const PRICE_DRIFT: f32 = 0.05;
+
+/// How far from the reference price agents following the market place their orders
+const PRICE_MARGIN: f32 = 0.1;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Order {
+    pub pos: Vec2,
+    pub qty: i32,
+    /// Price per unit. The highest a buyer will pay or the lowest a seller will accept.
+    pub price: Money,
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct SingleMarket {
-    capital: HashMap<SoulID, i32>,
-    buy_orders: HashMap<SoulID, (Vec2, i32)>,
-    sell_orders: HashMap<SoulID, (Vec2, i32)>,
+    capital: BTreeMap<SoulID, i32>,
+    /// Quantity bought from sellers that deliver, not yet brought to the buyer
+    in_transit: BTreeMap<SoulID, i32>,
+    buy_orders: BTreeMap<SoulID, Order>,
+    sell_orders: BTreeMap<SoulID, Order>,
+    /// Reference price per unit, follows the trades and the balance of offer and demand
+    price: Money,
 }
 
 impl SingleMarket {
+    pub fn new(price: Money) -> Self {
+        Self {
+            capital: Default::default(),
+            in_transit: Default::default(),
+            buy_orders: Default::default(),
+            sell_orders: Default::default(),
+            price,
+        }
+    }
+
     pub fn capital(&self, soul: SoulID) -> i32 {
         self.capital.get(&soul).copied().unwrap_or(0)
     }
+
+    pub fn in_transit(&self, soul: SoulID) -> i32 {
+        self.in_transit.get(&soul).copied().unwrap_or(0)
+    }
+
+    pub fn price(&self) -> Money {
+        self.price
+    }
+
+    pub fn n_buy_orders(&self) -> usize {
+        self.buy_orders.len()
+    }
+
+    pub fn n_sell_orders(&self) -> usize {
+        self.sell_orders.len()
+    }
+
+    /// Quantity held by all the agents
+    pub fn stock(&self) -> i32 {
+        self.capital.values().copied().filter(|&x| x > 0).sum()
+    }
+
+    /// Quantity wanted by the buyers minus quantity the sellers can provide
+    fn excess_demand(&self) -> i32 {
+        let demand: i32 = self.buy_orders.values().map(|o| o.qty).sum();
+        let offer: i32 = self
+            .sell_orders
+            .iter()
+            .map(|(&soul, o)| o.qty.min(self.capital(soul)))
+            .sum();
+        demand - offer
+    }
+}
+
+/// Removes the quantity traded from the order, and the order itself once it is complete
+fn fill_order(orders: &mut BTreeMap<SoulID, Order>, soul: SoulID, qty: i32) {
+    if let Some(order) = orders.get_mut(&soul) {
+        order.qty -= qty;
+        if order.qty <= 0 {
+            orders.remove(&soul);
+        }
+    }
 }
 
 register_resource!(Market, "market");
 #[derive(Serialize, Deserialize)]
 pub struct Market {
-    markets: HashMap<CommodityKind, SingleMarket>,
+    #[serde(deserialize_with = "deserialize_markets")]
+    markets: BTreeMap<CommodityKind, SingleMarket>,
+    money: BTreeMap<SoulID, Money>,
+    /// Sellers bringing the goods to the buyers, the buyers only get them once delivered
+    delivering: BTreeSet<SoulID>,
 }
 
 impl Default for Market {
@@ -29,12 +108,27 @@ impl Default for Market {
         Self {
             markets: CommodityKind::values()
                 .iter()
-                .map(|&v| (v, SingleMarket::default()))
+                .map(|&v| (v, SingleMarket::new(v.base_price())))
                 .collect(),
+            money: Default::default(),
+            delivering: Default::default(),
         }
     }
 }
 
+/// The commodities added to the data file since the market was saved start with an empty market
+fn deserialize_markets<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<CommodityKind, SingleMarket>, D::Error> {
+    let mut markets = deserialize_known_kinds(deserializer)?;
+    for &kind in CommodityKind::values() {
+        markets
+            .entry(kind)
+            .or_insert_with(|| SingleMarket::new(kind.base_price()));
+    }
+    Ok(markets)
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct Trade {
     pub buyer: SoulID,
@@ -43,48 +137,220 @@ pub struct Trade {
     pub sell_pos: Vec2,
     pub buy_pos: Vec2,
     pub kind: CommodityKind,
+    /// Price paid per unit
+    pub price: Money,
+}
+
+impl Trade {
+    /// Money paid by the buyer to the seller
+    pub fn cost(&self) -> Money {
+        self.price * self.qty
+    }
+}
+
+/// A [Trade] as saved, its commodity may have been removed from the data file since
+#[derive(Deserialize)]
+pub(crate) struct TradeData {
+    buyer: SoulID,
+    seller: SoulID,
+    qty: i32,
+    sell_pos: Vec2,
+    buy_pos: Vec2,
+    kind: String,
+    price: Money,
+}
+
+impl TradeData {
+    pub(crate) fn known(self) -> Option<Trade> {
+        Some(Trade {
+            buyer: self.buyer,
+            seller: self.seller,
+            qty: self.qty,
+            sell_pos: self.sell_pos,
+            buy_pos: self.buy_pos,
+            kind: known_kind(&self.kind)?,
+            price: self.price,
+        })
+    }
 }
 
 impl Market {
+    pub fn market(&self, kind: CommodityKind) -> Option<&SingleMarket> {
+        self.markets.get(&kind)
+    }
+
     fn m(&mut self, kind: CommodityKind) -> &mut SingleMarket {
-        self.markets.get_mut(&kind).unwrap()
+        self.markets
+            .entry(kind)
+            .or_insert_with(|| SingleMarket::new(kind.base_price()))
     }
 
-    /// Called when an agent tells the world it wants to sell something
+    /// Called when an agent tells the world it wants to sell something at at least `price` per unit.
     /// If an order is already placed, it will be updated.
     /// Beware that you need capital to sell anything, using produce.
-    pub fn sell(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32) {
-        log::info!("{:?} sell {:?} {:?} near {:?}", soul, qty, kind, near);
-        self.m(kind).sell_orders.insert(soul, (near, qty));
+    pub fn sell(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32, price: Money) {
+        log::info!(
+            "{:?} sell {:?} {:?} near {:?} for {}",
+            soul,
+            qty,
+            kind,
+            near,
+            price
+        );
+        self.m(kind).sell_orders.insert(
+            soul,
+            Order {
+                pos: near,
+                qty,
+                price,
+            },
+        );
     }
 
-    pub fn sell_all(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind) {
+    pub fn sell_all(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, price: Money) {
         let c = self.capital(soul, kind);
         if c == 0 {
             return;
         }
-        self.sell(soul, near, kind, c);
+        self.sell(soul, near, kind, c, price);
     }
 
-    /// Called when an agent tells the world it wants to buy something
+    /// Called when an agent tells the world it wants to buy something for at most `price` per unit.
     /// If an order is already placed, it will be updated.
-    pub fn buy(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32) {
-        log::info!("{:?} buy {:?} {:?} near {:?}", soul, qty, kind, near);
+    pub fn buy(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32, price: Money) {
+        log::info!(
+            "{:?} buy {:?} {:?} near {:?} for {}",
+            soul,
+            qty,
+            kind,
+            near,
+            price
+        );
 
-        self.m(kind).buy_orders.insert(soul, (near, qty));
+        self.m(kind).buy_orders.insert(
+            soul,
+            Order {
+                pos: near,
+                qty,
+                price,
+            },
+        );
     }
 
-    pub fn buy_until(&mut self, soul: SoulID, near: Vec2, kind: CommodityKind, qty: i32) {
-        let c = self.capital(soul, kind);
+    pub fn buy_until(
+        &mut self,
+        soul: SoulID,
+        near: Vec2,
+        kind: CommodityKind,
+        qty: i32,
+        price: Money,
+    ) {
+        let c = self.capital(soul, kind) + self.in_transit(soul, kind);
         if c >= qty {
             return;
         }
-        self.buy(soul, near, kind, qty - c);
+        self.buy(soul, near, kind, qty - c, price);
+    }
+
+    /// Removes all the buy and sell orders placed by this agent
+    pub fn cancel_orders(&mut self, soul: SoulID) {
+        for market in self.markets.values_mut() {
+            market.buy_orders.remove(&soul);
+            market.sell_orders.remove(&soul);
+        }
+    }
+
+    /// Removes everything the agent owns and its orders, returns the money it had
+    pub fn remove_agent(&mut self, soul: SoulID) -> Money {
+        for market in self.markets.values_mut() {
+            market.capital.remove(&soul);
+            market.in_transit.remove(&soul);
+            market.buy_orders.remove(&soul);
+            market.sell_orders.remove(&soul);
+        }
+        self.delivering.remove(&soul);
+        self.money.remove(&soul).unwrap_or_default()
+    }
+
+    /// The buy orders placed by the soul, sorted by commodity
+    pub fn buy_orders(&self, soul: SoulID) -> Vec<(CommodityKind, Order)> {
+        self.orders_of(soul, |m| &m.buy_orders)
+    }
+
+    /// The sell orders placed by the soul, sorted by commodity
+    pub fn sell_orders(&self, soul: SoulID) -> Vec<(CommodityKind, Order)> {
+        self.orders_of(soul, |m| &m.sell_orders)
+    }
+
+    fn orders_of(
+        &self,
+        soul: SoulID,
+        orders: impl Fn(&SingleMarket) -> &BTreeMap<SoulID, Order>,
+    ) -> Vec<(CommodityKind, Order)> {
+        let mut v: Vec<_> = self
+            .markets
+            .iter()
+            .filter_map(|(&kind, m)| Some((kind, *orders(m).get(&soul)?)))
+            .collect();
+        v.sort_unstable_by_key(|&(kind, _)| kind.0);
+        v
     }
 
     /// Get the capital that this agent owns
     pub fn capital(&self, soul: SoulID, kind: CommodityKind) -> i32 {
-        self.markets.get(&kind).unwrap().capital(soul)
+        self.market(kind).map_or(0, |m| m.capital(soul))
+    }
+
+    /// Get the quantity this agent bought that is still being delivered
+    pub fn in_transit(&self, soul: SoulID, kind: CommodityKind) -> i32 {
+        self.market(kind).map_or(0, |m| m.in_transit(soul))
+    }
+
+    /// The goods sold by this agent will only reach the buyers once [Market::deliver] is called
+    pub fn set_delivering(&mut self, soul: SoulID) {
+        self.delivering.insert(soul);
+    }
+
+    /// Called when the goods of a trade reach the buyer, they are added to its capital
+    pub fn deliver(&mut self, trade: &Trade) {
+        let market = self.m(trade.kind);
+        *market.in_transit.entry(trade.buyer).or_default() -= trade.qty;
+        if market.in_transit(trade.buyer) == 0 {
+            market.in_transit.remove(&trade.buyer);
+        }
+        *market.capital.entry(trade.buyer).or_default() += trade.qty;
+    }
+
+    /// Reference price per unit of this commodity
+    pub fn price(&self, kind: CommodityKind) -> Money {
+        self.market(kind)
+            .map_or(kind.base_price(), SingleMarket::price)
+    }
+
+    /// Price for a buyer ready to pay a bit more than the reference price
+    pub fn bid_price(&self, kind: CommodityKind) -> Money {
+        self.price(kind).scale(1.0 + PRICE_MARGIN)
+    }
+
+    /// Price for a seller ready to accept a bit less than the reference price
+    pub fn ask_price(&self, kind: CommodityKind) -> Money {
+        self.price(kind).scale(1.0 - PRICE_MARGIN)
+    }
+
+    /// Get the money that this agent owns, it is negative if it is in debt
+    pub fn money(&self, soul: SoulID) -> Money {
+        self.money.get(&soul).copied().unwrap_or_default()
+    }
+
+    /// Called when money enters or leaves the economy, like when an agent is created.
+    pub fn give_money(&mut self, soul: SoulID, amount: Money) {
+        *self.money.entry(soul).or_default() += amount;
+    }
+
+    /// Moves money from one agent to another, the payer can go into debt
+    pub fn pay(&mut self, from: SoulID, to: SoulID, amount: Money) {
+        *self.money.entry(from).or_default() -= amount;
+        *self.money.entry(to).or_default() += amount;
     }
 
     /// Called whenever an agent (like a farm) produces something on it's own
@@ -97,79 +363,111 @@ impl Market {
         *v
     }
 
-    /// Returns a list of buy and sell orders matched together.
-    /// A trade updates the buy and sell orders from the market, and the capital of the buyers and sellers.
-    /// A trade can only be completed if the seller has enough capital.
+    /// Moves the reference prices up where the demand exceeds the offer and down where the
+    /// offer exceeds the demand.
+    pub fn update_prices(&mut self) {
+        for market in self.markets.values_mut() {
+            let excess = market.excess_demand();
+            let factor = match excess {
+                x if x > 0 => 1.0 + PRICE_DRIFT,
+                x if x < 0 => 1.0 - PRICE_DRIFT,
+                _ => continue,
+            };
+            if market.price == Money::ZERO {
+                continue;
+            }
+            market.price = market.price.scale(factor).max(Money::from_cents(1));
+        }
+    }
+
+    /// Returns a list of buy and sell orders matched together, see [match_orders].
+    /// A trade updates the buy and sell orders from the market, the capital of the buyers and sellers
+    /// and their money.
+    /// A trade can only be completed if the seller has enough capital, if the buyer can afford it
+    /// and if the buyer's price is at least the seller's price.
+    /// The price paid is halfway between the two.
+    /// The goods of sellers that deliver are kept in transit until [Market::deliver] is called.
     pub fn make_trades(&mut self) -> impl Iterator<Item = Trade> + '_ {
         let mut all_trades = vec![];
-        let mut potential = vec![];
-
-        for (&kind, market) in &mut self.markets {
-            // Naive O(n²) alg
-            for (&seller, &(sell_pos, qty_sell)) in &market.sell_orders {
-                let capital_sell = market.capital(seller);
-                if qty_sell > capital_sell {
-                    continue;
-                }
-                for (&buyer, &(buy_pos, qty_buy)) in &market.buy_orders {
-                    if seller == buyer {
-                        log::warn!(
-                            "{:?} is both selling and buying same commodity: {:?}",
-                            seller,
-                            kind
-                        );
-                        continue;
-                    }
-                    if qty_buy <= qty_sell {
-                        let score = sell_pos.distance2(buy_pos);
-                        potential.push((
-                            score,
-                            Trade {
-                                buyer,
-                                seller,
-                                qty: qty_buy,
-                                sell_pos,
-                                buy_pos,
-                                kind,
-                            },
-                            qty_buy == qty_sell,
-                        ))
-                    }
+
+        let Market {
+            markets,
+            money,
+            delivering,
+        } = self;
+
+        for (&kind, market) in markets {
+            let sellers: Vec<SoulID> = market.sell_orders.keys().copied().collect();
+            let buyers: Vec<SoulID> = market.buy_orders.keys().copied().collect();
+
+            for &soul in &sellers {
+                if market.buy_orders.contains_key(&soul) {
+                    log::warn!(
+                        "{:?} is both selling and buying same commodity: {:?}",
+                        soul,
+                        kind
+                    );
                 }
             }
-            potential.sort_unstable_by_key(|(x, _, _)| OrderedFloat(*x));
-            let mut already_sold = HashSet::new();
-            let SingleMarket {
-                buy_orders,
-                sell_orders,
-                capital,
-            } = market;
-
-            all_trades.extend(
-                potential
-                    .drain(..)
-                    .filter(move |(_, trade, complete)| {
-                        let ok =
-                            already_sold.insert(trade.buyer) && already_sold.insert(trade.seller);
-                        if !ok {
-                            return false;
-                        }
-                        buy_orders.remove(&trade.buyer);
-                        if *complete {
-                            sell_orders.remove(&trade.seller);
-                        } else if let Some((_, qty)) = sell_orders.get_mut(&trade.seller) {
-                            *qty -= trade.qty
-                        }
-
-                        *capital.entry(trade.buyer).or_default() += trade.qty;
-                        *capital
-                            .get_mut(&trade.seller)
-                            .expect("what is this ? a 0 qty trade ?") -= trade.qty;
-
-                        true
-                    })
-                    .map(|(_, x, _)| x),
-            )
+
+            let sells: Vec<SellOffer> = market
+                .sell_orders
+                .iter()
+                .map(|(&soul, o)| SellOffer {
+                    pos: o.pos,
+                    qty: o.qty.min(market.capital(soul)).max(0),
+                    price: o.price,
+                })
+                .collect();
+            let buys: Vec<BuyRequest> = market
+                .buy_orders
+                .iter()
+                .map(|(soul, o)| BuyRequest {
+                    pos: o.pos,
+                    qty: if market.sell_orders.contains_key(soul) {
+                        0
+                    } else {
+                        o.qty
+                    },
+                    price: o.price,
+                    money: money.get(soul).copied().unwrap_or_default(),
+                })
+                .collect();
+
+            for m in match_orders(&sells, &buys) {
+                let trade = Trade {
+                    buyer: buyers[m.buyer],
+                    seller: sellers[m.seller],
+                    qty: m.qty,
+                    sell_pos: sells[m.seller].pos,
+                    buy_pos: buys[m.buyer].pos,
+                    kind,
+                    price: m.price,
+                };
+
+                let cost = trade.cost();
+                *money.entry(trade.buyer).or_default() -= cost;
+                *money.entry(trade.seller).or_default() += cost;
+
+                fill_order(&mut market.buy_orders, trade.buyer, trade.qty);
+                fill_order(&mut market.sell_orders, trade.seller, trade.qty);
+
+                let received = if delivering.contains(&trade.seller) {
+                    &mut market.in_transit
+                } else {
+                    &mut market.capital
+                };
+                *received.entry(trade.buyer).or_default() += trade.qty;
+                *market
+                    .capital
+                    .get_mut(&trade.seller)
+                    .expect("what is this ? a 0 qty trade ?") -= trade.qty;
+
+                market.price =
+                    market.price.scale(1.0 - PRICE_SMOOTHING) + trade.price.scale(PRICE_SMOOTHING);
+
+                all_trades.push(trade);
+            }
         }
 
         all_trades.into_iter()
@@ -179,13 +477,14 @@ impl Market {
 #[cfg(test)]
 mod tests {
     use super::Market;
-    use crate::economy::CommodityKind;
+    use crate::economy::{CommodityKind, Money, Sold};
+    use crate::utils::mk_ent;
     use crate::SoulID;
     use geom::{vec2, Vec2};
-    use legion::Entity;
+    use legion::serialize::Canon;
 
-    fn mk_ent(id: u64) -> Entity {
-        unsafe { std::mem::transmute(id) }
+    fn cereal() -> CommodityKind {
+        CommodityKind::from_name("cereal").unwrap()
     }
 
     #[test]
@@ -196,12 +495,15 @@ mod tests {
 
         let mut m = Market::default();
 
-        m.produce(seller, CommodityKind::Cereal, 3);
-        m.produce(seller_far, CommodityKind::Cereal, 3);
+        m.produce(seller, cereal(), 3);
+        m.produce(seller_far, cereal(), 3);
+        m.give_money(buyer, Money::from_units(100));
 
-        m.buy(buyer, Vec2::ZERO, CommodityKind::Cereal, 2);
-        m.sell(seller, Vec2::UNIT_X, CommodityKind::Cereal, 3);
-        m.sell(seller_far, vec2(10.0, 10.0), CommodityKind::Cereal, 3);
+        let price = Money::from_units(10);
+
+        m.buy(buyer, Vec2::ZERO, cereal(), 2, price);
+        m.sell(seller, Vec2::UNIT_X, cereal(), 3, price);
+        m.sell(seller_far, vec2(10.0, 10.0), cereal(), 3, price);
 
         let trades = m.make_trades().collect::<Vec<_>>();
 
@@ -210,5 +512,105 @@ mod tests {
         assert_eq!(t0.seller, seller);
         assert_eq!(t0.buyer, buyer);
         assert_eq!(t0.qty, 2);
+        assert_eq!(t0.price, price);
+        assert_eq!(m.money(buyer), Money::from_units(80));
+        assert_eq!(m.money(seller), Money::from_units(20));
+    }
+
+    #[test]
+    fn test_prices() {
+        let seller = SoulID(mk_ent(1));
+        let buyer = SoulID(mk_ent(2));
+
+        let mut m = Market::default();
+
+        m.produce(seller, cereal(), 1);
+        m.give_money(buyer, Money::from_units(8));
+
+        // too expensive for the buyer's limit
+        m.sell(seller, Vec2::UNIT_X, cereal(), 1, Money::from_units(12));
+        m.buy(buyer, Vec2::ZERO, cereal(), 1, Money::from_units(10));
+        assert_eq!(m.make_trades().count(), 0);
+
+        // too expensive for the buyer's money
+        m.sell(seller, Vec2::UNIT_X, cereal(), 1, Money::from_units(8));
+        assert_eq!(m.make_trades().count(), 0);
+
+        m.give_money(buyer, Money::from_units(1));
+        let trades = m.make_trades().collect::<Vec<_>>();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].price, Money::from_units(9));
+        assert_eq!(m.money(buyer), Money::ZERO);
+        assert_eq!(m.capital(buyer, cereal()), 1);
+    }
+
+    #[test]
+    fn test_delivery() {
+        let seller = SoulID(mk_ent(1));
+        let buyer = SoulID(mk_ent(2));
+
+        let mut m = Market::default();
+        m.set_delivering(seller);
+
+        m.produce(seller, cereal(), 3);
+        m.give_money(buyer, Money::from_units(100));
+
+        let price = Money::from_units(10);
+        m.sell_all(seller, Vec2::UNIT_X, cereal(), price);
+        m.buy_until(buyer, Vec2::ZERO, cereal(), 2, price);
+
+        let trades = m.make_trades().collect::<Vec<_>>();
+        assert_eq!(trades.len(), 1);
+        assert_eq!(m.capital(buyer, cereal()), 0);
+        assert_eq!(m.in_transit(buyer, cereal()), 2);
+
+        // what is on its way counts towards the stock wanted
+        m.buy_until(buyer, Vec2::ZERO, cereal(), 2, price);
+        assert_eq!(m.make_trades().count(), 0);
+
+        m.deliver(&trades[0]);
+        assert_eq!(m.capital(buyer, cereal()), 2);
+        assert_eq!(m.in_transit(buyer, cereal()), 0);
+    }
+
+    /// Renames the commodity in the saved data, as if it was removed from the data file
+    fn forget_cereal(data: Vec<u8>) -> Vec<u8> {
+        let mut data = data;
+        let at = data
+            .windows(6)
+            .position(|w| w == b"cereal")
+            .expect("cereal is not saved");
+        data[at..at + 6].copy_from_slice(b"cerea_");
+        data
+    }
+
+    #[test]
+    fn test_unknown_commodities() {
+        let seller = SoulID(mk_ent(1));
+        let buyer = SoulID(mk_ent(2));
+
+        let mut m = Market::default();
+        m.produce(seller, cereal(), 3);
+        m.give_money(buyer, Money::from_units(100));
+        m.sell(seller, Vec2::UNIT_X, cereal(), 3, Money::from_units(10));
+        m.buy(buyer, Vec2::ZERO, cereal(), 2, Money::from_units(10));
+        let trades: Vec<_> = m.make_trades().collect();
+        assert_eq!(trades.len(), 1);
+
+        let (m, sold) = legion::serialize::set_entity_serializer(&Canon::default(), || {
+            let m = forget_cereal(bincode::serialize(&m).unwrap());
+            let sold = forget_cereal(bincode::serialize(&Sold(trades)).unwrap());
+            (
+                bincode::deserialize::<Market>(&m).unwrap(),
+                bincode::deserialize::<Sold>(&sold).unwrap(),
+            )
+        });
+
+        // The market of the commodity is back, empty
+        assert_eq!(m.capital(seller, cereal()), 0);
+        assert_eq!(m.price(cereal()), cereal().base_price());
+        assert!(m.market(cereal()).is_some());
+        assert_eq!(m.money(buyer), Money::from_units(80));
+        assert!(sold.0.is_empty());
     }
 }
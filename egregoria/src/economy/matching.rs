@@ -0,0 +1,350 @@
+use crate::economy::Money;
+use flat_spatial::storage::{CellIdx, Storage};
+use flat_spatial::SparseGrid;
+use geom::Vec2;
+use ordered_float::OrderedFloat;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+/// Size of the cells of the grid used to find the closest sellers, in meters
+const MATCHING_CELL_SIZE: i32 = 200;
+
+#[derive(Copy, Clone, Debug)]
+pub struct SellOffer {
+    pub pos: Vec2,
+    /// Quantity the seller can actually provide
+    pub qty: i32,
+    pub price: Money,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BuyRequest {
+    pub pos: Vec2,
+    pub qty: i32,
+    pub price: Money,
+    /// Money the buyer can spend
+    pub money: Money,
+}
+
+/// A part of a buy request filled by a sell offer, referenced by their index.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub buyer: usize,
+    pub seller: usize,
+    pub qty: i32,
+    /// Price per unit, halfway between what the buyer and the seller asked for
+    pub price: Money,
+}
+
+/// Remaining quantities and money while matching, shared by both algorithms so they fill the
+/// orders exactly the same way.
+struct Book {
+    need: Vec<i32>,
+    money: Vec<Money>,
+    supply: Vec<i32>,
+    matches: Vec<Match>,
+}
+
+impl Book {
+    fn new(sells: &[SellOffer], buys: &[BuyRequest]) -> Self {
+        Self {
+            need: buys.iter().map(|b| b.qty).collect(),
+            money: buys.iter().map(|b| b.money).collect(),
+            supply: sells.iter().map(|s| s.qty).collect(),
+            matches: vec![],
+        }
+    }
+
+    /// Fills as much as possible of the buyer's need with the seller's supply.
+    /// Returns true if the buyer still needs something.
+    fn fill(
+        &mut self,
+        sells: &[SellOffer],
+        buys: &[BuyRequest],
+        buyer: usize,
+        seller: usize,
+    ) -> bool {
+        let price = buys[buyer].price.midpoint(sells[seller].price);
+        let mut qty = self.need[buyer].min(self.supply[seller]);
+        if price > Money::ZERO {
+            qty = qty.min((self.money[buyer].cents() / price.cents()).min(i32::MAX as i64) as i32);
+        }
+
+        if qty > 0 {
+            self.need[buyer] -= qty;
+            self.supply[seller] -= qty;
+            self.money[buyer] -= price * qty;
+            self.matches.push(Match {
+                buyer,
+                seller,
+                qty,
+                price,
+            });
+        }
+
+        self.need[buyer] > 0
+    }
+}
+
+fn acceptable(sell: &SellOffer, buy: &BuyRequest) -> bool {
+    sell.qty > 0 && buy.qty > 0 && sell.price <= buy.price
+}
+
+/// Matches the buyers with the closest sellers first, over all pairs. A buyer can be served by
+/// several sellers and a seller can serve several buyers.
+///
+/// Pairs are considered by increasing distance, ties are broken by buyer then seller index.
+/// Each pair fills as much as the remaining need, supply and money of the buyer allow.
+/// The result is the same as [match_orders_naive] but the pairs are enumerated lazily, by walking
+/// a grid around each buyer.
+pub fn match_orders(sells: &[SellOffer], buys: &[BuyRequest]) -> Vec<Match> {
+    let mut book = Book::new(sells, buys);
+    if sells.is_empty() || buys.is_empty() {
+        return book.matches;
+    }
+
+    let mut grid: SparseGrid<usize> = SparseGrid::new(MATCHING_CELL_SIZE);
+    for (i, s) in sells.iter().enumerate() {
+        if s.qty > 0 {
+            grid.insert(s.pos, i);
+        }
+    }
+
+    let cells = grid.storage().cells().keys();
+    let bounds = cells.fold(
+        ((i32::MAX, i32::MAX), (i32::MIN, i32::MIN)),
+        |(ll, ur), &(x, y)| ((ll.0.min(x), ll.1.min(y)), (ur.0.max(x), ur.1.max(y))),
+    );
+
+    let mut streams: Vec<NearestSellers> = buys
+        .iter()
+        .map(|b| NearestSellers::new(&grid, bounds, b.pos))
+        .collect();
+
+    let mut heap = BinaryHeap::with_capacity(buys.len());
+    for (i, stream) in streams.iter_mut().enumerate() {
+        if buys[i].qty <= 0 {
+            continue;
+        }
+        if let Some((d, s)) = stream.next(&grid, |s| acceptable(&sells[s], &buys[i])) {
+            heap.push(Reverse((OrderedFloat(d), i, s)));
+        }
+    }
+
+    while let Some(Reverse((_, buyer, seller))) = heap.pop() {
+        if book.supply[seller] > 0 && !book.fill(sells, buys, buyer, seller) {
+            continue;
+        }
+        let next = streams[buyer].next(&grid, |s| acceptable(&sells[s], &buys[buyer]));
+        if let Some((d, s)) = next {
+            heap.push(Reverse((OrderedFloat(d), buyer, s)));
+        }
+    }
+
+    book.matches
+}
+
+/// Reference implementation of [match_orders] that sorts every pair of orders, in O(n²).
+pub fn match_orders_naive(sells: &[SellOffer], buys: &[BuyRequest]) -> Vec<Match> {
+    let mut book = Book::new(sells, buys);
+
+    let mut pairs = vec![];
+    for (buyer, buy) in buys.iter().enumerate() {
+        for (seller, sell) in sells.iter().enumerate() {
+            if acceptable(sell, buy) {
+                pairs.push((OrderedFloat(buy.pos.distance2(sell.pos)), buyer, seller));
+            }
+        }
+    }
+    pairs.sort_unstable();
+
+    for (_, buyer, seller) in pairs {
+        if book.need[buyer] > 0 && book.supply[seller] > 0 {
+            book.fill(sells, buys, buyer, seller);
+        }
+    }
+
+    book.matches
+}
+
+/// Enumerates the sellers by increasing distance to a position, by scanning rings of cells
+/// further and further away.
+struct NearestSellers {
+    pos: Vec2,
+    center: CellIdx,
+    /// Next ring to scan
+    ring: i32,
+    /// Last ring containing any cell with sellers
+    max_ring: i32,
+    /// Lowest and highest cells containing sellers
+    bounds: (CellIdx, CellIdx),
+    /// Sellers found but not returned yet, closest last
+    found: Vec<(f32, usize)>,
+}
+
+impl NearestSellers {
+    fn new(grid: &SparseGrid<usize>, bounds: (CellIdx, CellIdx), pos: Vec2) -> Self {
+        let (ll, ur) = bounds;
+        let center = grid.storage().cell_id(pos);
+        let max_ring = (center.0 - ll.0)
+            .max(ur.0 - center.0)
+            .max(center.1 - ll.1)
+            .max(ur.1 - center.1);
+        Self {
+            pos,
+            center,
+            ring: 0,
+            max_ring,
+            bounds,
+            found: vec![],
+        }
+    }
+
+    /// Distance under which no seller remains in the rings that were not scanned yet
+    fn scanned_radius(&self) -> f32 {
+        if self.ring == 0 {
+            return 0.0;
+        }
+        let cs = MATCHING_CELL_SIZE as f32;
+        let r = self.ring - 1;
+        let ll_x = (self.center.0 - r) as f32 * cs;
+        let ll_y = (self.center.1 - r) as f32 * cs;
+        let ur_x = (self.center.0 + r + 1) as f32 * cs;
+        let ur_y = (self.center.1 + r + 1) as f32 * cs;
+
+        (self.pos.x - ll_x)
+            .min(ur_x - self.pos.x)
+            .min(self.pos.y - ll_y)
+            .min(ur_y - self.pos.y)
+            .max(0.0)
+    }
+
+    /// Returns the next closest accepted seller with its squared distance
+    fn next(
+        &mut self,
+        grid: &SparseGrid<usize>,
+        accept: impl Fn(usize) -> bool,
+    ) -> Option<(f32, usize)> {
+        loop {
+            if let Some(&(d, s)) = self.found.last() {
+                let radius = self.scanned_radius();
+                if self.ring > self.max_ring || d < radius * radius {
+                    self.found.pop();
+                    return Some((d, s));
+                }
+            } else if self.ring > self.max_ring {
+                return None;
+            }
+
+            self.scan_ring(grid, &accept);
+            self.ring += 1;
+        }
+    }
+
+    fn scan_ring(&mut self, grid: &SparseGrid<usize>, accept: &impl Fn(usize) -> bool) {
+        let (cx, cy) = self.center;
+        let r = self.ring;
+
+        let before = self.found.len();
+        let mut scan_cell = |x: i32, y: i32| {
+            if x < self.bounds.0 .0 || x > self.bounds.1 .0 {
+                return;
+            }
+            if y < self.bounds.0 .1 || y > self.bounds.1 .1 {
+                return;
+            }
+            let cell = unwrap_or!(grid.storage().cell((x, y)), return);
+            for &(h, pos) in &cell.objs {
+                let s = *unwrap_or!(grid.get(h), continue).1;
+                if accept(s) {
+                    self.found.push((self.pos.distance2(pos), s));
+                }
+            }
+        };
+
+        if r == 0 {
+            scan_cell(cx, cy);
+        } else {
+            for x in cx - r..=cx + r {
+                scan_cell(x, cy - r);
+                scan_cell(x, cy + r);
+            }
+            for y in cy - r + 1..cy + r {
+                scan_cell(cx - r, y);
+                scan_cell(cx + r, y);
+            }
+        }
+
+        if self.found.len() > before {
+            self.found
+                .sort_unstable_by_key(|&(d, s)| Reverse((OrderedFloat(d), s)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_orders, match_orders_naive, BuyRequest, SellOffer};
+    use crate::economy::Money;
+    use geom::vec2;
+    use rand::rngs::SmallRng;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test_same_as_naive() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        let sells: Vec<_> = (0..300)
+            .map(|_| SellOffer {
+                pos: vec2(
+                    rng.gen_range(-2000.0..2000.0),
+                    rng.gen_range(-2000.0..2000.0),
+                ),
+                qty: rng.gen_range(0..4),
+                price: Money::from_cents(rng.gen_range(500..1500)),
+            })
+            .collect();
+        let buys: Vec<_> = (0..500)
+            .map(|_| BuyRequest {
+                pos: vec2(
+                    rng.gen_range(-2000.0..2000.0),
+                    rng.gen_range(-2000.0..2000.0),
+                ),
+                qty: rng.gen_range(1..4),
+                price: Money::from_cents(rng.gen_range(500..1500)),
+                money: Money::from_cents(rng.gen_range(0..4000)),
+            })
+            .collect();
+
+        let naive = match_orders_naive(&sells, &buys);
+        assert!(!naive.is_empty());
+        assert_eq!(match_orders(&sells, &buys), naive);
+    }
+
+    #[test]
+    fn test_partial_fills() {
+        let sells = [
+            SellOffer {
+                pos: vec2(1.0, 0.0),
+                qty: 2,
+                price: Money::from_units(10),
+            },
+            SellOffer {
+                pos: vec2(1000.0, 0.0),
+                qty: 5,
+                price: Money::from_units(10),
+            },
+        ];
+        let buys = [BuyRequest {
+            pos: vec2(0.0, 0.0),
+            qty: 4,
+            price: Money::from_units(10),
+            money: Money::from_units(100),
+        }];
+
+        let matches = match_orders(&sells, &buys);
+        assert_eq!(matches.len(), 2);
+        assert_eq!((matches[0].seller, matches[0].qty), (0, 2));
+        assert_eq!((matches[1].seller, matches[1].qty), (1, 2));
+    }
+}
@@ -0,0 +1,230 @@
+use crate::economy::{Bought, CommodityKind, Market, Sold, Trade};
+use crate::SoulID;
+use geom::Vec2;
+use legion::{Entity, IntoQuery, Registry, World};
+use serde::{Deserialize, Serialize};
+
+/// The commodities were an enum saved as the index of the variant, in this order
+const KINDS_V0: [&str; 22] = [
+    "job_opening",
+    "cereal",
+    "flour",
+    "bread",
+    "vegetable",
+    "carcass",
+    "raw_meat",
+    "meat",
+    "tree_log",
+    "wood_plank",
+    "iron_ore",
+    "metal",
+    "rare_metal",
+    "high_tech_product",
+    "furniture",
+    "flower",
+    "wool",
+    "textile",
+    "cloth",
+    "oil",
+    "polyester",
+    "petrol",
+];
+
+/// None if the commodity was removed from the data file since
+fn kind_v0(idx: u32) -> Option<CommodityKind> {
+    CommodityKind::from_name(KINDS_V0.get(idx as usize)?)
+}
+
+/// Hash maps are saved as their length followed by the entries, like a vec of pairs
+#[derive(Serialize, Deserialize)]
+struct SingleMarketV0 {
+    capital: Vec<(SoulID, i32)>,
+    buy_orders: Vec<(SoulID, (Vec2, i32))>,
+    sell_orders: Vec<(SoulID, (Vec2, i32))>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MarketV0 {
+    markets: Vec<(u32, SingleMarketV0)>,
+}
+
+// Orders are placed at the price agents following the market would pay.
+// Nobody has money yet, it comes with the next wages.
+register_migration!("market", 0, |data| {
+    let old: MarketV0 = bincode::deserialize(data).ok()?;
+    let mut market = Market::default();
+    for (idx, m) in old.markets {
+        let kind = unwrap_or!(kind_v0(idx), {
+            log::warn!("dropping the market of the unknown commodity {}", idx);
+            continue;
+        });
+        for (soul, qty) in m.capital {
+            market.produce(soul, kind, qty);
+        }
+        for (soul, (pos, qty)) in m.buy_orders {
+            let price = market.bid_price(kind);
+            market.buy(soul, pos, kind, qty, price);
+        }
+        for (soul, (pos, qty)) in m.sell_orders {
+            let price = market.ask_price(kind);
+            market.sell(soul, pos, kind, qty, price);
+        }
+    }
+    bincode::serialize(&market).ok()
+});
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct TradeV0 {
+    buyer: SoulID,
+    seller: SoulID,
+    qty: i32,
+    sell_pos: Vec2,
+    buy_pos: Vec2,
+    kind: u32,
+}
+
+impl TradeV0 {
+    /// The trade is made at the base price of the commodity
+    fn convert(self) -> Option<Trade> {
+        let kind = kind_v0(self.kind)?;
+        Some(Trade {
+            buyer: self.buyer,
+            seller: self.seller,
+            qty: self.qty,
+            sell_pos: self.sell_pos,
+            buy_pos: self.buy_pos,
+            kind,
+            price: kind.base_price(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SoldV0(Vec<TradeV0>);
+
+#[derive(Serialize, Deserialize)]
+struct BoughtV0(Vec<(u32, Vec<TradeV0>)>);
+
+/// Reads the trade components with their previous layout, `key` gives the registry key of a
+/// component from its name
+pub(crate) fn register_world_v0(registry: &mut Registry<u64>, key: impl Fn(&'static str) -> u64) {
+    registry.register::<SoldV0>(key("Sold"));
+    registry.register::<BoughtV0>(key("Bought"));
+}
+
+/// Replaces the trade components read by [register_world_v0] with the current ones
+pub(crate) fn convert_world_v0(world: &mut World) {
+    let sold: Vec<(Entity, Sold)> = <(Entity, &SoldV0)>::query()
+        .iter(world)
+        .map(|(&e, s)| (e, Sold(s.0.iter().filter_map(|t| t.convert()).collect())))
+        .collect();
+    for (e, s) in sold {
+        let mut entry = unwrap_or!(world.entry(e), continue);
+        entry.remove_component::<SoldV0>();
+        entry.add_component(s);
+    }
+
+    let bought: Vec<(Entity, Bought)> = <(Entity, &BoughtV0)>::query()
+        .iter(world)
+        .map(|(&e, b)| {
+            let trades = b
+                .0
+                .iter()
+                .filter_map(|(idx, trades)| {
+                    let trades: Vec<Trade> = trades.iter().filter_map(|t| t.convert()).collect();
+                    Some((kind_v0(*idx)?, trades))
+                })
+                .collect();
+            (e, Bought(trades))
+        })
+        .collect();
+    for (e, b) in bought {
+        let mut entry = unwrap_or!(world.entry(e), continue);
+        entry.remove_component::<BoughtV0>();
+        entry.add_component(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BoughtV0, MarketV0, SingleMarketV0, SoldV0, TradeV0};
+    use crate::economy::{Bought, CommodityKind, Market, Money, Sold};
+    use crate::SoulID;
+    use common::saveload::Section;
+    use geom::vec2;
+    use legion::serialize::Canon;
+    use legion::{any, EntityStore, World};
+
+    #[test]
+    fn test_migrate_market() {
+        let mut world = World::default();
+        let a = SoulID(world.push((0u32,)));
+        let b = SoulID(world.push((1u32,)));
+
+        let old = MarketV0 {
+            markets: vec![(
+                3,
+                SingleMarketV0 {
+                    capital: vec![(a, 5)],
+                    buy_orders: vec![(b, (vec2(1.0, 2.0), 2))],
+                    sell_orders: vec![(a, (vec2(3.0, 4.0), 5))],
+                },
+            )],
+        };
+
+        let market = legion::serialize::set_entity_serializer(&Canon::default(), || {
+            let mut section = Section::from_value("market", 0, &old).unwrap();
+            assert!(crate::migrate(&mut section));
+            section.read::<Market>().unwrap()
+        });
+
+        let bread = CommodityKind::from_name("bread").unwrap();
+        assert_eq!(market.capital(a, bread), 5);
+        assert_eq!(market.buy_orders(b)[0].0, bread);
+        assert_eq!(market.buy_orders(b)[0].1.qty, 2);
+        assert_eq!(market.sell_orders(a)[0].1.qty, 5);
+        assert_eq!(market.money(a), Money::ZERO);
+    }
+
+    #[test]
+    fn test_migrate_trades() {
+        let mut souls = World::default();
+        let a = SoulID(souls.push((0u32,)));
+
+        let trade = TradeV0 {
+            buyer: a,
+            seller: a,
+            qty: 2,
+            sell_pos: vec2(0.0, 0.0),
+            buy_pos: vec2(1.0, 0.0),
+            kind: 3,
+        };
+        let mut world = World::default();
+        let e = world.push((
+            SoldV0(vec![trade]),
+            // A commodity that doesn't exist anymore
+            BoughtV0(vec![(3, vec![trade]), (99, vec![trade])]),
+        ));
+
+        let canon = Canon::default();
+        let data = bincode::serialize(&world.as_serializable(any(), &crate::registry_v0(), &canon))
+            .unwrap();
+        let mut section = Section::new("world", 0, data);
+        assert!(crate::migrate(&mut section));
+        let world: World = section
+            .read_seed(crate::registry().as_deserialize(&canon))
+            .unwrap();
+
+        let bread = CommodityKind::from_name("bread").unwrap();
+        let entry = world.entry_ref(e).unwrap();
+        let sold = entry.get_component::<Sold>().unwrap();
+        assert_eq!(sold.0.len(), 1);
+        assert_eq!(sold.0[0].kind, bread);
+        assert_eq!(sold.0[0].price, bread.base_price());
+        assert_eq!(sold.0[0].buyer, a);
+
+        let bought = entry.get_component::<Bought>().unwrap();
+        assert_eq!(bought.0.len(), 1);
+        assert_eq!(bought.0[&bread].len(), 1);
+    }
+}
@@ -1,74 +1,187 @@
 use legion::world::SubWorld;
 use legion::{system, EntityStore};
-use serde::{Deserialize, Serialize};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+mod data;
 mod market;
+mod matching;
+pub(crate) mod migrations;
+mod money;
+mod retail;
+mod stats;
 
 use crate::SoulID;
-use imgui::__core::fmt::Formatter;
+use common::{GameTime, SECONDS_PER_HOUR};
+pub use data::*;
+use geom::Vec2;
+use map_model::BuildingID;
 pub use market::*;
-use std::collections::HashMap;
-use std::fmt::Display;
+pub use matching::*;
+pub use money::*;
+use ordered_float::OrderedFloat;
+pub use retail::*;
+pub use stats::*;
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
 
 pub trait Commodity {}
 impl<T> Commodity for T {}
 
 pub trait CommodityList {}
 
-#[derive(Default, Serialize, Deserialize)]
-pub struct Sold(pub Vec<Trade>);
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Sold(#[serde(deserialize_with = "deserialize_trades")] pub Vec<Trade>);
 
-#[derive(Default, Serialize, Deserialize)]
-pub struct Bought(pub HashMap<CommodityKind, Vec<Trade>>);
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Bought(
+    #[serde(deserialize_with = "deserialize_bought")] pub BTreeMap<CommodityKind, Vec<Trade>>,
+);
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Workers(pub Vec<SoulID>);
 
-macro_rules! commodity {
-    {$($member:tt => $display:literal),*,} => {
-        #[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
-        pub enum CommodityKind {
-            $($member),*
-        }
-        impl CommodityKind {
-            pub fn values() -> &'static [Self] {
-                use CommodityKind::*;
-                &[$($member),*]
-            }
-        }
-        impl Display for CommodityKind {
-            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-                match self {
-                    $(Self::$member => f.write_str($display)),*
-                }
+/// Goods loaded in a truck, with the building of the buyer they are delivered to
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Cargo(#[serde(deserialize_with = "deserialize_cargo")] pub Vec<(BuildingID, Trade)>);
+
+impl Cargo {
+    pub fn units(&self) -> i32 {
+        self.0.iter().map(|(_, t)| t.qty).sum()
+    }
+
+    /// Removes and returns the goods delivered to this building
+    pub fn unload(&mut self, building: BuildingID) -> Vec<Trade> {
+        let mut unloaded = vec![];
+        self.0.retain(|&(b, trade)| {
+            if b == building {
+                unloaded.push(trade);
+                return false;
             }
-        }
-    };
+            true
+        });
+        unloaded
+    }
+
+    /// The closest building left to deliver to
+    pub fn closest_stop(&self, from: Vec2) -> Option<BuildingID> {
+        self.0
+            .iter()
+            .min_by_key(|(_, t)| OrderedFloat(t.buy_pos.distance2(from)))
+            .map(|&(b, _)| b)
+    }
 }
 
-commodity! {
-    JobOpening => "Job opening",
-    Cereal => "Cereal",
-    Flour => "Flour",
-    Bread => "Bread",
-    Vegetable => "Vegetables",
-    Carcass => "Carcass",
-    RawMeat => "Raw meat",
-    Meat => "Meat",
-    TreeLog => "Tree Log",
-    WoodPlank => "Wood Planks",
-    IronOre => "Iron Ore",
-    Metal => "Metal",
-    RareMetal => "Rare Metal",
-    HighTechProduct => "High Tech Product",
-    Furniture => "Furniture",
-    Flower => "Flower",
-    Wool => "Wool",
-    Textile => "Textile",
-    Cloth => "Cloth",
-    Oil => "Oil",
-    Polyester => "Polyester",
-    Petrol => "Petrol",
+/// A kind of goods traded on the market, described in the economy data file.
+/// It is saved as its name so that the data file can be reordered.
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct CommodityKind(u16);
+
+impl CommodityKind {
+    /// Workers are hired by buying a job opening from a company, it always exists.
+    pub const JOB_OPENING: CommodityKind = CommodityKind(0);
+
+    pub fn values() -> &'static [Self] {
+        &economy_data().kinds
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        economy_data().by_name.get(name).copied()
+    }
+
+    /// The commodity humans buy to eat
+    pub fn food() -> Self {
+        economy_data().food
+    }
+
+    pub fn description(self) -> &'static CommodityDescription {
+        &economy_data().commodities[self.0 as usize]
+    }
+
+    pub fn name(self) -> &'static str {
+        &self.description().name
+    }
+
+    /// Price a market starts at
+    pub fn base_price(self) -> Money {
+        self.description().base_price
+    }
+}
+
+impl Display for CommodityKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.description().label)
+    }
+}
+
+impl Debug for CommodityKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for CommodityKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for CommodityKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        CommodityKind::from_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown commodity {}", name)))
+    }
+}
+
+/// None if the commodity was removed from the data file since it was saved
+pub(crate) fn known_kind(name: &str) -> Option<CommodityKind> {
+    let kind = CommodityKind::from_name(name);
+    if kind.is_none() {
+        log::warn!("skipping the unknown commodity {}", name);
+    }
+    kind
+}
+
+/// Reads a map of commodities, the ones removed from the data file since it was saved are skipped
+pub(crate) fn deserialize_known_kinds<'de, D: Deserializer<'de>, V: Deserialize<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<CommodityKind, V>, D::Error> {
+    let named = BTreeMap::<String, V>::deserialize(deserializer)?;
+    Ok(named
+        .into_iter()
+        .filter_map(|(name, v)| Some((known_kind(&name)?, v)))
+        .collect())
+}
+
+fn deserialize_trades<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Trade>, D::Error> {
+    let trades = Vec::<TradeData>::deserialize(deserializer)?;
+    Ok(trades.into_iter().filter_map(TradeData::known).collect())
+}
+
+fn deserialize_bought<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<CommodityKind, Vec<Trade>>, D::Error> {
+    let bought = deserialize_known_kinds::<D, Vec<TradeData>>(deserializer)?;
+    Ok(bought
+        .into_iter()
+        .map(|(kind, trades)| {
+            (
+                kind,
+                trades.into_iter().filter_map(TradeData::known).collect(),
+            )
+        })
+        .collect())
+}
+
+fn deserialize_cargo<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(BuildingID, Trade)>, D::Error> {
+    let cargo = Vec::<(BuildingID, TradeData)>::deserialize(deserializer)?;
+    Ok(cargo
+        .into_iter()
+        .filter_map(|(b, trade)| Some((b, trade.known()?)))
+        .collect())
 }
 
 register_system!(market_update);
@@ -76,14 +189,25 @@ register_system!(market_update);
 #[write_component(Sold)]
 #[write_component(Bought)]
 #[write_component(Workers)]
-pub fn market_update(#[resource] m: &mut Market, subworld: &mut SubWorld) {
+pub fn market_update(
+    #[resource] m: &mut Market,
+    #[resource] stats: &mut EconomyStats,
+    #[resource] time: &GameTime,
+    subworld: &mut SubWorld,
+) {
+    if time.tick(SECONDS_PER_HOUR as u32) {
+        stats.end_hour(m);
+        m.update_prices();
+    }
+
     for trade in m.make_trades() {
         log::info!("A trade was made! {:?}", trade);
+        stats.record_trade(&trade);
 
         let mut ent = unwrap_orr!(subworld.entry_mut(trade.seller.0), continue);
 
         match trade.kind {
-            CommodityKind::JobOpening => ent
+            CommodityKind::JOB_OPENING => ent
                 .get_component_mut::<Workers>()
                 .expect("employer has no component Workers")
                 .0
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+/// An amount of money in cents
+#[derive(
+    Copy, Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize,
+)]
+pub struct Money(pub i64);
+
+impl Money {
+    pub const ZERO: Money = Money(0);
+
+    pub const fn from_cents(cents: i64) -> Self {
+        Money(cents)
+    }
+
+    pub const fn from_units(units: i64) -> Self {
+        Money(units * 100)
+    }
+
+    pub fn cents(self) -> i64 {
+        self.0
+    }
+
+    /// Scales the amount, rounding to the nearest cent
+    pub fn scale(self, factor: f32) -> Self {
+        Money((self.0 as f64 * factor as f64).round() as i64)
+    }
+
+    /// Halfway between the two amounts, rounded down
+    pub fn midpoint(self, other: Money) -> Self {
+        Money((self.0 + other.0).div_euclid(2))
+    }
+}
+
+impl Display for Money {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.abs();
+        write!(f, "{}${}.{:02}", sign, abs / 100, abs % 100)
+    }
+}
+
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, rhs: Money) -> Money {
+        Money(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, rhs: Money) -> Money {
+        Money(self.0 - rhs.0)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, rhs: Money) {
+        self.0 += rhs.0
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, rhs: Money) {
+        self.0 -= rhs.0
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money(-self.0)
+    }
+}
+
+impl Mul<i32> for Money {
+    type Output = Money;
+
+    fn mul(self, rhs: i32) -> Money {
+        Money(self.0 * rhs as i64)
+    }
+}
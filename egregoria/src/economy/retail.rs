@@ -0,0 +1,180 @@
+use crate::economy::{deserialize_known_kinds, CommodityKind, Market, Money, Trade};
+use crate::souls::desire::NeedKind;
+use crate::SoulID;
+use geom::Vec2;
+use map_model::BuildingID;
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// How much more than the reference price stores sell their goods for
+const RETAIL_MARGIN: f32 = 0.2;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Store {
+    pub soul: SoulID,
+    pub building: BuildingID,
+    pub door: Vec2,
+}
+
+register_resource!(Stores, "stores");
+/// The stores selling each commodity on site, to customers coming to their door.
+/// Their shelf stock is their capital in the [Market].
+#[derive(Default, Serialize, Deserialize)]
+pub struct Stores {
+    #[serde(deserialize_with = "deserialize_known_kinds")]
+    by_commodity: BTreeMap<CommodityKind, Vec<Store>>,
+}
+
+impl Stores {
+    pub fn add(&mut self, store: Store, shelf: impl IntoIterator<Item = CommodityKind>) {
+        for kind in shelf {
+            self.by_commodity.entry(kind).or_default().push(store);
+        }
+    }
+
+    pub fn remove(&mut self, soul: SoulID) {
+        for stores in self.by_commodity.values_mut() {
+            stores.retain(|s| s.soul != soul);
+        }
+    }
+
+    /// Price paid by customers at the counter
+    pub fn retail_price(market: &Market, kind: CommodityKind) -> Money {
+        market.price(kind).scale(1.0 + RETAIL_MARGIN)
+    }
+
+    /// The closest store that has this commodity on its shelves
+    pub fn closest_with_stock(
+        &self,
+        market: &Market,
+        kind: CommodityKind,
+        near: Vec2,
+    ) -> Option<Store> {
+        self.by_commodity
+            .get(&kind)?
+            .iter()
+            .filter(|s| market.capital(s.soul, kind) > 0)
+            .min_by_key(|s| OrderedFloat(s.door.distance2(near)))
+            .copied()
+    }
+
+    /// The closest store with stock of a commodity satisfying the need, that the customer can
+    /// afford with its budget
+    pub fn closest_satisfying(
+        &self,
+        market: &Market,
+        need: NeedKind,
+        near: Vec2,
+        budget: Money,
+    ) -> Option<(Store, CommodityKind)> {
+        CommodityKind::values()
+            .iter()
+            .filter(|kind| kind.description().need == Some(need))
+            .filter(|&&kind| Self::retail_price(market, kind) <= budget)
+            .filter_map(|&kind| Some((self.closest_with_stock(market, kind, near)?, kind)))
+            .min_by_key(|(s, _)| OrderedFloat(s.door.distance2(near)))
+    }
+
+    /// Called when a customer is at the door of the store, the goods are used right away.
+    /// Returns None if the store ran out of stock or the customer can't pay.
+    pub fn buy_on_site(
+        market: &mut Market,
+        customer: SoulID,
+        store: Store,
+        kind: CommodityKind,
+        qty: i32,
+    ) -> Option<Trade> {
+        let price = Self::retail_price(market, kind);
+        if market.capital(store.soul, kind) < qty || market.money(customer) < price * qty {
+            return None;
+        }
+
+        market.produce(store.soul, kind, -qty);
+        market.pay(customer, store.soul, price * qty);
+
+        Some(Trade {
+            buyer: customer,
+            seller: store.soul,
+            qty,
+            sell_pos: store.door,
+            buy_pos: store.door,
+            kind,
+            price,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Store, Stores};
+    use crate::economy::{CommodityKind, Market, Money};
+    use crate::utils::mk_ent;
+    use crate::SoulID;
+    use geom::{vec2, Vec2};
+    use legion::serialize::Canon;
+    use map_model::BuildingID;
+
+    #[test]
+    fn test_buy_on_site() {
+        let food = CommodityKind::food();
+        let near = Store {
+            soul: SoulID(mk_ent(1)),
+            building: BuildingID::default(),
+            door: Vec2::UNIT_X,
+        };
+        let far = Store {
+            soul: SoulID(mk_ent(2)),
+            building: BuildingID::default(),
+            door: vec2(100.0, 0.0),
+        };
+        let customer = SoulID(mk_ent(3));
+
+        let mut m = Market::default();
+        let mut stores = Stores::default();
+        stores.add(near, vec![food]);
+        stores.add(far, vec![food]);
+
+        m.produce(far.soul, food, 1);
+        m.give_money(customer, Money::from_units(100));
+
+        // The closest store is out of stock
+        let store = stores.closest_with_stock(&m, food, Vec2::ZERO).unwrap();
+        assert_eq!(store.soul, far.soul);
+
+        let price = Stores::retail_price(&m, food);
+        let trade = Stores::buy_on_site(&mut m, customer, store, food, 1).unwrap();
+        assert_eq!(trade.price, price);
+        assert_eq!(m.capital(far.soul, food), 0);
+        assert_eq!(m.money(customer), Money::from_units(100) - price);
+
+        assert!(Stores::buy_on_site(&mut m, customer, store, food, 1).is_none());
+        assert!(stores.closest_with_stock(&m, food, Vec2::ZERO).is_none());
+    }
+
+    #[test]
+    fn test_unknown_commodities() {
+        let food = CommodityKind::food();
+        let cereal = CommodityKind::from_name("cereal").unwrap();
+        let store = Store {
+            soul: SoulID(mk_ent(1)),
+            building: BuildingID::default(),
+            door: Vec2::UNIT_X,
+        };
+        let mut m = Market::default();
+        m.produce(store.soul, food, 1);
+        m.produce(store.soul, cereal, 1);
+        let mut stores = Stores::default();
+        stores.add(store, vec![food, cereal]);
+
+        // As if cereal was removed from the data file
+        let stores = legion::serialize::set_entity_serializer(&Canon::default(), || {
+            let json = serde_json::to_string(&stores).unwrap();
+            let json = json.replace("\"cereal\"", "\"unobtainium\"");
+            serde_json::from_str::<Stores>(&json).unwrap()
+        });
+
+        assert!(stores.closest_with_stock(&m, food, Vec2::ZERO).is_some());
+        assert!(stores.closest_with_stock(&m, cereal, Vec2::ZERO).is_none());
+    }
+}
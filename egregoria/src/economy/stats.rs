@@ -0,0 +1,108 @@
+use crate::economy::{deserialize_known_kinds, CommodityKind, Market, Trade};
+use common::History;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+
+/// Number of in-game hours kept
+pub const STATS_HOURS: usize = 48;
+
+/// Statistics of one commodity, each history has one value per in-game hour
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CommodityStats {
+    /// Quantity traded
+    pub volume: History,
+    /// Buy orders still waiting at the end of the hour
+    pub unfilled_buy: History,
+    /// Sell orders still waiting at the end of the hour
+    pub unfilled_sell: History,
+    /// Average distance between the buyer and the seller of the trades, in meters
+    pub avg_distance: History,
+    /// Quantity held by all the agents
+    pub stock: History,
+    /// Reference price, in money units
+    pub price: History,
+
+    cur_volume: i32,
+    cur_trades: u32,
+    cur_distance: f32,
+}
+
+impl Default for CommodityStats {
+    fn default() -> Self {
+        Self {
+            volume: History::new(STATS_HOURS),
+            unfilled_buy: History::new(STATS_HOURS),
+            unfilled_sell: History::new(STATS_HOURS),
+            avg_distance: History::new(STATS_HOURS),
+            stock: History::new(STATS_HOURS),
+            price: History::new(STATS_HOURS),
+            cur_volume: 0,
+            cur_trades: 0,
+            cur_distance: 0.0,
+        }
+    }
+}
+
+register_resource!(EconomyStats, "economy_stats");
+#[derive(Serialize, Deserialize)]
+pub struct EconomyStats {
+    #[serde(deserialize_with = "deserialize_commodities")]
+    commodities: BTreeMap<CommodityKind, CommodityStats>,
+}
+
+/// The commodities added to the data file since the stats were saved start without history
+fn deserialize_commodities<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<CommodityKind, CommodityStats>, D::Error> {
+    let mut commodities = deserialize_known_kinds(deserializer)?;
+    for &kind in CommodityKind::values() {
+        commodities.entry(kind).or_default();
+    }
+    Ok(commodities)
+}
+
+impl Default for EconomyStats {
+    fn default() -> Self {
+        Self {
+            commodities: CommodityKind::values()
+                .iter()
+                .map(|&v| (v, CommodityStats::default()))
+                .collect(),
+        }
+    }
+}
+
+impl EconomyStats {
+    pub fn get(&self, kind: CommodityKind) -> &CommodityStats {
+        &self.commodities[&kind]
+    }
+
+    pub fn record_trade(&mut self, trade: &Trade) {
+        let s = self.commodities.entry(trade.kind).or_default();
+        s.cur_volume += trade.qty;
+        s.cur_trades += 1;
+        s.cur_distance += trade.sell_pos.distance(trade.buy_pos);
+    }
+
+    /// Called at the end of every in-game hour, closes the hour using the orders left in the market
+    pub fn end_hour(&mut self, market: &Market) {
+        for (&kind, s) in &mut self.commodities {
+            let m = unwrap_or!(market.market(kind), continue);
+
+            s.volume.add_value(s.cur_volume as f32);
+            s.unfilled_buy.add_value(m.n_buy_orders() as f32);
+            s.unfilled_sell.add_value(m.n_sell_orders() as f32);
+            s.avg_distance.add_value(if s.cur_trades > 0 {
+                s.cur_distance / s.cur_trades as f32
+            } else {
+                0.0
+            });
+            s.stock.add_value(m.stock() as f32);
+            s.price.add_value(m.price().cents() as f32 / 100.0);
+
+            s.cur_volume = 0;
+            s.cur_trades = 0;
+            s.cur_distance = 0.0;
+        }
+    }
+}
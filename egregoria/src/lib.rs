@@ -3,18 +3,24 @@
 #![allow(clippy::too_many_arguments)]
 
 use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
+use std::ops::Range;
 
 use atomic_refcell::{AtomicRef, AtomicRefMut};
-use legion::serialize::Canon;
+use common::saveload::{Archive, JsonValue, Section};
+use legion::serialize::{Canon, EntitySerializer, TypeKey, UnknownType};
 use legion::storage::Component;
+use legion::storage::{Archetype, ArchetypeWriter, Components, EntityLayout};
 use legion::systems::{ParallelRunnable, Resource};
+use legion::world::{Allocate, Duplicate, Merger};
 use legion::{any, Entity, IntoQuery, Registry, Resources, World};
+use serde::de::DeserializeSeed;
 use serde::{Deserialize, Serialize};
 
 use common::{GameTime, SECONDS_PER_DAY, SECONDS_PER_HOUR};
 use geom::{Transform, Vec2};
-use map_model::{Map, SerializedMap};
+use map_model::{Map, SerializedMap, SerializedMapV0};
 use pedestrians::Location;
 use utils::frame_log::FrameLog;
 use utils::par_command_buffer::Deleted;
@@ -22,7 +28,7 @@ pub use utils::par_command_buffer::ParCommandBuffer;
 use utils::rand_provider::RandProvider;
 use utils::scheduler::SeqSchedule;
 
-use crate::economy::{Bought, Sold, Workers};
+use crate::economy::{Bought, Cargo, Sold, Workers};
 use crate::engine_interaction::Selectable;
 use crate::map_dynamic::{Itinerary, Router};
 use crate::pedestrians::Pedestrian;
@@ -30,7 +36,14 @@ use crate::physics::CollisionWorld;
 use crate::physics::{Collider, Kinematics};
 use crate::rendering::assets::AssetRender;
 use crate::rendering::meshrender_component::MeshRender;
-use crate::souls::desire::{BuyFood, Desire, Home, Work};
+use crate::save_slots::{SaveMeta, META_SECTION};
+use crate::souls::add_souls_to_empty_buildings;
+use crate::souls::demographics::Person;
+use crate::souls::desire::{
+    BuyFood, Desire, Desires, Home, Leisure, Needs, Shopping, VisitFriend, Work,
+};
+use crate::souls::household::Household;
+use crate::transit::Bus;
 use crate::vehicles::Vehicle;
 
 #[macro_export]
@@ -38,7 +51,7 @@ macro_rules! register_system {
     ($f: ident) => {
         inventory::submit! {
             paste::paste! {
-                $crate::GSystem::new(std::cell::RefCell::new(Some(Box::new([<$f _system >]()))))
+                $crate::GSystem::new(|| Box::new([<$f _system >]()))
             }
         }
     };
@@ -63,14 +76,40 @@ macro_rules! register_resource {
         });
         inventory::submit! {
             $crate::SaveLoadFunc {
-                save: Box::new(|goria| {
-                     common::saveload::save(&*goria.read::<$t>(), $name);
+                name: $name,
+                save: Box::new(|goria, archive| {
+                    archive.add_value($name, $crate::section_version($name), &*goria.read::<$t>());
                 }),
-                load: Box::new(|goria| {
-                    if let Some(res) = common::saveload::load::<$t>($name) {
+                load: Box::new(|goria, archive| {
+                    if let Some(res) = archive.section($name).and_then(|s| s.read::<$t>()) {
                         goria.insert(res);
                     }
-                })
+                }),
+                hash: Box::new(|goria, hasher| {
+                    common::saveload::hash_into(&*goria.read::<$t>(), hasher);
+                }),
+                export: Box::new(|goria| {
+                    common::saveload::to_json_value(&*goria.read::<$t>(), $name)
+                }),
+                import: Box::new(|goria, value| {
+                    if let Some(res) = common::saveload::from_json_value::<$t>(value, $name) {
+                        goria.insert(res);
+                    }
+                }),
+            }
+        }
+    };
+}
+
+/// Registers a function converting the bincode data of a save section from version `from` to `from + 1`
+#[macro_export]
+macro_rules! register_migration {
+    ($section: expr, $from: expr, $f: expr) => {
+        inventory::submit! {
+            $crate::Migration {
+                section: $section,
+                from: $from,
+                f: $f,
             }
         }
     };
@@ -94,14 +133,17 @@ extern crate imgui_inspect;
 #[macro_use]
 extern crate log as extern_log;
 
+pub mod background_save;
 pub mod economy;
 pub mod engine_interaction;
 pub mod map_dynamic;
 pub mod pedestrians;
 pub mod physics;
 pub mod rendering;
+pub mod save_slots;
 pub mod scenarios;
 pub mod souls;
+pub mod transit;
 pub mod utils;
 pub mod vehicles;
 
@@ -111,6 +153,18 @@ pub struct SoulID(pub Entity);
 
 debug_inspect_impl!(SoulID);
 
+impl PartialOrd for SoulID {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SoulID {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        utils::entity_key(self.0).cmp(&utils::entity_key(other.0))
+    }
+}
+
 #[derive(Default)]
 pub struct Egregoria {
     pub world: World,
@@ -118,24 +172,124 @@ pub struct Egregoria {
     resources: Resources,
 }
 
+type SaveFn = Box<dyn Fn(&mut Egregoria, &mut Archive) + 'static>;
+type LoadFn = Box<dyn Fn(&mut Egregoria, &Archive) + 'static>;
+type HashFn = Box<dyn Fn(&Egregoria, &mut dyn Hasher) + 'static>;
+type ExportFn = Box<dyn Fn(&Egregoria) -> Option<JsonValue> + 'static>;
+type ImportFn = Box<dyn Fn(&mut Egregoria, JsonValue) + 'static>;
+
 pub struct SaveLoadFunc {
-    pub save: Box<dyn Fn(&mut Egregoria) + 'static>,
-    pub load: Box<dyn Fn(&mut Egregoria) + 'static>,
+    pub name: &'static str,
+    pub save: SaveFn,
+    pub load: LoadFn,
+    pub hash: HashFn,
+    pub export: ExportFn,
+    pub import: ImportFn,
 }
 inventory::collect!(SaveLoadFunc);
 
+inventory::submit! {
+    SaveLoadFunc {
+        name: "time",
+        save: Box::new(|goria, archive| {
+            archive.add_value("time", section_version("time"), &*goria.read::<GameTime>());
+        }),
+        load: Box::new(|goria, archive| {
+            if let Some(time) = archive.section("time").and_then(|s| s.read::<GameTime>()) {
+                goria.insert(time);
+            }
+        }),
+        hash: Box::new(|goria, hasher| {
+            common::saveload::hash_into(&*goria.read::<GameTime>(), hasher);
+        }),
+        export: Box::new(|goria| {
+            common::saveload::to_json_value(&*goria.read::<GameTime>(), "time")
+        }),
+        import: Box::new(|goria, value| {
+            if let Some(time) = common::saveload::from_json_value::<GameTime>(value, "time") {
+                goria.insert(time);
+            }
+        }),
+    }
+}
+
+pub struct Migration {
+    pub section: &'static str,
+    pub from: u32,
+    pub f: fn(&[u8]) -> Option<Vec<u8>>,
+}
+
+inventory::collect!(Migration);
+
+/// The current version of a save section is the number of migrations registered for it
+pub fn section_version(name: &str) -> u32 {
+    inventory::iter::<Migration>
+        .into_iter()
+        .filter(|m| m.section == name)
+        .count() as u32
+}
+
+/// Brings the section up to date, returns false if it can't be
+fn migrate(section: &mut Section) -> bool {
+    let version = section_version(&section.name);
+    if section.version > version {
+        log::error!(
+            "section {} has version {} but only up to {} is supported",
+            section.name,
+            section.version,
+            version
+        );
+        return false;
+    }
+
+    while section.version < version {
+        let m = unwrap_or!(
+            inventory::iter::<Migration>
+                .into_iter()
+                .find(|m| m.section == section.name && m.from == section.version),
+            {
+                log::error!(
+                    "no migration for section {} from version {}",
+                    section.name,
+                    section.version
+                );
+                return false;
+            }
+        );
+        // Entities are read and written back with the same name
+        let data =
+            legion::serialize::set_entity_serializer(&Canon::default(), || (m.f)(section.data()));
+        let data = unwrap_or!(data, {
+            log::error!(
+                "failed migrating section {} from version {}",
+                section.name,
+                section.version
+            );
+            return false;
+        });
+        section.set_data(section.version + 1, data);
+        log::info!(
+            "migrated section {} to version {}",
+            section.name,
+            section.version
+        );
+    }
+    true
+}
+
 pub struct InitFunc {
     pub f: Box<dyn Fn(&mut Egregoria) + 'static>,
 }
 
 inventory::collect!(InitFunc);
 
+/// Systems are built on demand so that several Egregoria instances can live in the same process
 pub struct GSystem {
-    s: std::cell::RefCell<Option<Box<dyn ParallelRunnable + 'static>>>,
+    s: fn() -> Box<dyn ParallelRunnable + 'static>,
 }
 
 impl GSystem {
-    pub fn new(s: std::cell::RefCell<Option<Box<dyn ParallelRunnable + 'static>>>) -> Self {
+    pub fn new(s: fn() -> Box<dyn ParallelRunnable + 'static>) -> Self {
         Self { s }
     }
 }
@@ -156,6 +310,20 @@ impl Egregoria {
         ParCommandBuffer::apply(self);
     }
 
+    /// Advances the game time by `delta` seconds, runs the simulation once and
+    /// populates the buildings that don't have a soul yet.
+    /// Does not depend on any rendering or windowing state, so it can be driven headlessly.
+    pub fn tick(&mut self, delta: f64) {
+        {
+            let mut time = self.write::<GameTime>();
+            *time = GameTime::new(delta as f32, time.timestamp + delta);
+        }
+
+        self.run();
+
+        add_souls_to_empty_buildings(self);
+    }
+
     pub fn init() -> Egregoria {
         let mut goria = Egregoria::default();
         info!("Seed is {}", RNG_SEED);
@@ -175,8 +343,7 @@ impl Egregoria {
         }
 
         for s in inventory::iter::<GSystem> {
-            let s = s.s.borrow_mut().take().unwrap();
-            goria.schedule.add_system(s);
+            goria.schedule.add_system((s.s)());
         }
 
         goria
@@ -240,79 +407,535 @@ where
 }
 
 macro_rules! register {
-    ($r: expr; $($t: ty),+,) => {
+    ($r: expr, $key: expr; $($t: ty),+,) => {
         $(
-            $r.register::<$t>(my_hash(stringify!($t)))
+            $r.register::<$t>($key(stringify!($t)))
         );+
     };
 }
 
+macro_rules! register_clone {
+    ($m: expr; $($t: ty),+,) => {
+        $(
+            $m.register_clone::<$t>()
+        );+
+    };
+}
+
+/// Calls the given macro with all the components that are saved
+macro_rules! saved_components {
+    ($m: ident!($($arg: expr),*)) => {
+        $m!($($arg),*;
+          Transform,
+          AssetRender,
+          Kinematics,
+          Selectable,
+          Vehicle,
+          Pedestrian,
+          Itinerary,
+          Collider,
+          MeshRender,
+          Location,
+          Person,
+          Household,
+          Needs,
+          Desires,
+          Desire<Home>,
+          Desire<BuyFood>,
+          Desire<Work>,
+          Desire<Shopping>,
+          Desire<Leisure>,
+          Desire<VisitFriend>,
+          Bought,
+          Sold,
+          Workers,
+          Cargo,
+          Router,
+          Bus,
+        )
+    };
+}
+
 pub struct NoSerialize;
 
+/// The world as it was saved before the world section was versioned. The components whose layout
+/// changed since are registered with their previous type under the same key.
+fn registry_v0() -> Registry<u64> {
+    let mut registry = registry();
+    economy::migrations::register_world_v0(&mut registry, my_hash);
+    souls::migrations::register_world_v0(&mut registry, my_hash);
+    registry
+}
+
+register_migration!("world", 0, |data| {
+    migrate_world(data, registry_v0(), |world| {
+        economy::migrations::convert_world_v0(world);
+        souls::migrations::convert_world_v0(world);
+    })
+});
+
+// Intersections got actuated lights, green waves and reservations
+register_migration!("map", 0, |data| {
+    let old: SerializedMapV0 = bincode::deserialize(data).ok()?;
+    bincode::serialize(&old.migrate()).ok()
+});
+
+/// Reads the world with `old` then lets `convert` replace the components of the previous layout
+/// with the current ones, so that it can be written back with the current registry
+fn migrate_world(
+    data: &[u8],
+    old: Registry<u64>,
+    convert: impl FnOnce(&mut World),
+) -> Option<Vec<u8>> {
+    let entity_serializer = Canon::default();
+    let mut world: World = Section::new("world", 0, data.to_vec())
+        .read_seed(old.as_deserialize(&entity_serializer))?;
+    convert(&mut world);
+
+    // The archetypes of the previous components are still there, only empty
+    let mut registry = registry();
+    registry.on_unknown(UnknownType::Ignore);
+    bincode::serialize(&world.as_serializable(any(), &registry, &entity_serializer))
+        .map_err(|e| log::error!("failed serializing migrated world: {}", e))
+        .ok()
+}
+
+/// Serializes entities as their rank when sorted by id, unlike Canon which gives them a random uuid.
+/// Entities of a world are allocated in increasing order, so this doesn't depend on other worlds
+/// living in the same process. Only used for hashing, deserializing with it fails.
+struct RankEntitySerializer {
+    ranks: HashMap<Entity, u64>,
+}
+
+fn sorted_entities(world: &World) -> Vec<Entity> {
+    let mut entities: Vec<Entity> = <Entity>::query().iter(world).copied().collect();
+    entities.sort_unstable_by_key(|&e| utils::entity_key(e));
+    entities
+}
+
+/// A Canon where entities are named after their rank instead of a random uuid,
+/// so that exporting the same world twice gives the same result.
+fn ranked_canon(world: &World) -> Canon {
+    let canon = Canon::default();
+    for (i, e) in sorted_entities(world).into_iter().enumerate() {
+        let _ = canon.canonize(e, (i as u128 + 1).to_be_bytes());
+    }
+    canon
+}
+
+impl RankEntitySerializer {
+    fn new(world: &World) -> Self {
+        Self {
+            ranks: sorted_entities(world)
+                .into_iter()
+                .enumerate()
+                .map(|(i, e)| (e, i as u64))
+                .collect(),
+        }
+    }
+}
+
+impl EntitySerializer for RankEntitySerializer {
+    /// Dead entities that are still referenced all map to the same value
+    fn serialize(
+        &self,
+        entity: Entity,
+        serialize_fn: &mut dyn FnMut(&dyn erased_serde::Serialize),
+    ) {
+        serialize_fn(&self.ranks.get(&entity).copied().unwrap_or(u64::MAX))
+    }
+
+    fn deserialize(
+        &self,
+        _: &mut dyn erased_serde::Deserializer,
+    ) -> Result<Entity, erased_serde::Error> {
+        Err(serde::de::Error::custom(
+            "entities serialized by rank are only used for hashing",
+        ))
+    }
+}
+
 fn registry() -> Registry<u64> {
+    registry_with(my_hash)
+}
+
+/// Components are keyed by their type name, used for human readable exports
+fn named_registry() -> Registry<String> {
+    registry_with(|name| name.to_string())
+}
+
+fn registry_with<K: TypeKey>(key: impl Fn(&'static str) -> K) -> Registry<K> {
     let mut registry = Registry::default();
-    register!(registry;
-      Transform,
-      AssetRender,
-      Kinematics,
-      Selectable,
-      Vehicle,
-      Pedestrian,
-      Itinerary,
-      Collider,
-      MeshRender,
-      Location,
-      Desire<Home>,
-      Desire<BuyFood>,
-      Desire<Work>,
-      Bought,
-      Sold,
-      Workers,
-      Router,
-    );
+    saved_components!(register!(registry, key));
     registry
 }
 
-pub fn save_to_disk(goria: &mut Egregoria) {
-    let registry = registry();
+/// Clones the saved components while keeping the entity ids, so that references to them stay valid
+struct SnapshotMerger(Duplicate);
+
+impl SnapshotMerger {
+    fn new() -> Self {
+        let mut merger = Duplicate::default();
+        saved_components!(register_clone!(merger));
+        Self(merger)
+    }
+}
 
+impl Merger for SnapshotMerger {
+    fn assign_id(&mut self, existing: Entity, _: &mut Allocate) -> Entity {
+        existing
+    }
+
+    fn convert_layout(&mut self, source_layout: EntityLayout) -> EntityLayout {
+        self.0.convert_layout(source_layout)
+    }
+
+    fn merge_archetype(
+        &mut self,
+        src_entity_range: Range<usize>,
+        src_arch: &Archetype,
+        src_components: &Components,
+        dst: &mut ArchetypeWriter,
+    ) {
+        self.0
+            .merge_archetype(src_entity_range, src_arch, src_components, dst)
+    }
+}
+
+/// Copy of everything that goes in a save. It is quick to take compared to serializing the world
+/// and the map, which can then be done on another thread.
+pub struct SaveSnapshot {
+    /// Already contains the metadata and the resources
+    archive: Archive,
+    world: World,
+    map: SerializedMap,
+}
+
+impl SaveSnapshot {
+    pub fn new(goria: &mut Egregoria) -> Self {
+        let mut archive = Archive::new();
+
+        archive.add_value(
+            META_SECTION,
+            section_version(META_SECTION),
+            &SaveMeta::new(goria),
+        );
+
+        let mut world = World::default();
+        world.clone_from(
+            &goria.world,
+            &!legion::query::component::<NoSerialize>(),
+            &mut SnapshotMerger::new(),
+        );
+
+        // Entities are named after their rank in the snapshot, the same way as when the world
+        // is serialized in [SaveSnapshot::write]
+        let entity_serializer = ranked_canon(&world);
+        legion::serialize::set_entity_serializer(&entity_serializer, || {
+            for l in inventory::iter::<SaveLoadFunc> {
+                (l.save)(goria, &mut archive);
+            }
+        });
+
+        Self {
+            archive,
+            world,
+            map: SerializedMap::from(&*goria.read::<Map>()),
+        }
+    }
+
+    /// Serializes the world and the map then writes the archive to the slot.
+    /// `progress` is called with values going from 0 to 1.
+    pub fn write(mut self, slot: &str, progress: impl Fn(f32)) -> Option<()> {
+        let registry = registry();
+        let entity_serializer = ranked_canon(&self.world);
+
+        progress(0.0);
+        let s = self
+            .world
+            .as_serializable(any(), &registry, &entity_serializer);
+        self.archive
+            .add_value("world", section_version("world"), &s)?;
+        progress(0.5);
+        self.archive
+            .add_value("map", section_version("map"), &self.map)?;
+        progress(0.8);
+        self.archive.save(slot)?;
+        progress(1.0);
+        Some(())
+    }
+}
+
+pub fn save_to_disk(goria: &mut Egregoria, slot: &str) {
+    SaveSnapshot::new(goria).write(slot, |_| {});
+}
+
+/// Writes the world, the map and the resources as pretty json in world/<name>.json.
+/// Entities are named after their creation order so that two exports can be diffed.
+pub fn export_json(goria: &Egregoria, name: &str) -> Option<()> {
+    let registry = named_registry();
+    let entity_serializer = ranked_canon(&goria.world);
+
+    let world = common::saveload::to_json_value(
+        &goria.world.as_serializable(
+            !legion::query::component::<NoSerialize>(),
+            &registry,
+            &entity_serializer,
+        ),
+        "world",
+    )?;
+    let map = common::saveload::to_json_value(&SerializedMap::from(&*goria.read::<Map>()), "map")?;
+
+    let resources: serde_json::Map<String, JsonValue> =
+        legion::serialize::set_entity_serializer(&entity_serializer, || {
+            inventory::iter::<SaveLoadFunc>
+                .into_iter()
+                .filter_map(|l| Some((l.name.to_string(), (l.export)(goria)?)))
+                .collect()
+        });
+
+    let mut export = serde_json::Map::new();
+    export.insert("world".to_string(), world);
+    export.insert("map".to_string(), map);
+    export.insert("resources".to_string(), JsonValue::Object(resources));
+
+    common::saveload::save_json(&export, name)
+}
+
+/// Loads an export made by [export_json] into a freshly initialized Egregoria
+pub fn import_json(goria: &mut Egregoria, name: &str) -> Option<()> {
+    let mut export: serde_json::Map<String, JsonValue> = common::saveload::load_json(name)?;
+
+    let registry = named_registry();
     let entity_serializer = Canon::default();
+
+    if let Some(world) = export.remove("world") {
+        // Entities are allocated in the order of the export so they are created in the same order
+        // as the exported world, instead of when they are first referenced.
+        if let Some(JsonValue::Object(entities)) = world.get("entities") {
+            for name in entities.keys() {
+                if let Ok(x) = u128::from_str_radix(&name.replace('-', ""), 16) {
+                    entity_serializer.canonize_name(&x.to_be_bytes());
+                }
+            }
+        }
+
+        let mut w: World = registry
+            .as_deserialize(&entity_serializer)
+            .deserialize(world)
+            .map_err(|e| log::error!("failed deserializing world: {}", e))
+            .ok()?;
+        goria.world.move_from(&mut w, &any());
+    }
+
+    if let Some(JsonValue::Object(mut resources)) = export.remove("resources") {
+        legion::serialize::set_entity_serializer(&entity_serializer, || {
+            for l in inventory::iter::<SaveLoadFunc> {
+                if let Some(v) = resources.remove(l.name) {
+                    (l.import)(goria, v);
+                }
+            }
+        });
+    }
+
+    if let Some(map) = export
+        .remove("map")
+        .and_then(|m| common::saveload::from_json_value::<SerializedMap>(m, "map"))
+    {
+        goria.insert::<Map>(map.into());
+    }
+
+    Some(())
+}
+
+/// Hash of all the serializable components and resources, the map excluded.
+/// Two runs from the same state with the same inputs should have the same hash at every tick.
+pub fn world_hash(goria: &Egregoria) -> u64 {
+    let registry = registry();
+    let entity_serializer = RankEntitySerializer::new(&goria.world);
+
+    let mut hasher = DefaultHasher::new();
+
     let s = goria.world.as_serializable(
         !legion::query::component::<NoSerialize>(),
         &registry,
         &entity_serializer,
     );
-
-    common::saveload::save(&s, "world");
-    common::saveload::save(&SerializedMap::from(&*goria.read::<Map>()), "map");
+    common::saveload::hash_into(&s, &mut hasher);
 
     legion::serialize::set_entity_serializer(&entity_serializer, || {
         for l in inventory::iter::<SaveLoadFunc> {
-            (l.save)(goria);
+            (l.hash)(goria, &mut hasher);
         }
     });
+
+    hasher.finish()
 }
 
-pub fn load_from_disk(goria: &mut Egregoria) {
+/// Loads the given slot. If it doesn't exist, tries to load a save made before slots existed.
+pub fn load_from_disk(goria: &mut Egregoria, slot: &str) {
+    let mut archive = Archive::load(slot).unwrap_or_else(|| {
+        Archive::load_legacy(
+            ["world", "map"]
+                .iter()
+                .copied()
+                .chain(inventory::iter::<SaveLoadFunc>.into_iter().map(|l| l.name)),
+        )
+    });
+
+    let outdated: Vec<String> = archive
+        .sections_mut()
+        .filter_map(|s| {
+            if migrate(s) {
+                None
+            } else {
+                Some(s.name.clone())
+            }
+        })
+        .collect();
+    for name in outdated {
+        archive.remove(&name);
+    }
+
     let registry = registry();
 
     let entity_serializer = Canon::default();
-    let _ = common::saveload::load_seed("world", registry.as_deserialize(&entity_serializer)).map(
-        |mut w: World| {
+    let _ = archive
+        .section("world")
+        .and_then(|s| s.read_seed(registry.as_deserialize(&entity_serializer)))
+        .map(|mut w: World| {
             log::info!("successfully loaded world with {} entities", w.len());
             goria.world.move_from(&mut w, &any());
-        },
-    );
+        });
 
     legion::serialize::set_entity_serializer(&entity_serializer, || {
         for l in inventory::iter::<SaveLoadFunc> {
-            (l.load)(goria);
+            (l.load)(goria, &archive);
         }
     });
 
     goria.insert::<Map>(
-        common::saveload::load::<map_model::SerializedMap>("map")
+        archive
+            .section("map")
+            .and_then(|s| s.read::<map_model::SerializedMap>())
             .map(|x| x.into())
             .unwrap_or_default(),
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::background_save::{save_to_disk_background, SaveProgress};
+    use crate::map_dynamic::BuildingInfos;
+    use crate::save_slots::delete_slot;
+    use crate::{
+        export_json, import_json, load_from_disk, migrate, section_version, world_hash, Egregoria,
+    };
+    use common::saveload::Section;
+    use geom::{vec3, Camera};
+    use map_model::Map;
+
+    fn test_goria() -> Egregoria {
+        // assets are loaded relatively to the repository root
+        let _ = std::env::set_current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/.."));
+
+        let mut goria = Egregoria::init();
+        goria.insert(Camera::new(1.0, 1.0, vec3(0.0, 0.0, 1.0)));
+
+        let mut map = Map::empty();
+        map_model::procgen::load_testfield(&mut map);
+        {
+            let mut infos = goria.write::<BuildingInfos>();
+            for build in map.build_houses() {
+                infos.insert(build);
+            }
+        }
+        goria.insert(map);
+        goria
+    }
+
+    #[test]
+    fn test_determinism() {
+        const DELTA: f64 = 1.0 / 30.0;
+
+        let mut g1 = test_goria();
+        let mut g2 = test_goria();
+
+        assert_eq!(world_hash(&g1), world_hash(&g2));
+
+        for tick in 0..300 {
+            g1.tick(DELTA);
+            g2.tick(DELTA);
+
+            assert_eq!(
+                world_hash(&g1),
+                world_hash(&g2),
+                "simulation diverged at tick {}",
+                tick
+            );
+        }
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut g1 = test_goria();
+        for _ in 0..100 {
+            g1.tick(1.0 / 30.0);
+        }
+
+        export_json(&g1, "test_export").unwrap();
+
+        let mut g2 = Egregoria::init();
+        import_json(&mut g2, "test_export").unwrap();
+        common::saveload::delete_json("test_export").unwrap();
+
+        assert_eq!(world_hash(&g1), world_hash(&g2));
+    }
+
+    #[test]
+    fn test_background_save() {
+        let mut g1 = test_goria();
+        for _ in 0..100 {
+            g1.tick(1.0 / 30.0);
+        }
+
+        assert!(save_to_disk_background(&mut g1, "test_background_save"));
+        g1.write::<SaveProgress>().poll(true);
+        assert_eq!(
+            g1.read::<SaveProgress>().last,
+            Some(("test_background_save".to_string(), true))
+        );
+
+        let mut g2 = Egregoria::init();
+        load_from_disk(&mut g2, "test_background_save");
+        delete_slot("test_background_save").unwrap();
+
+        assert_eq!(world_hash(&g1), world_hash(&g2));
+    }
+
+    register_migration!("test_migration", 0, |data| {
+        let v: u32 = bincode::deserialize(data).ok()?;
+        bincode::serialize(&(v, 0.5f32)).ok()
+    });
+    register_migration!("test_migration", 1, |data| {
+        let (v, f): (u32, f32) = bincode::deserialize(data).ok()?;
+        bincode::serialize(&(v, f, "hello")).ok()
+    });
+
+    #[test]
+    fn test_migrate() {
+        assert_eq!(section_version("test_migration"), 2);
+
+        let mut section = Section::from_value("test_migration", 0, &3u32).unwrap();
+        assert!(migrate(&mut section));
+        assert!(section.is_valid());
+        assert_eq!(section.version, 2);
+        assert_eq!(
+            section.read::<(u32, f32, String)>(),
+            Some((3, 0.5, "hello".to_string()))
+        );
+
+        let mut section = Section::from_value("test_migration", 3, &3u32).unwrap();
+        assert!(!migrate(&mut section));
+    }
+}
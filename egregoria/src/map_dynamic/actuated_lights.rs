@@ -0,0 +1,57 @@
+use crate::map_dynamic::Itinerary;
+use crate::vehicles::Vehicle;
+use crate::ParCommandBuffer;
+use common::GameTime;
+use geom::Transform;
+use legion::world::SubWorld;
+use legion::{system, IntoQuery};
+use map_model::{LaneID, Map, TrafficControl, TraverseKind};
+use std::collections::BTreeMap;
+
+/// Vehicles this close to the light at the end of their lane count as queued
+const QUEUE_DIST: f32 = 50.0;
+
+register_system!(actuated_lights);
+/// Counts the vehicles queued at actuated lights every second so the lights can change phase.
+/// The map is only written at the end of the frame when some lights change.
+#[system]
+#[read_component(Vehicle)]
+#[read_component(Itinerary)]
+#[read_component(Transform)]
+pub fn actuated_lights(
+    #[resource] map: &Map,
+    #[resource] time: &GameTime,
+    #[resource] cbuf: &ParCommandBuffer,
+    world: &SubWorld,
+) {
+    if !time.tick(1) {
+        return;
+    }
+
+    let mut queues: BTreeMap<LaneID, u32> = BTreeMap::new();
+    for (_, it, trans) in <(&Vehicle, &Itinerary, &Transform)>::query().iter(world) {
+        let lane = match it.get_travers().map(|t| t.kind) {
+            Some(TraverseKind::Lane(lane)) => lane,
+            _ => continue,
+        };
+        let l = unwrap_or!(map.lanes().get(lane), continue);
+        if !matches!(l.control, TrafficControl::Actuated(_)) {
+            continue;
+        }
+        if l.control_point().is_close(trans.position(), QUEUE_DIST) {
+            *queues.entry(lane).or_default() += 1;
+        }
+    }
+
+    let changes =
+        map.actuated_lights_changes(time.seconds, |lane| queues.get(&lane).copied().unwrap_or(0));
+    if changes.is_empty() {
+        return;
+    }
+    cbuf.exec(move |goria| {
+        let mut map = goria.write::<Map>();
+        for (id, lights) in changes {
+            map.set_actuated_lights(id, lights);
+        }
+    });
+}
@@ -2,12 +2,15 @@ use crate::SoulID;
 use map_model::BuildingID;
 use serde::{Deserialize, Serialize};
 use slotmap::SecondaryMap;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::ops::{Index, IndexMut};
 
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct BuildingInfo {
+    /// The company or the household the building belongs to
     pub owner: Option<SoulID>,
+    /// The humans living in the building
+    pub residents: Vec<SoulID>,
     pub inside: Vec<SoulID>,
 }
 
@@ -15,7 +18,8 @@ register_resource!(BuildingInfos, "binfos");
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct BuildingInfos {
     assignment: SecondaryMap<BuildingID, BuildingInfo>,
-    owners: HashMap<SoulID, BuildingID>,
+    owners: BTreeMap<SoulID, BuildingID>,
+    homes: BTreeMap<SoulID, BuildingID>,
 }
 
 impl BuildingInfos {
@@ -42,6 +46,52 @@ impl BuildingInfos {
         self.owners.insert(soul, building);
     }
 
+    /// The building doesn't belong to the soul anymore, it can be taken by someone else
+    pub fn remove_owner(&mut self, soul: SoulID) -> Option<BuildingID> {
+        let building = self.owners.remove(&soul)?;
+        if let Some(x) = self.get_mut(building) {
+            if x.owner == Some(soul) {
+                x.owner = None;
+            }
+        }
+        Some(building)
+    }
+
+    pub fn add_resident(&mut self, building: BuildingID, soul: SoulID) {
+        if let Some(x) = self.get_mut(building) {
+            x.residents.push(soul);
+        }
+        self.homes.insert(soul, building);
+    }
+
+    /// Returns the building the soul lived in
+    pub fn remove_resident(&mut self, soul: SoulID) -> Option<BuildingID> {
+        let building = self.homes.remove(&soul)?;
+        if let Some(x) = self.get_mut(building) {
+            x.residents.retain(|&s| s != soul);
+        }
+        Some(building)
+    }
+
+    pub fn home_of(&self, soul: SoulID) -> Option<BuildingID> {
+        self.homes.get(&soul).copied()
+    }
+
+    pub fn residents(&self, building: BuildingID) -> &[SoulID] {
+        self.get(building).map(|x| &*x.residents).unwrap_or(&[])
+    }
+
+    pub fn population(&self) -> usize {
+        self.homes.len()
+    }
+
+    /// Removes the soul from the building without warning if it wasn't inside
+    pub fn forget_inside(&mut self, building: BuildingID, soul: SoulID) {
+        if let Some(x) = self.get_mut(building) {
+            x.inside.retain(|&s| s != soul);
+        }
+    }
+
     pub fn get_in(&mut self, building: BuildingID, e: SoulID) {
         if cfg!(debug_assertions) && self[building].inside.contains(&e) {
             log::warn!(
@@ -80,3 +130,48 @@ impl IndexMut<BuildingID> for BuildingInfos {
         &mut self.assignment[index]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::BuildingInfos;
+    use crate::utils::mk_ent;
+    use crate::SoulID;
+    use map_model::BuildingID;
+    use slotmap::SlotMap;
+
+    #[test]
+    fn test_residents() {
+        let mut buildings: SlotMap<BuildingID, ()> = SlotMap::with_key();
+        let house = buildings.insert(());
+        let other = buildings.insert(());
+
+        let household = SoulID(mk_ent(1));
+        let a = SoulID(mk_ent(2));
+        let b = SoulID(mk_ent(3));
+        let c = SoulID(mk_ent(4));
+
+        let mut infos = BuildingInfos::default();
+        infos.insert(house);
+        infos.insert(other);
+        infos.set_owner(house, household);
+        infos.add_resident(house, a);
+        infos.add_resident(house, b);
+        infos.add_resident(other, c);
+
+        assert_eq!(infos.residents(house), &[a, b]);
+        assert_eq!(infos.population(), 3);
+        assert_eq!(infos[house].owner, Some(household));
+
+        assert_eq!(infos.remove_resident(a), Some(house));
+        assert_eq!(infos.residents(house), &[b]);
+        assert_eq!(infos.home_of(a), None);
+        assert_eq!(infos.home_of(b), Some(house));
+
+        // The household keeps the house until it leaves
+        assert_eq!(infos.remove_resident(b), Some(house));
+        assert!(infos.residents(house).is_empty());
+        assert_eq!(infos[house].owner, Some(household));
+        assert_eq!(infos.remove_owner(household), Some(house));
+        assert_eq!(infos[house].owner, None);
+    }
+}
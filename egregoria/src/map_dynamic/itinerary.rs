@@ -8,13 +8,13 @@ use legion::system;
 use map_model::{Map, Pathfinder, Traversable, TraverseDirection, TraverseKind};
 use serde::{Deserialize, Serialize};
 
-#[derive(Default, Debug, Serialize, Deserialize, Inspect)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize, Inspect)]
 pub struct Itinerary {
     kind: ItineraryKind,
     local_path: Vec<Vec2>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ItineraryKind {
     None,
     WaitUntil(f64),
@@ -22,7 +22,7 @@ pub enum ItineraryKind {
     Route(Route),
 }
 
-#[derive(Debug, Serialize, Deserialize, Inspect)]
+#[derive(Debug, Clone, Serialize, Deserialize, Inspect)]
 pub struct Route {
     /// Route is reversed, allows for efficient popping
     pub reversed_route: Vec<Traversable>,
@@ -143,7 +143,7 @@ impl Itinerary {
                     return;
                 });
 
-                if k.can_pass(time, map.lanes()) {
+                if k.can_pass(self.peek(), time, map) {
                     self.advance(map);
                 }
             }
@@ -192,6 +192,14 @@ impl Itinerary {
         }
     }
 
+    /// The traversable coming after the current one
+    pub fn peek(&self) -> Option<&Traversable> {
+        match &self.kind {
+            ItineraryKind::None | ItineraryKind::WaitUntil(_) | ItineraryKind::Simple => None,
+            ItineraryKind::Route(Route { reversed_route, .. }) => reversed_route.last(),
+        }
+    }
+
     pub fn kind(&self) -> &ItineraryKind {
         &self.kind
     }
@@ -1,11 +1,19 @@
+mod actuated_lights;
 mod add_trees;
 mod house_assignment;
 mod itinerary;
 mod parking;
+mod reservations;
+mod right_of_way;
 mod router;
+mod trip_planner;
 
+pub use actuated_lights::*;
 pub use add_trees::*;
 pub use house_assignment::*;
 pub use itinerary::*;
 pub use parking::*;
+pub use reservations::*;
+pub use right_of_way::*;
 pub use router::*;
+pub use trip_planner::*;
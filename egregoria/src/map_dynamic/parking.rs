@@ -6,9 +6,45 @@ use serde::{Deserialize, Serialize};
 register_resource!(ParkingManagement, "pmanagement");
 #[derive(Clone, Default, Serialize, Deserialize)]
 pub struct ParkingManagement {
+    #[serde(with = "reserved_spots_serde")]
     reserved_spots: DashMap<ParkingSpotID, ()>,
 }
 
+/// Spots are sorted so that the output doesn't depend on the DashMap's random state.
+/// Formats like json only allow string keys for maps, so spots are written as a list there.
+mod reserved_spots_serde {
+    use dashmap::DashMap;
+    use map_model::ParkingSpotID;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        spots: &DashMap<ParkingSpotID, ()>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut spots: Vec<ParkingSpotID> = spots.iter().map(|x| *x.key()).collect();
+        spots.sort_unstable();
+
+        if serializer.is_human_readable() {
+            serializer.collect_seq(spots)
+        } else {
+            serializer.collect_map(spots.into_iter().map(|spot| (spot, ())))
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DashMap<ParkingSpotID, ()>, D::Error> {
+        if deserializer.is_human_readable() {
+            Ok(Vec::<ParkingSpotID>::deserialize(deserializer)?
+                .into_iter()
+                .map(|spot| (spot, ()))
+                .collect())
+        } else {
+            DashMap::deserialize(deserializer)
+        }
+    }
+}
+
 impl ParkingManagement {
     pub fn free(&self, spot: ParkingSpotID) {
         if self.reserved_spots.remove(&spot).is_none() {
@@ -21,6 +57,24 @@ impl ParkingManagement {
     }
 
     pub fn reserve_near(&self, near: Vec2, map: &Map) -> Option<ParkingSpotID> {
+        self.search_near(near, map, |spot| {
+            self.reserved_spots.insert(spot, ()).is_none()
+        })
+    }
+
+    /// A free spot near the position without reserving it, to know if a trip can end there
+    pub fn find_near(&self, near: Vec2, map: &Map) -> Option<ParkingSpotID> {
+        self.search_near(near, map, |spot| !self.reserved_spots.contains_key(&spot))
+    }
+
+    /// The first spot accepted by `take`, looking at the closest parking lane then the ones
+    /// following it
+    fn search_near(
+        &self,
+        near: Vec2,
+        map: &Map,
+        take: impl Fn(ParkingSpotID) -> bool,
+    ) -> Option<ParkingSpotID> {
         let lane = map.nearest_lane(near, LaneKind::Parking)?;
         let lane = map.lanes().get(lane)?;
 
@@ -36,7 +90,7 @@ impl ParkingManagement {
                 let plane = unwrap_or!(parent.parking_next_to(lane), continue);
 
                 for spot in map.parking.closest_spots(plane, near) {
-                    if self.reserved_spots.insert(spot, ()).is_none() {
+                    if take(spot) {
                         return Some(spot);
                     }
                 }
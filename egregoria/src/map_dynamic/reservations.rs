@@ -0,0 +1,192 @@
+use crate::map_dynamic::Itinerary;
+use crate::utils::entity_key;
+use crate::vehicles::{Vehicle, VehicleState};
+use common::GameTime;
+use geom::Transform;
+use legion::world::SubWorld;
+use legion::{system, Entity, IntoQuery};
+use map_model::{Intersection, IntersectionID, LaneID, Map, TraverseKind, TurnID};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Vehicles ask for their turn this close to the end of their lane, far enough to stop before
+/// entering the intersection at cruising speed
+const RESERVE_DIST: f32 = 30.0;
+
+/// The turns reserved by the vehicles at the intersections with reservations. A turn can only be
+/// reserved when none of the turns conflicting with it is, and vehicles keep their reservation
+/// until they leave the turn. The others wait at the end of their lane, first-come-first-served.
+#[derive(Default)]
+pub struct Reservations {
+    reserved: BTreeMap<IntersectionID, Vec<(u64, TurnID)>>,
+    holders: BTreeSet<u64>,
+    /// The turn each waiting vehicle asked for and since when
+    waiting: BTreeMap<u64, (TurnID, f64)>,
+}
+
+register_resource_noserialize!(Reservations);
+
+impl Reservations {
+    /// Whether the vehicle holds a reservation, so it doesn't need to look out for crossing
+    /// traffic
+    pub fn holds(&self, vehicle: Entity) -> bool {
+        self.holders.contains(&entity_key(vehicle))
+    }
+
+    /// Whether the vehicle is waiting for its reservation
+    pub fn waiting(&self, vehicle: Entity) -> bool {
+        self.waiting.contains_key(&entity_key(vehicle))
+    }
+
+    /// Grants the turn to the vehicle if no reserved turn conflicts with it
+    fn reserve(&mut self, inter: &Intersection, vehicle: u64, turn: TurnID) -> bool {
+        let reserved = self.reserved.entry(inter.id).or_default();
+        if reserved
+            .iter()
+            .any(|&(v, t)| v != vehicle && inter.turns_conflict(turn, t))
+        {
+            return false;
+        }
+        reserved.push((vehicle, turn));
+        self.holders.insert(vehicle);
+        true
+    }
+
+    /// Frees the turns reserved by the vehicle, like when it is removed before leaving them
+    pub fn release(&mut self, vehicle: Entity) {
+        self.release_key(entity_key(vehicle));
+    }
+
+    fn release_key(&mut self, vehicle: u64) {
+        for reserved in self.reserved.values_mut() {
+            reserved.retain(|&(v, _)| v != vehicle);
+        }
+        self.holders.remove(&vehicle);
+    }
+}
+
+register_system!(intersection_reservations);
+/// Reserves the turns of the vehicles arriving at intersections with reservations, the vehicles
+/// already holding one go first.
+#[system]
+#[read_component(Vehicle)]
+#[read_component(Itinerary)]
+#[read_component(Transform)]
+pub fn intersection_reservations(
+    #[resource] map: &Map,
+    #[resource] time: &GameTime,
+    #[resource] res: &mut Reservations,
+    world: &SubWorld,
+) {
+    let uses_reservations =
+        |id: IntersectionID| matches!(map.intersections().get(id), Some(i) if i.reservations);
+
+    let mut inside: Vec<(u64, TurnID)> = vec![];
+    let mut front: BTreeMap<LaneID, (f32, u64, TurnID)> = BTreeMap::new();
+
+    let mut query = <(Entity, &Vehicle, &Itinerary, &Transform)>::query();
+    for (e, vehicle, it, trans) in query.iter(world) {
+        if !matches!(
+            vehicle.state,
+            VehicleState::Driving | VehicleState::Panicking(_)
+        ) {
+            continue;
+        }
+        let key = entity_key(*e);
+        let cur = unwrap_or!(it.get_travers(), continue);
+        match (cur.kind, it.peek().map(|t| t.kind)) {
+            (TraverseKind::Turn(turn), _) if uses_reservations(turn.parent) => {
+                inside.push((key, turn));
+            }
+            (TraverseKind::Lane(lane), Some(TraverseKind::Turn(turn)))
+                if uses_reservations(turn.parent) =>
+            {
+                // Reservations are only asked for when the light lets the vehicle go
+                if !cur.can_pass(it.peek(), time.seconds, map) {
+                    continue;
+                }
+                let l = unwrap_or!(map.lanes().get(lane), continue);
+                let dist = l.control_point().distance(trans.position());
+                if dist > RESERVE_DIST {
+                    continue;
+                }
+                let closest = front.entry(lane).or_insert((dist, key, turn));
+                if dist < closest.0 {
+                    *closest = (dist, key, turn);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let previous = std::mem::take(&mut res.reserved);
+    let was_waiting = std::mem::take(&mut res.waiting);
+    res.holders.clear();
+
+    // Vehicles on the intersection keep their turn whatever happens
+    for (key, turn) in inside {
+        res.reserved
+            .entry(turn.parent)
+            .or_default()
+            .push((key, turn));
+        res.holders.insert(key);
+    }
+
+    let mut requests: Vec<(bool, f64, u64, TurnID)> = front
+        .into_values()
+        .map(|(_, key, turn)| {
+            let held = matches!(previous.get(&turn.parent), Some(r) if r.contains(&(key, turn)));
+            let since = was_waiting
+                .get(&key)
+                .filter(|(t, _)| *t == turn)
+                .map_or(time.timestamp, |&(_, since)| since);
+            (!held, since, key, turn)
+        })
+        .collect();
+    requests.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    for (_, since, key, turn) in requests {
+        let inter = unwrap_or!(map.intersections().get(turn.parent), continue);
+        if !res.reserve(inter, key, turn) {
+            res.waiting.insert(key, (turn, since));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Reservations;
+    use geom::Vec2;
+    use map_model::procgen::add_four_way;
+    use map_model::{LanePatternBuilder, Map};
+
+    #[test]
+    fn test_reserve() {
+        let mut map = Map::empty();
+        let pattern = LanePatternBuilder::new().build();
+        let fw = add_four_way(Vec2::ZERO, &mut map, &pattern, &pattern);
+        map.update_intersection(fw.center, |i| i.reservations = true);
+
+        let inter = &map.intersections()[fw.center];
+        let r = fw.roads;
+        let turn = |from, to| fw.turn(&map, from, to).id;
+
+        let mut res = Reservations::default();
+        assert!(res.reserve(inter, 1, turn(r[0], r[1])));
+        // Crossing the reserved turn
+        assert!(!res.reserve(inter, 2, turn(r[2], r[3])));
+        // Following the first vehicle, and going the other way
+        assert!(res.reserve(inter, 3, turn(r[0], r[1])));
+        assert!(res.reserve(inter, 4, turn(r[1], r[0])));
+        // Turning left in front of the oncoming traffic
+        assert!(!res.reserve(inter, 5, turn(r[1], r[2])));
+
+        // The crossing turn stays refused until every conflicting reservation is released
+        res.release_key(1);
+        res.release_key(3);
+        assert!(!res.reserve(inter, 2, turn(r[2], r[3])));
+        res.release_key(4);
+        assert!(res.reserve(inter, 2, turn(r[2], r[3])));
+        assert!(res.holders.contains(&2));
+        assert!(!res.holders.contains(&1));
+    }
+}
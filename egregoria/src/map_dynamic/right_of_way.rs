@@ -0,0 +1,168 @@
+use crate::map_dynamic::Itinerary;
+use crate::utils::entity_key;
+use crate::vehicles::{Vehicle, VehicleState};
+use common::GameTime;
+use geom::Transform;
+use legion::world::SubWorld;
+use legion::{system, Entity, IntoQuery};
+use map_model::{Intersection, IntersectionID, LaneID, Map, TrafficControl, TraverseKind, TurnID};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Vehicles this close to the end of their lane wait for their turn at the intersection
+const APPROACH_DIST: f32 = 15.0;
+
+/// Vehicles waiting longer than this stop yielding to the other vehicles waiting, so that a
+/// vehicle stuck at the intersection can't block it forever, in seconds
+const MAX_YIELD_TIME: f64 = 30.0;
+
+#[derive(Copy, Clone)]
+struct Arrival {
+    turn: TurnID,
+    since: f64,
+    stop_sign: bool,
+}
+
+/// Which vehicles have to let the others go first at the intersections without lights.
+/// Only the first vehicle of each lane waits for its turn, the others follow it.
+#[derive(Default)]
+pub struct RightOfWay {
+    arrivals: BTreeMap<u64, Arrival>,
+    yielding: BTreeSet<u64>,
+}
+
+register_resource_noserialize!(RightOfWay);
+
+impl RightOfWay {
+    pub fn must_yield(&self, vehicle: Entity) -> bool {
+        self.yielding.contains(&entity_key(vehicle))
+    }
+
+    /// Whether the vehicle waiting at `a` goes before the one waiting at `b`: vehicles without
+    /// a stop sign go first, then stop signs are first-come-first-served.
+    fn goes_before(inter: &Intersection, a: &Arrival, b: &Arrival, map: &Map) -> bool {
+        match (a.stop_sign, b.stop_sign) {
+            (false, true) => true,
+            (true, false) => false,
+            (true, true) => a.since < b.since,
+            (false, false) => {
+                let ta = unwrap_or!(inter.find_turn(a.turn), return false);
+                let tb = unwrap_or!(inter.find_turn(b.turn), return false);
+                inter.has_priority(ta, tb, map.lanes(), map.roads())
+                    && !inter.has_priority(tb, ta, map.lanes(), map.roads())
+            }
+        }
+    }
+
+    fn update_intersection(
+        &mut self,
+        inter: &Intersection,
+        waiting: &[(u64, Arrival)],
+        inside: &[TurnID],
+        map: &Map,
+        now: f64,
+    ) {
+        let mut all_yield = true;
+        for (key, a) in waiting {
+            let blocked = inside.iter().any(|&t| inter.turns_conflict(a.turn, t))
+                || (now - a.since < MAX_YIELD_TIME
+                    && waiting.iter().any(|(other, b)| {
+                        other != key
+                            && inter.turns_conflict(a.turn, b.turn)
+                            && Self::goes_before(inter, b, a, map)
+                    }));
+            if blocked {
+                self.yielding.insert(*key);
+            } else {
+                all_yield = false;
+            }
+        }
+
+        // Vehicles all giving way to one another, the first one to arrive goes
+        if all_yield && inside.is_empty() {
+            let first = waiting
+                .iter()
+                .min_by(|(_, a), (_, b)| a.since.partial_cmp(&b.since).unwrap());
+            if let Some((key, _)) = first {
+                self.yielding.remove(key);
+            }
+        }
+    }
+}
+
+register_system!(right_of_way);
+/// Decides which vehicles have to yield at the intersections controlled by stop signs or
+/// without any control, unless the vehicles reserve their turns there.
+#[system]
+#[read_component(Vehicle)]
+#[read_component(Itinerary)]
+#[read_component(Transform)]
+pub fn right_of_way(
+    #[resource] map: &Map,
+    #[resource] time: &GameTime,
+    #[resource] row: &mut RightOfWay,
+    world: &SubWorld,
+) {
+    let mut inside: BTreeMap<IntersectionID, Vec<TurnID>> = BTreeMap::new();
+    let mut front: BTreeMap<LaneID, (f32, u64, TurnID)> = BTreeMap::new();
+
+    let mut query = <(Entity, &Vehicle, &Itinerary, &Transform)>::query();
+    for (e, vehicle, it, trans) in query.iter(world) {
+        if !matches!(
+            vehicle.state,
+            VehicleState::Driving | VehicleState::Panicking(_)
+        ) {
+            continue;
+        }
+        match (it.get_travers().map(|t| t.kind), it.peek().map(|t| t.kind)) {
+            (Some(TraverseKind::Turn(turn)), _) => {
+                inside.entry(turn.parent).or_default().push(turn);
+            }
+            (Some(TraverseKind::Lane(lane)), Some(TraverseKind::Turn(turn))) => {
+                let l = unwrap_or!(map.lanes().get(lane), continue);
+                if !matches!(l.control, TrafficControl::Always | TrafficControl::StopSign) {
+                    continue;
+                }
+                if matches!(map.intersections().get(turn.parent), Some(i) if i.reservations) {
+                    continue;
+                }
+                let dist = l.control_point().distance(trans.position());
+                if dist > APPROACH_DIST {
+                    continue;
+                }
+                let key = entity_key(*e);
+                let closest = front.entry(lane).or_insert((dist, key, turn));
+                if dist < closest.0 {
+                    *closest = (dist, key, turn);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut waiting: BTreeMap<IntersectionID, Vec<(u64, Arrival)>> = BTreeMap::new();
+    for (lane, (_, key, turn)) in front {
+        let since = row
+            .arrivals
+            .get(&key)
+            .filter(|a| a.turn == turn)
+            .map_or(time.timestamp, |a| a.since);
+        let stop_sign = matches!(map.lanes()[lane].control, TrafficControl::StopSign);
+        waiting.entry(turn.parent).or_default().push((
+            key,
+            Arrival {
+                turn,
+                since,
+                stop_sign,
+            },
+        ));
+    }
+
+    row.arrivals.clear();
+    row.yielding.clear();
+    for (id, waiting) in waiting {
+        let inter = unwrap_or!(map.intersections().get(id), continue);
+        let inside = inside.get(&id).map(Vec::as_slice).unwrap_or_default();
+        row.update_intersection(inter, &waiting, inside, map, time.timestamp);
+        row.arrivals.extend(waiting);
+    }
+}
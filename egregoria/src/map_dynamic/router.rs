@@ -1,14 +1,19 @@
-use crate::map_dynamic::{Itinerary, ParkingManagement};
+use crate::map_dynamic::{
+    bike_time, drive_and_park_time, walk_time, walk_time_approx, Itinerary, ParkingManagement,
+    TravelMode, TripPlan, TripStats,
+};
 use crate::pedestrians::{put_pedestrian_in_coworld, Location};
 use crate::physics::{Collider, CollisionWorld, Kinematics};
+use crate::rendering::assets::AssetRender;
 use crate::rendering::meshrender_component::MeshRender;
-use crate::vehicles::{put_vehicle_in_coworld, Vehicle, VehicleID, VehicleState};
+use crate::transit::{Bus, BusLineID, BusLines};
+use crate::vehicles::{put_vehicle_in_coworld, Vehicle, VehicleID, VehicleKind, VehicleState};
 use crate::{Egregoria, ParCommandBuffer};
 use geom::{Spline, Transform, Vec2};
 use imgui_inspect_derive::*;
 use legion::world::SubWorld;
 use legion::{system, Entity, EntityStore};
-use map_model::{BuildingID, CarPath, Map, ParkingSpotID, PedestrianPath};
+use map_model::{BikePath, BuildingID, CarPath, Map, ParkingSpotID, PedestrianPath};
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Inspect, Serialize, Deserialize)]
@@ -19,6 +24,9 @@ pub struct Router {
     reroute: bool,
     vehicle: Option<VehicleID>,
     pub personal_car: Option<VehicleID>,
+    pub bike: Option<VehicleID>,
+    /// How the last trip was planned
+    plan: Option<TripPlan>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
@@ -39,19 +47,30 @@ pub enum RoutingStep {
     GetOutVehicle(VehicleID),
     GetInBuilding(BuildingID),
     GetOutBuilding(BuildingID),
+    /// Waits at the stop until a bus of the line stops there and gets in
+    BoardBus(BusLineID, usize),
+    /// Rides the bus until it stops there and gets out
+    AlightBus(BusLineID, usize),
+    GetOnBike(VehicleID),
+    GetOffBike(VehicleID),
 }
 
 debug_inspect_impl!(RoutingStep);
 
 register_system!(routing_update);
-#[system(par_for_each)]
+/// Not parallel: parking spots are reserved while routing, which spot goes to whom must not depend
+/// on thread scheduling.
+#[system(for_each)]
 #[read_component(Transform)]
 #[read_component(Vehicle)]
 #[read_component(Itinerary)]
+#[read_component(Bus)]
 pub fn routing_update(
     #[resource] map: &Map,
     #[resource] cbuf: &ParCommandBuffer,
     #[resource] parking: &ParkingManagement,
+    #[resource] lines: &BusLines,
+    #[resource] stats: &mut TripStats,
     body: &Entity,
     trans: &Transform,
     itin: &Itinerary,
@@ -62,8 +81,11 @@ pub fn routing_update(
     subworld: &SubWorld,
 ) {
     let pos = trans.position();
-    if !router.reroute {
-        let next_step = unwrap_or!(router.steps.last(), {
+    // Passengers don't get out of the bus in the middle of the road to go elsewhere
+    let riding = matches!(*loc, Location::Vehicle(v) if Some(v) != router.vehicle)
+        && !router.steps.is_empty();
+    if !router.reroute || riding {
+        let next_step = *unwrap_or!(router.steps.last(), {
             router.cur_step = None;
             return;
         });
@@ -93,9 +115,20 @@ pub fn routing_update(
                 RoutingStep::GetOutVehicle(_) => true,
                 RoutingStep::GetInBuilding(_) => true,
                 RoutingStep::GetOutBuilding(_) => true,
+                RoutingStep::BoardBus(_, _) => true,
+                RoutingStep::AlightBus(_, _) => true,
+                RoutingStep::GetOnBike(_) => true,
+                RoutingStep::GetOffBike(_) => true,
             };
         }
 
+        if let RoutingStep::BoardBus(line, _) = next_step {
+            if lines.get(line).is_none() {
+                router.reroute = true;
+                return;
+            }
+        }
+
         let next_step_ready = match next_step {
             RoutingStep::WalkTo(_) => true,
             RoutingStep::DriveTo(_, _) => true,
@@ -109,10 +142,20 @@ pub fn routing_update(
                 .position()
                 .is_close(pos, 3.0),
             RoutingStep::GetOutVehicle(_) => true,
-            &RoutingStep::GetInBuilding(build) => {
-                map.buildings()[build].door_pos.is_close(pos, 3.0)
-            }
+            RoutingStep::GetInBuilding(build) => map.buildings()[build].door_pos.is_close(pos, 3.0),
             RoutingStep::GetOutBuilding(_) => true,
+            RoutingStep::BoardBus(line, stop) => bus_at_stop(lines, subworld, line, stop).is_some(),
+            RoutingStep::AlightBus(_, stop) => match *loc {
+                // The bus is gone with its line, get out where it was last seen
+                Location::Vehicle(bus) => subworld
+                    .entry_ref(bus.0)
+                    .ok()
+                    .and_then(|e| e.get_component::<Bus>().ok().map(|b| b.stopped_at(stop)))
+                    .unwrap_or(true),
+                _ => true,
+            },
+            RoutingStep::GetOnBike(_) => true,
+            RoutingStep::GetOffBike(_) => true,
         };
 
         if !(next_step_ready && cur_step_over) {
@@ -128,7 +171,16 @@ pub fn routing_update(
                 }
             }
             RoutingStep::DriveTo(vehicle, obj) => {
-                if let Some(route) = Itinerary::route(pos, obj, &*map, &CarPath) {
+                let kind = subworld
+                    .entry_ref(vehicle.0)
+                    .ok()
+                    .and_then(|e| e.get_component::<Vehicle>().ok().map(|v| v.kind));
+                let route = if matches!(kind, Some(VehicleKind::Bike)) {
+                    Itinerary::route(pos, obj, map, &BikePath)
+                } else {
+                    Itinerary::route(pos, obj, map, &CarPath)
+                };
+                if let Some(route) = route {
                     cbuf.add_component(vehicle.0, route);
                 }
             }
@@ -138,10 +190,10 @@ pub fn routing_update(
                     return;
                 }
 
-                cbuf.exec(park(vehicle, spot));
+                cbuf.exec_ent(*body, park(vehicle, spot));
             }
             RoutingStep::Unpark(vehicle) => {
-                cbuf.exec(unpark(vehicle));
+                cbuf.exec_ent(*body, unpark(vehicle));
             }
             RoutingStep::GetInVehicle(vehicle) => {
                 *loc = Location::Vehicle(vehicle);
@@ -164,6 +216,30 @@ pub fn routing_update(
                 let wpos = map.buildings()[build].door_pos;
                 walk_outside(*body, wpos, cbuf, mr, loc);
             }
+            RoutingStep::BoardBus(line, stop) => {
+                if let Some(bus) = bus_at_stop(lines, subworld, line, stop) {
+                    *loc = Location::Vehicle(bus);
+                    walk_inside(*body, cbuf, mr, kin);
+                }
+            }
+            RoutingStep::AlightBus(line, stop) => {
+                let wpos = lines.stop(line, stop).map_or(pos, |s| s.sidewalk);
+                walk_outside(*body, wpos, cbuf, mr, loc);
+            }
+            RoutingStep::GetOnBike(bike) => {
+                cbuf.exec_ent(*body, take_out_bike(bike));
+                *loc = Location::Vehicle(bike);
+                walk_inside(*body, cbuf, mr, kin);
+            }
+            RoutingStep::GetOffBike(bike) => {
+                let bpos = subworld
+                    .entry_ref(bike.0)
+                    .ok()
+                    .and_then(|e| e.get_component::<Transform>().ok().map(|t| t.position()))
+                    .unwrap_or(pos);
+                cbuf.exec_ent(*body, stow_bike(bike));
+                walk_outside(*body, bpos, cbuf, mr, loc);
+            }
         }
         return;
     }
@@ -174,8 +250,8 @@ pub fn routing_update(
     let dest = router.dest.expect("destination is empty but dirty is true");
     router.clear_steps(parking);
     match dest {
-        Destination::Outside(pos) => {
-            router.steps = router.steps_to(pos, parking, map, loc, subworld)
+        Destination::Outside(obj) => {
+            router.steps = router.steps_to(pos, obj, parking, lines, map, loc, subworld);
         }
         Destination::Building(build) => {
             if let Location::Building(cur_build) = loc {
@@ -185,14 +261,33 @@ pub fn routing_update(
             }
 
             let door_pos = map.buildings()[build].door_pos;
-            router.steps = router.steps_to(door_pos, parking, map, loc, subworld);
+            router.steps = router.steps_to(pos, door_pos, parking, lines, map, loc, subworld);
             router.steps.push(RoutingStep::GetInBuilding(build));
         }
     }
+    if let Some(plan) = router.plan {
+        stats.record(plan);
+    }
 
     router.steps.reverse();
 }
 
+/// A bus of the line waiting for passengers at the stop
+fn bus_at_stop(
+    lines: &BusLines,
+    subworld: &SubWorld,
+    line: BusLineID,
+    stop: usize,
+) -> Option<VehicleID> {
+    lines.get(line)?.buses.iter().copied().find(|bus| {
+        subworld
+            .entry_ref(bus.0)
+            .ok()
+            .and_then(|e| e.get_component::<Bus>().ok().map(|b| b.stopped_at(stop)))
+            .unwrap_or(false)
+    })
+}
+
 fn walk_inside(body: Entity, cbuf: &ParCommandBuffer, mr: &mut MeshRender, kin: &mut Kinematics) {
     mr.hide = true;
     cbuf.remove_component::<Collider>(body);
@@ -209,7 +304,7 @@ fn walk_outside(
 ) {
     mr.hide = false;
     *loc = Location::Outside;
-    cbuf.exec(move |goria| {
+    cbuf.exec_ent(body, move |goria| {
         goria.comp_mut::<Transform>(body).unwrap().set_position(pos);
         let coll = put_pedestrian_in_coworld(&mut goria.write::<CollisionWorld>(), pos);
         goria.add_comp(body, coll);
@@ -242,6 +337,33 @@ fn park(vehicle: VehicleID, spot_id: ParkingSpotID) -> impl FnOnce(&mut Egregori
     }
 }
 
+/// The bike is unlocked where it was left and gets on the road
+fn take_out_bike(bike: VehicleID) -> impl FnOnce(&mut Egregoria) {
+    move |goria| {
+        let at = *unwrap_or!(goria.comp::<Transform>(bike.0), return);
+        if let Some(ar) = goria.comp_mut::<AssetRender>(bike.0) {
+            ar.hide = false;
+        }
+        let coll = put_vehicle_in_coworld(goria, VehicleKind::Bike.width(), at);
+        goria.add_comp(bike.0, coll);
+    }
+}
+
+/// The bike is locked and hidden until its owner rides it again
+fn stow_bike(bike: VehicleID) -> impl FnOnce(&mut Egregoria) {
+    move |goria| {
+        if let Some(ar) = goria.comp_mut::<AssetRender>(bike.0) {
+            ar.hide = true;
+        }
+        if let Some(kin) = goria.comp_mut::<Kinematics>(bike.0) {
+            kin.velocity = Vec2::ZERO;
+        }
+        goria
+            .read::<ParCommandBuffer>()
+            .remove_component::<Collider>(bike.0);
+    }
+}
+
 fn unpark(vehicle: VehicleID) -> impl FnOnce(&mut Egregoria) {
     move |goria| {
         let v = goria.comp::<Vehicle>(vehicle.0).unwrap();
@@ -268,6 +390,8 @@ impl Router {
             reroute: false,
             personal_car,
             vehicle: personal_car,
+            bike: None,
+            plan: None,
         }
     }
 
@@ -275,6 +399,25 @@ impl Router {
         self.vehicle = v;
     }
 
+    /// The vehicle used to go places, a truck for drivers at work
+    pub fn vehicle(&self) -> Option<VehicleID> {
+        self.vehicle
+    }
+
+    pub fn dest(&self) -> Option<Destination> {
+        self.dest
+    }
+
+    /// The mode and estimated time of the last trip planned
+    pub fn plan(&self) -> Option<TripPlan> {
+        self.plan
+    }
+
+    /// The step being done followed by the ones left to reach the destination
+    pub fn steps(&self) -> impl Iterator<Item = &RoutingStep> {
+        self.cur_step.iter().chain(self.steps.iter())
+    }
+
     fn clear_steps(&mut self, parking: &ParkingManagement) {
         for s in self.steps.drain(..) {
             if let RoutingStep::Park(_, spot) = s {
@@ -295,10 +438,15 @@ impl Router {
         false
     }
 
+    /// Compares walking, driving, biking and taking the bus by estimated travel time and returns the
+    /// steps of the fastest. Drivers at work and people already in their car always drive.
+    #[allow(clippy::too_many_arguments)]
     fn steps_to(
-        &self,
+        &mut self,
+        from: Vec2,
         obj: Vec2,
         parking: &ParkingManagement,
+        lines: &BusLines,
         map: &Map,
         loc: &Location,
         subworld: &SubWorld,
@@ -308,34 +456,118 @@ impl Router {
             steps.push(RoutingStep::GetOutBuilding(*cur_build));
         }
 
-        if let Some(car) = self.vehicle {
-            if let Some(spot_id) = parking.reserve_near(obj, map) {
-                let lane = map.parking_to_drive(spot_id).unwrap();
-                let spot = *map.parking.get(spot_id).unwrap();
+        let in_car = matches!(*loc, Location::Vehicle(v) if Some(v) == self.vehicle);
+        let must_drive = in_car || (self.vehicle.is_some() && self.vehicle != self.personal_car);
 
-                let (pos, _, dir) = map.lanes()[lane]
-                    .points
-                    .project_segment_dir(spot.trans.position());
-                let parking_pos = pos - dir * 4.0;
+        // safety: only pedestrians have transforms, not cars
+        let car = self.vehicle.and_then(|car| {
+            let carpos = subworld
+                .entry_ref(car.0)
+                .ok()?
+                .get_component::<Transform>()
+                .ok()?
+                .position();
+            Some((car, carpos))
+        });
 
-                if !matches!(loc, Location::Vehicle(_)) {
-                    // safety: only pedestrians have transforms, not cars
-                    let carpos = subworld
-                        .entry_ref(car.0)
-                        .unwrap()
-                        .get_component::<Transform>()
-                        .unwrap()
-                        .position();
+        let drive = car.and_then(|(_, carpos)| {
+            let spot = parking.find_near(obj, map)?;
+            let spot_pos = map.parking.get(spot)?.trans.position();
+            let walk = if in_car {
+                0.0
+            } else {
+                walk_time_approx(from, carpos)
+            };
+            Some(TripPlan {
+                mode: TravelMode::Drive,
+                cost: walk + drive_and_park_time(map, carpos, spot_pos, obj, !in_car)?,
+            })
+        });
 
-                    steps.push(RoutingStep::WalkTo(carpos));
-                    steps.push(RoutingStep::GetInVehicle(car));
-                    steps.push(RoutingStep::Unpark(car));
-                }
+        let transit = lines.best_trip(from, obj, walk_time_approx);
 
-                steps.push(RoutingStep::DriveTo(car, parking_pos));
-                steps.push(RoutingStep::Park(car, spot_id));
-                steps.push(RoutingStep::GetOutVehicle(car));
+        // bikes stay where they were left, like cars
+        let on_bike = matches!(*loc, Location::Vehicle(v) if Some(v) == self.bike);
+        let bike = self.bike.and_then(|bike| {
+            let bikepos = subworld
+                .entry_ref(bike.0)
+                .ok()?
+                .get_component::<Transform>()
+                .ok()?
+                .position();
+            Some((bike, bikepos))
+        });
+
+        let ride = bike.and_then(|(_, bikepos)| {
+            let walk = if on_bike {
+                0.0
+            } else {
+                walk_time_approx(from, bikepos)
+            };
+            Some(TripPlan {
+                mode: TravelMode::Bike,
+                cost: walk + bike_time(map, bikepos, obj)?,
+            })
+        });
+
+        self.plan = if must_drive {
+            drive
+        } else {
+            TripPlan::best(
+                std::iter::once(TripPlan {
+                    mode: TravelMode::Walk,
+                    cost: walk_time(map, from, obj),
+                })
+                .chain(drive)
+                .chain(ride)
+                .chain(transit.map(|(_, cost)| TripPlan {
+                    mode: TravelMode::Transit,
+                    cost,
+                })),
+            )
+        };
+
+        match self.plan.map(|p| p.mode) {
+            Some(TravelMode::Drive) => {
+                let (car, carpos) = car.unwrap(); // Unwrap ok: driving needs a car
+                if let Some(spot_id) = parking.reserve_near(obj, map) {
+                    let lane = map.parking_to_drive(spot_id).unwrap();
+                    let spot = *map.parking.get(spot_id).unwrap();
+
+                    let (pos, _, dir) = map.lanes()[lane]
+                        .points
+                        .project_segment_dir(spot.trans.position());
+                    let parking_pos = pos - dir * 4.0;
+
+                    if !in_car {
+                        steps.push(RoutingStep::WalkTo(carpos));
+                        steps.push(RoutingStep::GetInVehicle(car));
+                        steps.push(RoutingStep::Unpark(car));
+                    }
+
+                    steps.push(RoutingStep::DriveTo(car, parking_pos));
+                    steps.push(RoutingStep::Park(car, spot_id));
+                    steps.push(RoutingStep::GetOutVehicle(car));
+                }
+            }
+            Some(TravelMode::Transit) => {
+                let (trip, _) = transit.unwrap(); // Unwrap ok: chosen so it exists
+                if let Some(stop) = lines.stop(trip.line, trip.board) {
+                    steps.push(RoutingStep::WalkTo(stop.sidewalk));
+                    steps.push(RoutingStep::BoardBus(trip.line, trip.board));
+                    steps.push(RoutingStep::AlightBus(trip.line, trip.alight));
+                }
+            }
+            Some(TravelMode::Bike) => {
+                let (bike, bikepos) = bike.unwrap(); // Unwrap ok: chosen only with a bike
+                if !on_bike {
+                    steps.push(RoutingStep::WalkTo(bikepos));
+                    steps.push(RoutingStep::GetOnBike(bike));
+                }
+                steps.push(RoutingStep::DriveTo(bike, obj));
+                steps.push(RoutingStep::GetOffBike(bike));
             }
+            Some(TravelMode::Walk) | None => {}
         }
 
         steps.push(RoutingStep::WalkTo(obj));
@@ -0,0 +1,156 @@
+use crate::vehicles::{VehicleKind, TIME_TO_PARK};
+use geom::Vec2;
+use map_model::{BikePath, CarPath, Map, Pathfinder, PedestrianPath};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Walking speed used for estimates, in m/s
+pub const WALK_SPEED: f32 = 1.34;
+
+/// How much longer than a straight line a trip is when it isn't pathfound
+pub const DETOUR: f32 = 1.3;
+
+/// Time lost getting the car out of its spot and parking it at the end, in seconds
+const PARKING_TIME: f32 = 2.0 * TIME_TO_PARK;
+
+/// Time lost getting on the bike and locking it at the end, in seconds
+const BIKE_TIME: f32 = 20.0;
+
+/// Bikes are only used for trips shorter than this, as the crow flies
+pub const MAX_BIKE_DISTANCE: f32 = 3000.0;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum TravelMode {
+    Walk,
+    Drive,
+    Transit,
+    Bike,
+}
+
+/// The mode chosen for a trip and its estimated travel time in seconds
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct TripPlan {
+    pub mode: TravelMode,
+    pub cost: f32,
+}
+
+debug_inspect_impl!(TripPlan);
+
+impl TripPlan {
+    /// The fastest of the options, ties go to the first one
+    pub fn best(options: impl IntoIterator<Item = TripPlan>) -> Option<TripPlan> {
+        options
+            .into_iter()
+            .fold(None, |best: Option<TripPlan>, plan| match best {
+                Some(b) if b.cost <= plan.cost => Some(b),
+                _ => Some(plan),
+            })
+    }
+}
+
+/// Estimated time to walk between two positions, following the sidewalks when possible
+pub fn walk_time(map: &Map, from: Vec2, to: Vec2) -> f32 {
+    PedestrianPath
+        .path_length(map, from, to)
+        .unwrap_or_else(|| from.distance(to) * DETOUR)
+        / WALK_SPEED
+}
+
+/// Estimated time to walk without pathfinding, for the short walks around a trip
+pub fn walk_time_approx(from: Vec2, to: Vec2) -> f32 {
+    from.distance(to) * DETOUR / WALK_SPEED
+}
+
+/// Estimated time to drive between two positions, None if there is no road between them
+pub fn drive_time(map: &Map, from: Vec2, to: Vec2) -> Option<f32> {
+    Some(CarPath.path_length(map, from, to)? / VehicleKind::Car.cruising_speed())
+}
+
+/// Estimated time to ride a bike between two positions, None if the trip is too long
+pub fn bike_time(map: &Map, from: Vec2, to: Vec2) -> Option<f32> {
+    if from.distance(to) > MAX_BIKE_DISTANCE {
+        return None;
+    }
+    Some(BikePath.path_length(map, from, to)? / VehicleKind::Bike.cruising_speed() + BIKE_TIME)
+}
+
+/// Estimated time to get the car out, drive it to the spot and walk from there
+pub fn drive_and_park_time(
+    map: &Map,
+    car: Vec2,
+    spot: Vec2,
+    to: Vec2,
+    parked: bool,
+) -> Option<f32> {
+    let parking = if parked { PARKING_TIME } else { TIME_TO_PARK };
+    Some(drive_time(map, car, spot)? + parking + walk_time_approx(spot, to))
+}
+
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+pub struct ModeStats {
+    pub trips: u32,
+    /// Sum of the estimated travel times, in seconds
+    pub total_cost: f64,
+}
+
+impl ModeStats {
+    pub fn average_cost(&self) -> f64 {
+        if self.trips == 0 {
+            return 0.0;
+        }
+        self.total_cost / self.trips as f64
+    }
+}
+
+register_resource!(TripStats, "trip_stats");
+/// The trips planned since the start of the game by mode
+#[derive(Default, Serialize, Deserialize)]
+pub struct TripStats {
+    pub modes: BTreeMap<TravelMode, ModeStats>,
+}
+
+impl TripStats {
+    pub fn record(&mut self, plan: TripPlan) {
+        let stats = self.modes.entry(plan.mode).or_default();
+        stats.trips += 1;
+        stats.total_cost += plan.cost as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bike_time, TravelMode, TripPlan, MAX_BIKE_DISTANCE};
+    use geom::vec2;
+    use map_model::{BikePath, LaneKind, LanePatternBuilder, Map, Pathfinder, RoadSegmentKind};
+
+    #[test]
+    fn test_best() {
+        let plan = |mode, cost| TripPlan { mode, cost };
+        assert!(TripPlan::best(vec![]).is_none());
+
+        let best = TripPlan::best(vec![
+            plan(TravelMode::Walk, 300.0),
+            plan(TravelMode::Drive, 120.0),
+            plan(TravelMode::Transit, 120.0),
+        ])
+        .unwrap();
+        assert_eq!(best.mode, TravelMode::Drive);
+    }
+
+    #[test]
+    fn test_bike_lanes() {
+        let mut map = Map::empty();
+        let a = map.add_intersection(vec2(0.0, 0.0));
+        let b = map.add_intersection(vec2(200.0, 0.0));
+        let c = map.add_intersection(vec2(200.0 + MAX_BIKE_DISTANCE, 0.0));
+        let pattern = LanePatternBuilder::new().bike_lanes(true).build();
+        map.connect(a, b, &pattern, RoadSegmentKind::Straight);
+        map.connect(b, c, &pattern, RoadSegmentKind::Straight);
+
+        let lane = BikePath.nearest_lane(&map, vec2(10.0, 0.0)).unwrap();
+        assert_eq!(map.lanes()[lane].kind, LaneKind::Biking);
+
+        assert!(bike_time(&map, vec2(0.0, 0.0), vec2(200.0, 0.0)).is_some());
+        assert!(bike_time(&map, vec2(0.0, 0.0), vec2(200.0 + MAX_BIKE_DISTANCE, 0.0)).is_none());
+    }
+}
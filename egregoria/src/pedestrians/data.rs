@@ -12,7 +12,7 @@ use legion::Entity;
 use map_model::{BuildingID, Map};
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Inspect)]
+#[derive(Clone, Serialize, Deserialize, Inspect)]
 pub struct Pedestrian {
     pub walking_speed: f32,
     pub walk_anim: f32,
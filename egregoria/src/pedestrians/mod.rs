@@ -8,7 +8,7 @@ pub mod systems;
 pub use data::*;
 pub use systems::*;
 
-#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Location {
     Outside,
     Vehicle(VehicleID),
@@ -0,0 +1,100 @@
+use crate::pedestrians::Pedestrian;
+use crate::vehicles::Vehicle;
+use crate::Egregoria;
+use common::saveload::Archive;
+use common::{DayTime, GameTime};
+use geom::AABB;
+use legion::IntoQuery;
+use map_model::Map;
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Slot used when none is given
+pub const DEFAULT_SLOT: &str = "save";
+
+const AUTOSAVE_PREFIX: &str = "autosave_";
+
+/// Section holding the metadata. It is the first one of the archive so listing the slots
+/// doesn't need to read the whole saves.
+pub(crate) const META_SECTION: &str = "meta";
+
+/// Summary of a save, written along with it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SaveMeta {
+    pub daytime: DayTime,
+    /// Seconds since the unix epoch at which the save was made
+    pub timestamp: u64,
+    pub entities: u32,
+    pub pedestrians: u32,
+    pub vehicles: u32,
+    pub map_bounds: Option<AABB>,
+}
+
+impl SaveMeta {
+    pub fn new(goria: &Egregoria) -> Self {
+        Self {
+            daytime: goria.read::<GameTime>().daytime,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default(),
+            entities: goria.world.len() as u32,
+            pedestrians: <&Pedestrian>::query().iter(&goria.world).count() as u32,
+            vehicles: <&Vehicle>::query().iter(&goria.world).count() as u32,
+            map_bounds: goria.read::<Map>().bounds(),
+        }
+    }
+}
+
+pub struct SaveSlot {
+    pub name: String,
+    /// None if the metadata couldn't be read, the save is probably corrupted
+    pub meta: Option<SaveMeta>,
+}
+
+pub fn slot_meta(slot: &str) -> Option<SaveMeta> {
+    Archive::load_section(slot, META_SECTION)?.read()
+}
+
+/// All the save slots, most recent first
+pub fn list_slots() -> Vec<SaveSlot> {
+    let mut slots: Vec<SaveSlot> = Archive::list()
+        .into_iter()
+        .map(|name| SaveSlot {
+            meta: slot_meta(&name),
+            name,
+        })
+        .collect();
+    slots.sort_by_key(|s| std::cmp::Reverse(s.meta.as_ref().map(|m| m.timestamp)));
+    slots
+}
+
+pub fn latest_slot() -> Option<String> {
+    list_slots().into_iter().next().map(|s| s.name)
+}
+
+pub fn slot_exists(slot: &str) -> bool {
+    Archive::exists(slot)
+}
+
+pub fn delete_slot(slot: &str) -> Option<()> {
+    Archive::delete(slot)
+}
+
+pub fn duplicate_slot(src: &str, dst: &str) -> Option<()> {
+    Archive::duplicate(src, dst)
+}
+
+/// Autosaves rotate through `n` slots, returns the one to overwrite: the first one missing
+/// or else the oldest one.
+pub fn next_autosave_slot(n: u32) -> String {
+    let slots = (0..n.max(1)).map(|i| format!("{}{}", AUTOSAVE_PREFIX, i));
+    slots
+        .min_by_key(|slot| {
+            if !slot_exists(slot) {
+                return None;
+            }
+            Some(slot_meta(slot).map(|m| m.timestamp).unwrap_or_default())
+        })
+        .unwrap()
+}
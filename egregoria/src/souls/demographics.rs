@@ -0,0 +1,297 @@
+use crate::economy::{CommodityKind, Market};
+use crate::map_dynamic::{BuildingInfos, Router};
+use crate::pedestrians::Location;
+use crate::souls::desire::{Desire, Work};
+use crate::souls::household::{household_of, household_soul, return_car, share_cars, Household};
+use crate::souls::human::{leave_job, look_for_job, spawn_human, HUMAN_STARTING_MONEY};
+use crate::utils::rand_provider::RandProvider;
+use crate::{Egregoria, ParCommandBuffer, SoulID};
+use common::{GameInstant, GameTime, SECONDS_PER_DAY, SECONDS_PER_HOUR};
+use legion::world::SubWorld;
+use legion::{system, Entity, EntityStore, IntoQuery};
+use map_model::{BuildingID, BuildingKind, Map};
+use serde::{Deserialize, Serialize};
+
+/// A year passes every game day, so that generations come and go during a game
+pub const DAYS_PER_YEAR: i32 = 1;
+
+pub const ADULT_AGE: i32 = 18;
+pub const RETIREMENT_AGE: i32 = 65;
+const MAX_AGE: i32 = 105;
+
+/// Households don't grow beyond this through births
+pub const MAX_HOUSEHOLD_SIZE: usize = 5;
+
+/// Chance each day for a household with two adults young enough to have a child
+const BIRTH_CHANCE_PER_DAY: f32 = 0.05;
+const PARENT_AGES: std::ops::RangeInclusive<i32> = 20..=45;
+
+/// Humans of working age out of work for this long leave the city, in seconds
+const MOVE_OUT_AFTER: f64 = 3.0 * SECONDS_PER_DAY as f64;
+
+/// Households arriving in the city at most each hour when jobs are open
+const MAX_IMMIGRANTS_PER_HOUR: usize = 20;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Person {
+    pub household: SoulID,
+    /// Game day the person was born, before the start of the game for the first inhabitants
+    pub birth_day: i32,
+    /// Since when a person of working age has been without a job
+    pub jobless_since: Option<GameInstant>,
+}
+
+impl Person {
+    pub fn new(today: i32, age: i32, household: SoulID) -> Self {
+        Self {
+            household,
+            birth_day: today - age * DAYS_PER_YEAR,
+            jobless_since: None,
+        }
+    }
+
+    pub fn age(&self, today: i32) -> i32 {
+        (today - self.birth_day) / DAYS_PER_YEAR
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Departure {
+    Death,
+    MovedOut,
+}
+
+register_resource!(PopulationStats, "population_stats");
+#[derive(Copy, Clone, Default, Serialize, Deserialize)]
+pub struct PopulationStats {
+    pub births: u32,
+    pub deaths: u32,
+    pub emigrants: u32,
+    pub immigrants: u32,
+}
+
+/// Chance to die within the hour, nobody dies before 60
+fn death_chance_per_hour(age: i32) -> f32 {
+    if age >= MAX_AGE {
+        return 1.0;
+    }
+    let old = (age - 60).max(0) as f32 / 40.0;
+    old * old * 0.5 / 24.0
+}
+
+/// Removes a human from the city with its bike. Its car and its money go back to the household, which
+/// leaves the house when nobody is left in it.
+pub fn remove_human(goria: &mut Egregoria, human: SoulID, why: Departure) {
+    log::info!("{:?} left the city: {:?}", human, why);
+
+    leave_job(goria, human);
+    let household = household_of(goria, human);
+    let money = goria.write::<Market>().remove_agent(human);
+
+    {
+        let mut stats = goria.write::<PopulationStats>();
+        match why {
+            Departure::Death => stats.deaths += 1,
+            Departure::MovedOut => stats.emigrants += 1,
+        }
+    }
+
+    let mut binfos = goria.write::<BuildingInfos>();
+    let house = binfos.remove_resident(human);
+    if let Some(house) = house {
+        binfos.forget_inside(house, human);
+    }
+    let empty = house.filter(|&h| binfos.residents(h).is_empty()).is_some();
+    drop(binfos);
+
+    let bike = goria.comp::<Router>(human.0).and_then(|r| r.bike);
+    return_car(goria, household, human);
+    let cbuf = goria.read::<ParCommandBuffer>();
+    if let Some(bike) = bike {
+        cbuf.kill(bike.0);
+    }
+    cbuf.kill(human.0);
+    drop(cbuf);
+
+    if !empty {
+        goria.write::<Market>().give_money(household, money);
+        return;
+    }
+
+    goria.write::<Market>().remove_agent(household);
+    goria.write::<BuildingInfos>().remove_owner(household);
+    let cbuf = goria.read::<ParCommandBuffer>();
+    if let Some(h) = goria.comp::<Household>(household.0) {
+        for car in &h.cars {
+            cbuf.kill(car.0);
+        }
+    }
+    cbuf.kill(household.0);
+}
+
+fn retire(goria: &mut Egregoria, human: SoulID) {
+    leave_job(goria, human);
+    if let Some(p) = goria.comp_mut::<Person>(human.0) {
+        p.jobless_since = None;
+    }
+}
+
+fn come_of_age(goria: &mut Egregoria, human: SoulID) {
+    look_for_job(goria, human);
+    let household = household_of(goria, human);
+    share_cars(goria, household);
+}
+
+fn give_birth(goria: &mut Egregoria, household: SoulID) {
+    let house = unwrap_or!(goria.comp::<Household>(household.0), return).house;
+    if goria.read::<BuildingInfos>().residents(house).len() >= MAX_HOUSEHOLD_SIZE {
+        return;
+    }
+    spawn_human(goria, household, house, 0);
+    goria.write::<PopulationStats>().births += 1;
+}
+
+register_system!(demographics);
+/// Humans age, have children, die, and leave the city when they can't find work.
+/// Changes only happen to people at home so that nobody disappears on the road.
+#[system]
+#[write_component(Person)]
+#[read_component(Location)]
+#[read_component(Desire<Work>)]
+#[read_component(Household)]
+pub fn demographics(
+    #[resource] time: &GameTime,
+    #[resource] rng: &mut RandProvider,
+    #[resource] binfos: &BuildingInfos,
+    #[resource] cbuf: &ParCommandBuffer,
+    world: &mut SubWorld,
+) {
+    if !time.tick(SECONDS_PER_HOUR as u32) {
+        return;
+    }
+    let today = time.daytime.day;
+    let new_day = time.tick(SECONDS_PER_DAY as u32);
+
+    let mut query = <(Entity, &mut Person, &Location, Option<&Desire<Work>>)>::query();
+    for (&e, person, loc, work) in query.iter_mut(world) {
+        let soul = SoulID(e);
+        let age = person.age(today);
+
+        if new_day && age == ADULT_AGE {
+            cbuf.exec_ent(e, move |goria| come_of_age(goria, soul));
+        }
+        if new_day && age == RETIREMENT_AGE {
+            cbuf.exec_ent(e, move |goria| retire(goria, soul));
+        }
+
+        if (ADULT_AGE..RETIREMENT_AGE).contains(&age) && work.is_none() {
+            person.jobless_since.get_or_insert(time.instant());
+        } else {
+            person.jobless_since = None;
+        }
+
+        let at_home = matches!(*loc, Location::Building(b) if binfos.home_of(soul) == Some(b));
+        if !at_home {
+            continue;
+        }
+
+        if rng.random::<f32>() < death_chance_per_hour(age) {
+            cbuf.exec_ent(e, move |goria| remove_human(goria, soul, Departure::Death));
+            continue;
+        }
+
+        if let Some(since) = person.jobless_since {
+            if since.elapsed(time) > MOVE_OUT_AFTER {
+                cbuf.exec_ent(e, move |goria| {
+                    remove_human(goria, soul, Departure::MovedOut)
+                });
+            }
+        }
+    }
+
+    let mut households = <(Entity, &Household)>::query();
+    for (&e, household) in households.iter(world) {
+        let members = binfos.residents(household.house);
+        if members.len() >= MAX_HOUSEHOLD_SIZE {
+            continue;
+        }
+        let parents = members
+            .iter()
+            .filter_map(|s| world.entry_ref(s.0).ok())
+            .filter_map(|e| e.get_component::<Person>().ok().map(|p| p.age(today)))
+            .filter(|age| PARENT_AGES.contains(age))
+            .count();
+        if parents < 2 || rng.random::<f32>() >= BIRTH_CHANCE_PER_DAY / 24.0 {
+            continue;
+        }
+        cbuf.exec_ent(e, move |goria| give_birth(goria, SoulID(e)));
+    }
+}
+
+/// Spawns a household of one or two adults, couples may have children and a second car
+pub fn spawn_household(goria: &mut Egregoria, house: BuildingID) {
+    let mut rng = goria.write::<RandProvider>();
+    let n_adults = if rng.random::<f32>() < 0.6 { 2 } else { 1 };
+    let n_cars = if n_adults == 2 && rng.random::<f32>() < 0.5 {
+        2
+    } else {
+        1
+    };
+    let n_children = if n_adults == 2 {
+        rng.rand_range(0, 3)
+    } else {
+        0
+    };
+    let mut ages: Vec<i32> = (0..n_adults)
+        .map(|_| rng.rand_range(ADULT_AGE as i64 + 2, 56) as i32)
+        .collect();
+    ages.extend((0..n_children).map(|_| rng.rand_range(0, ADULT_AGE as i64) as i32));
+    drop(rng);
+
+    let household = household_soul(goria, house, n_cars);
+    goria
+        .write::<Market>()
+        .give_money(household, HUMAN_STARTING_MONEY * n_adults);
+    for age in ages {
+        spawn_human(goria, household, house, age);
+    }
+    share_cars(goria, household);
+    goria.write::<PopulationStats>().immigrants += 1;
+}
+
+/// Households move into empty houses when there are more open jobs than people looking for one
+pub fn immigration(goria: &mut Egregoria) {
+    if !goria.read::<GameTime>().tick(SECONDS_PER_HOUR as u32) {
+        return;
+    }
+
+    let market = goria.read::<Market>();
+    let jobs = unwrap_or!(market.market(CommodityKind::JOB_OPENING), return);
+    let open_jobs = jobs.stock() as usize;
+    let seekers = jobs.n_buy_orders();
+    drop(market);
+    if open_jobs <= seekers {
+        return;
+    }
+    // Households have about one and a half adult
+    let wanted = ((open_jobs - seekers) * 2 / 3).clamp(1, MAX_IMMIGRANTS_PER_HOUR);
+
+    let map = goria.read::<Map>();
+    let binfos = goria.read::<BuildingInfos>();
+    let empty: Vec<BuildingID> = map
+        .buildings()
+        .iter()
+        .filter(|(id, b)| {
+            matches!(b.kind, BuildingKind::House)
+                && matches!(binfos.get(*id), Some(info) if info.owner.is_none())
+        })
+        .map(|(id, _)| id)
+        .take(wanted)
+        .collect();
+    drop(binfos);
+    drop(map);
+
+    for house in empty {
+        spawn_household(goria, house);
+    }
+}
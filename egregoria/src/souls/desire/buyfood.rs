@@ -1,71 +1,108 @@
-use crate::economy::{Bought, CommodityKind, Market};
-use crate::map_dynamic::{BuildingInfos, Destination, Router};
-use crate::souls::desire::Desire;
-use crate::{ParCommandBuffer, SoulID};
-use common::{GameInstant, GameTime};
+use crate::economy::{Market, Stores};
+use crate::map_dynamic::{Destination, Router};
+use crate::souls::demographics::Person;
+use crate::souls::desire::{
+    update_store_trip, Desire, DesireKind, Desires, Home, NeedKind, Needs, StoreTrip,
+    StoreTripDesire,
+};
+use crate::souls::household::{food_in_stock, FOOD_BATCH};
+use crate::{Egregoria, ParCommandBuffer, SoulID};
 use geom::Transform;
 use legion::{system, Entity};
-use map_model::BuildingID;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
-pub enum BuyFoodState {
-    Empty,
-    WaitingForTrade,
-    BoughtAt(BuildingID),
+/// Eating from the household food stock, or buying some more at the store when it is empty
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BuyFood {
+    pub trip: StoreTrip,
+    pub eating: bool,
 }
 
-#[derive(Serialize, Deserialize)]
-pub struct BuyFood {
-    last_ate: GameInstant,
-    state: BuyFoodState,
+impl Default for BuyFood {
+    fn default() -> Self {
+        Self {
+            trip: StoreTrip::Empty,
+            eating: false,
+        }
+    }
+}
+
+impl DesireKind for BuyFood {
+    const NAME: &'static str = "buy_food";
+}
+
+impl StoreTripDesire for BuyFood {
+    const NEED: NeedKind = NeedKind::Food;
+    const QTY: i32 = FOOD_BATCH;
+
+    fn trip(&mut self) -> &mut StoreTrip {
+        &mut self.trip
+    }
 }
 
-impl BuyFood {
-    pub fn new(start: GameInstant) -> Self {
-        BuyFood {
-            last_ate: start,
-            state: BuyFoodState::Empty,
+fn eat_at_home(goria: &mut Egregoria, soul: SoulID, household: SoulID) {
+    let mut market = goria.write::<Market>();
+    let food = food_in_stock(&market, household);
+    if let Some(kind) = food {
+        market.produce(household, kind, -1);
+    }
+    drop(market);
+
+    if food.is_some() {
+        if let Some(needs) = goria.comp_mut::<Needs>(soul.0) {
+            needs.satisfy(NeedKind::Food, 1.0);
         }
     }
+
+    if let Some(d) = goria.comp_mut::<Desire<BuyFood>>(soul.0) {
+        d.v.eating = false;
+    }
 }
 
 register_system!(desire_buy_food);
 #[system(par_for_each)]
+#[allow(clippy::too_many_arguments)]
 pub fn desire_buy_food(
     #[resource] cbuf: &ParCommandBuffer,
-    #[resource] binfos: &BuildingInfos,
-    #[resource] time: &GameTime,
+    #[resource] stores: &Stores,
+    #[resource] market: &Market,
     me: &Entity,
     trans: &Transform,
     router: &mut Router,
+    needs: &Needs,
+    person: &Person,
+    home: &Desire<Home>,
+    desires: &mut Desires,
     d: &mut Desire<BuyFood>,
-    bought: &mut Bought,
 ) {
     let soul = SoulID(*me);
+    let household = person.household;
     let pos = trans.position();
     d.score_and_apply(
-        move |buy_food| buy_food.last_ate.elapsed(time) as f32 * 0.001 - 1.0,
-        move |buy_food| match buy_food.state {
-            BuyFoodState::Empty => {
-                cbuf.exec_on(move |market: &mut Market| {
-                    market.buy(soul, pos, CommodityKind::Bread, 1)
-                });
-                buy_food.state = BuyFoodState::WaitingForTrade;
-            }
-            BuyFoodState::WaitingForTrade => {
-                for trade in bought.0.entry(CommodityKind::Bread).or_default().drain(..) {
-                    if let Some(b) = binfos.building_owned_by(trade.seller) {
-                        buy_food.state = BuyFoodState::BoughtAt(b);
-                    }
-                }
+        desires,
+        |_| needs.urgency(BuyFood::NEED),
+        |buy_food| {
+            if buy_food.eating {
+                return;
             }
-            BuyFoodState::BoughtAt(b) => {
-                if router.go_to(Destination::Building(b)) {
-                    buy_food.state = BuyFoodState::Empty;
-                    buy_food.last_ate = time.instant();
+            let shopping = !matches!(buy_food.trip, StoreTrip::Empty);
+            if !shopping && food_in_stock(market, household).is_some() {
+                if router.go_to(Destination::Building(home.v.house())) {
+                    buy_food.eating = true;
+                    cbuf.exec_ent(soul.0, move |goria| eat_at_home(goria, soul, household));
                 }
+                return;
             }
+            update_store_trip::<BuyFood>(
+                &mut buy_food.trip,
+                soul,
+                household,
+                pos,
+                router,
+                stores,
+                market,
+                cbuf,
+            )
         },
     );
 }
@@ -1,10 +1,14 @@
 use crate::map_dynamic::{Destination, Router};
-use crate::souls::desire::Desire;
+use crate::souls::desire::{Desire, DesireKind, Desires, NeedKind, Needs};
+use common::{GameTime, SECONDS_PER_HOUR};
 use legion::system;
 use map_model::BuildingID;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+/// Sleep recovered for each hour spent at home
+const SLEEP_PER_HOUR: f32 = 1.0 / 5.0;
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Home {
     house: BuildingID,
 }
@@ -13,15 +17,35 @@ impl Home {
     pub fn new(house: BuildingID) -> Self {
         Home { house }
     }
+
+    pub fn house(&self) -> BuildingID {
+        self.house
+    }
+}
+
+impl DesireKind for Home {
+    const NAME: &'static str = "home";
 }
 
 register_system!(desire_home);
 #[system(par_for_each)]
-pub fn desire_home(router: &mut Router, d: &mut Desire<Home>) {
+pub fn desire_home(
+    #[resource] time: &GameTime,
+    router: &mut Router,
+    needs: &mut Needs,
+    desires: &mut Desires,
+    d: &mut Desire<Home>,
+) {
+    // Staying home is what humans do when nothing else is needed
+    let score = needs.urgency(NeedKind::Sleep).max(0.2);
     d.score_and_apply(
-        |_| 0.2,
+        desires,
+        |_| score,
         |home| {
-            router.go_to(Destination::Building(home.house));
+            if router.go_to(Destination::Building(home.house)) {
+                let hours = time.delta / SECONDS_PER_HOUR as f32;
+                needs.satisfy(NeedKind::Sleep, SLEEP_PER_HOUR * hours);
+            }
         },
     );
 }
@@ -1,70 +1,156 @@
+use legion::system;
+use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
 
 mod buyfood;
 mod home;
+mod needs;
+mod shopping;
+mod visit_friend;
 mod work;
 
 pub use buyfood::*;
 pub use home::*;
+pub use needs::*;
+pub use shopping::*;
+pub use visit_friend::*;
 pub use work::*;
 
-#[derive(Serialize, Deserialize)]
+/// Implemented by the values of [Desire], the name identifies the desire in [Desires]
+pub trait DesireKind {
+    const NAME: &'static str;
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Desire<T> {
     pub score: f32,
-    pub was_max: bool,
     pub v: T,
 }
 
-impl<T> Desire<T> {
+impl<T: DesireKind> Desire<T> {
     pub fn new(v: T) -> Self {
-        Self {
-            score: 0.0,
-            was_max: false,
-            v,
-        }
+        Self { score: 0.0, v }
     }
 
-    pub fn score_and_apply(&mut self, score: impl FnOnce(&T) -> f32, apply: impl FnOnce(&mut T)) {
-        if self.was_max {
+    /// Applies the desire if it is the one the soul acts on, then updates its score
+    pub fn score_and_apply(
+        &mut self,
+        desires: &mut Desires,
+        score: impl FnOnce(&T) -> f32,
+        apply: impl FnOnce(&mut T),
+    ) {
+        if desires.is_chosen(T::NAME) {
             apply(&mut self.v);
         }
         self.score = score(&self.v);
+        desires.set_score(T::NAME, self.score);
     }
 }
 
-macro_rules! desires_system {
-    ( $system_name: ident, $marker: ty, $($t:tt;$idx: literal)+) => (
-    use crate::souls::desire::Desire;
-    use legion::system;
-
-    register_system!($system_name);
-    #[system(par_for_each)]
-    #[allow(non_snake_case)]
-    #[allow(unused_assignments)]
-    pub fn $system_name($(_: &$marker, mut $t: Option<&mut Desire<$t>>),+) {
-        let mut max_score = f32::NEG_INFINITY;
-        let mut max_idx = -1;
-        $(
-        if let Some(ref mut v) = $t {
-          let score = v.score;
-          v.was_max = false;
-          if score > max_score {
-            max_score = score;
-            max_idx = $idx;
-          }
+/// The scores of all the desires of a soul, it acts on the one with the highest score.
+/// Each desire system writes its own score so adding a desire only takes a component and
+/// its system.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Desires {
+    scores: BTreeMap<String, f32>,
+    chosen: Option<String>,
+}
+
+impl Desires {
+    pub fn is_chosen(&self, name: &str) -> bool {
+        self.chosen.as_deref() == Some(name)
+    }
+
+    pub fn chosen(&self) -> Option<&str> {
+        self.chosen.as_deref()
+    }
+
+    pub fn scores(&self) -> impl Iterator<Item = (&str, f32)> {
+        self.scores.iter().map(|(name, &score)| (&**name, score))
+    }
+
+    fn set_score(&mut self, name: &'static str, score: f32) {
+        match self.scores.get_mut(name) {
+            Some(v) => *v = score,
+            None => {
+                self.scores.insert(name.to_string(), score);
+            }
         }
-        )+
+    }
 
-        if max_idx == -1 {
-            return;
+    /// Called when a desire component is removed from the soul
+    pub fn remove<T: DesireKind>(&mut self) {
+        self.scores.remove(T::NAME);
+        if self.is_chosen(T::NAME) {
+            self.chosen = None;
         }
+    }
+}
+
+register_system!(choose_desire);
+/// Picks the desire with the highest score. On ties the soul keeps doing what it was doing,
+/// otherwise it picks the first desire by name so the choice doesn't depend on the system order.
+#[system(par_for_each)]
+pub fn choose_desire(desires: &mut Desires) {
+    let chosen = desires.chosen.as_deref();
+    let best = desires
+        .scores
+        .iter()
+        .max_by_key(|(name, &score)| {
+            (
+                OrderedFloat(score),
+                Some(name.as_str()) == chosen,
+                Reverse(name.as_str()),
+            )
+        })
+        .map(|(name, _)| name);
+    if best != desires.chosen.as_ref() {
+        desires.chosen = best.cloned();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{choose_desire, Desires};
 
-        match max_idx {
-        $(
-            $idx => $t.unwrap().was_max = true,
-        )+
-        _ => unreachable!(),
+    fn scored(scores: &[(&'static str, f32)]) -> Desires {
+        let mut desires = Desires::default();
+        for &(name, score) in scores {
+            desires.set_score(name, score);
         }
+        desires
+    }
+
+    #[test]
+    fn test_choose_desire() {
+        let mut desires = scored(&[("home", 0.2), ("work", 0.5), ("buy_food", 0.3)]);
+        choose_desire(&mut desires);
+        assert_eq!(desires.chosen(), Some("work"));
+
+        desires.set_score("work", 0.0);
+        choose_desire(&mut desires);
+        assert_eq!(desires.chosen(), Some("buy_food"));
+    }
+
+    #[test]
+    fn test_choose_desire_ties() {
+        // Nothing chosen yet, the first by name
+        let mut desires = scored(&[("work", 0.5), ("home", 0.5), ("leisure", 0.1)]);
+        choose_desire(&mut desires);
+        assert_eq!(desires.chosen(), Some("home"));
+
+        // The chosen desire is kept as long as nothing scores higher
+        desires.set_score("work", 0.6);
+        choose_desire(&mut desires);
+        assert_eq!(desires.chosen(), Some("work"));
+        desires.set_score("home", 0.6);
+        choose_desire(&mut desires);
+        assert_eq!(desires.chosen(), Some("work"));
+
+        desires.set_score("work", 0.0);
+        choose_desire(&mut desires);
+        assert_eq!(desires.chosen(), Some("home"));
     }
-    );
 }
@@ -0,0 +1,129 @@
+use common::{GameTime, SECONDS_PER_HOUR};
+use legion::system;
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum NeedKind {
+    Food,
+    Sleep,
+    Leisure,
+    Shopping,
+    Social,
+}
+
+impl NeedKind {
+    pub const ALL: [NeedKind; 5] = [
+        NeedKind::Food,
+        NeedKind::Sleep,
+        NeedKind::Leisure,
+        NeedKind::Shopping,
+        NeedKind::Social,
+    ];
+
+    /// How much of the need is lost each hour
+    pub fn decay_per_hour(self) -> f32 {
+        match self {
+            NeedKind::Food => 1.0 / 15.0,
+            NeedKind::Sleep => 1.0 / 16.0,
+            NeedKind::Leisure => 1.0 / 24.0,
+            NeedKind::Shopping => 1.0 / 72.0,
+            NeedKind::Social => 1.0 / 48.0,
+        }
+    }
+
+    /// How important the need is compared to the others, a work shift scores 0.5
+    pub fn weight(self) -> f32 {
+        match self {
+            NeedKind::Food => 1.5,
+            NeedKind::Sleep => 1.2,
+            NeedKind::Leisure => 0.8,
+            NeedKind::Shopping => 0.7,
+            NeedKind::Social => 0.8,
+        }
+    }
+}
+
+/// How satisfied a human is for each need, from 0 (desperate) to 1 (fully satisfied).
+/// Needs decay over time and are restored by the desires satisfying them.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Needs {
+    levels: [f32; NeedKind::ALL.len()],
+}
+
+impl Default for Needs {
+    fn default() -> Self {
+        Self {
+            levels: [1.0; NeedKind::ALL.len()],
+        }
+    }
+}
+
+impl Needs {
+    pub fn get(&self, need: NeedKind) -> f32 {
+        self.levels[need as usize]
+    }
+
+    pub fn satisfy(&mut self, need: NeedKind, amount: f32) {
+        let v = &mut self.levels[need as usize];
+        *v = (*v + amount).min(1.0);
+    }
+
+    /// Score of the desire satisfying the need, grows as the need decays
+    pub fn urgency(&self, need: NeedKind) -> f32 {
+        need.weight() * (1.0 - self.get(need))
+    }
+}
+
+register_system!(needs_decay);
+#[system(par_for_each)]
+pub fn needs_decay(#[resource] time: &GameTime, needs: &mut Needs) {
+    let hours = time.delta / SECONDS_PER_HOUR as f32;
+    for need in NeedKind::ALL.iter().copied() {
+        let v = &mut needs.levels[need as usize];
+        *v = (*v - need.decay_per_hour() * hours).max(0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{needs_decay, NeedKind, Needs};
+    use common::{GameTime, SECONDS_PER_HOUR};
+
+    #[test]
+    fn test_needs_decay() {
+        let mut needs = Needs::default();
+        let time = GameTime::new(3.0 * SECONDS_PER_HOUR as f32, 0.0);
+        needs_decay(&time, &mut needs);
+
+        assert!((needs.get(NeedKind::Food) - (1.0 - 3.0 / 15.0)).abs() < 1e-5);
+        assert!((needs.get(NeedKind::Shopping) - (1.0 - 3.0 / 72.0)).abs() < 1e-5);
+
+        // Needs never go below 0
+        let time = GameTime::new(100.0 * SECONDS_PER_HOUR as f32, 0.0);
+        needs_decay(&time, &mut needs);
+        for &need in &NeedKind::ALL {
+            assert_eq!(needs.get(need), 0.0);
+        }
+
+        needs.satisfy(NeedKind::Food, 2.0);
+        assert_eq!(needs.get(NeedKind::Food), 1.0);
+    }
+
+    #[test]
+    fn test_urgency() {
+        let mut needs = Needs::default();
+        for &need in &NeedKind::ALL {
+            assert_eq!(needs.urgency(need), 0.0);
+        }
+
+        // As hungry as tired, food is more urgent
+        needs.levels[NeedKind::Food as usize] = 0.5;
+        needs.levels[NeedKind::Sleep as usize] = 0.5;
+        assert!((needs.urgency(NeedKind::Food) - 0.75).abs() < 1e-5);
+        assert!(needs.urgency(NeedKind::Food) > needs.urgency(NeedKind::Sleep));
+
+        // A need left alone long enough beats a work shift
+        needs.levels[NeedKind::Leisure as usize] = 0.3;
+        assert!(needs.urgency(NeedKind::Leisure) > 0.5);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::economy::{CommodityKind, EconomyStats, Market, Store, Stores};
+use crate::map_dynamic::{Destination, Router};
+use crate::souls::demographics::Person;
+use crate::souls::desire::{Desire, DesireKind, Desires, NeedKind, Needs};
+use crate::{Egregoria, ParCommandBuffer, SoulID};
+use geom::{Transform, Vec2};
+use legion::storage::Component;
+use legion::{system, Entity};
+use serde::{Deserialize, Serialize};
+
+/// A trip to the closest store selling something that satisfies a need, the goods are bought
+/// at the door.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum StoreTrip {
+    Empty,
+    GoingTo(Store, CommodityKind),
+    /// At the door of the store, waiting for the purchase to go through
+    Buying,
+}
+
+/// Implemented by the desires satisfied by buying something in a store
+pub trait StoreTripDesire: DesireKind + Component + Sized {
+    const NEED: NeedKind;
+    /// Units bought at once, one is used right away and the others are stocked by the household
+    const QTY: i32 = 1;
+
+    fn trip(&mut self) -> &mut StoreTrip;
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn update_store_trip<T: StoreTripDesire>(
+    trip: &mut StoreTrip,
+    soul: SoulID,
+    household: SoulID,
+    pos: Vec2,
+    router: &mut Router,
+    stores: &Stores,
+    market: &Market,
+    cbuf: &ParCommandBuffer,
+) {
+    match *trip {
+        StoreTrip::Empty => {
+            let budget = market.money(household);
+            if let Some((store, kind)) = stores.closest_satisfying(market, T::NEED, pos, budget) {
+                *trip = StoreTrip::GoingTo(store, kind);
+            }
+        }
+        StoreTrip::GoingTo(store, kind) => {
+            if !router.go_to(Destination::Building(store.building)) {
+                return;
+            }
+            *trip = StoreTrip::Buying;
+            cbuf.exec_ent(soul.0, move |goria| {
+                buy_at_store::<T>(goria, soul, household, store, kind)
+            });
+        }
+        StoreTrip::Buying => {}
+    }
+}
+
+/// The household pays for the goods, as many as it can afford up to [StoreTripDesire::QTY]
+fn buy_at_store<T: StoreTripDesire>(
+    goria: &mut Egregoria,
+    soul: SoulID,
+    household: SoulID,
+    store: Store,
+    kind: CommodityKind,
+) {
+    let mut market = goria.write::<Market>();
+    let price = Stores::retail_price(&market, kind).cents().max(1);
+    let affordable = (market.money(household).cents() / price) as i32;
+    let qty = T::QTY
+        .min(affordable)
+        .min(market.capital(store.soul, kind))
+        .max(1);
+    let trade = Stores::buy_on_site(&mut market, household, store, kind, qty);
+    if trade.is_some() && qty > 1 {
+        market.produce(household, kind, qty - 1);
+    }
+    drop(market);
+
+    if let Some(ref trade) = trade {
+        goria.write::<EconomyStats>().record_trade(trade);
+        if let Some(needs) = goria.comp_mut::<Needs>(soul.0) {
+            needs.satisfy(T::NEED, 1.0);
+        }
+    }
+
+    let d = unwrap_or!(goria.comp_mut::<Desire<T>>(soul.0), return);
+    *d.v.trip() = StoreTrip::Empty;
+}
+
+/// Buying clothes or furniture
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Shopping {
+    pub trip: StoreTrip,
+}
+
+impl Default for Shopping {
+    fn default() -> Self {
+        Self {
+            trip: StoreTrip::Empty,
+        }
+    }
+}
+
+impl DesireKind for Shopping {
+    const NAME: &'static str = "shopping";
+}
+
+impl StoreTripDesire for Shopping {
+    const NEED: NeedKind = NeedKind::Shopping;
+
+    fn trip(&mut self) -> &mut StoreTrip {
+        &mut self.trip
+    }
+}
+
+register_system!(desire_shopping);
+#[system(par_for_each)]
+#[allow(clippy::too_many_arguments)]
+pub fn desire_shopping(
+    #[resource] cbuf: &ParCommandBuffer,
+    #[resource] stores: &Stores,
+    #[resource] market: &Market,
+    me: &Entity,
+    trans: &Transform,
+    router: &mut Router,
+    needs: &Needs,
+    person: &Person,
+    desires: &mut Desires,
+    d: &mut Desire<Shopping>,
+) {
+    let soul = SoulID(*me);
+    let pos = trans.position();
+    d.score_and_apply(
+        desires,
+        |_| needs.urgency(Shopping::NEED),
+        |shopping| {
+            update_store_trip::<Shopping>(
+                &mut shopping.trip,
+                soul,
+                person.household,
+                pos,
+                router,
+                stores,
+                market,
+                cbuf,
+            )
+        },
+    );
+}
+
+/// Buying something to have a good time, like flowers or high tech products
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Leisure {
+    pub trip: StoreTrip,
+}
+
+impl Default for Leisure {
+    fn default() -> Self {
+        Self {
+            trip: StoreTrip::Empty,
+        }
+    }
+}
+
+impl DesireKind for Leisure {
+    const NAME: &'static str = "leisure";
+}
+
+impl StoreTripDesire for Leisure {
+    const NEED: NeedKind = NeedKind::Leisure;
+
+    fn trip(&mut self) -> &mut StoreTrip {
+        &mut self.trip
+    }
+}
+
+register_system!(desire_leisure);
+#[system(par_for_each)]
+#[allow(clippy::too_many_arguments)]
+pub fn desire_leisure(
+    #[resource] cbuf: &ParCommandBuffer,
+    #[resource] stores: &Stores,
+    #[resource] market: &Market,
+    me: &Entity,
+    trans: &Transform,
+    router: &mut Router,
+    needs: &Needs,
+    person: &Person,
+    desires: &mut Desires,
+    d: &mut Desire<Leisure>,
+) {
+    let soul = SoulID(*me);
+    let pos = trans.position();
+    d.score_and_apply(
+        desires,
+        |_| needs.urgency(Leisure::NEED),
+        |leisure| {
+            update_store_trip::<Leisure>(
+                &mut leisure.trip,
+                soul,
+                person.household,
+                pos,
+                router,
+                stores,
+                market,
+                cbuf,
+            )
+        },
+    );
+}
@@ -0,0 +1,46 @@
+use crate::map_dynamic::{Destination, Router};
+use crate::souls::desire::{Desire, DesireKind, Desires, NeedKind, Needs};
+use common::{GameTime, SECONDS_PER_HOUR};
+use legion::system;
+use map_model::BuildingID;
+use serde::{Deserialize, Serialize};
+
+/// Social need recovered for each hour spent at a friend's house
+const SOCIAL_PER_HOUR: f32 = 1.0 / 2.0;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VisitFriend {
+    friend_house: BuildingID,
+}
+
+impl VisitFriend {
+    pub fn new(friend_house: BuildingID) -> Self {
+        VisitFriend { friend_house }
+    }
+}
+
+impl DesireKind for VisitFriend {
+    const NAME: &'static str = "visit_friend";
+}
+
+register_system!(desire_visit_friend);
+#[system(par_for_each)]
+pub fn desire_visit_friend(
+    #[resource] time: &GameTime,
+    router: &mut Router,
+    needs: &mut Needs,
+    desires: &mut Desires,
+    d: &mut Desire<VisitFriend>,
+) {
+    let score = needs.urgency(NeedKind::Social);
+    d.score_and_apply(
+        desires,
+        |_| score,
+        |visit| {
+            if router.go_to(Destination::Building(visit.friend_house)) {
+                let hours = time.delta / SECONDS_PER_HOUR as f32;
+                needs.satisfy(NeedKind::Social, SOCIAL_PER_HOUR * hours);
+            }
+        },
+    );
+}
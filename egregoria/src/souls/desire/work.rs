@@ -1,8 +1,11 @@
 use crate::map_dynamic::{Destination, Router};
-use crate::souls::desire::Desire;
+use crate::souls::desire::{Desire, DesireKind, Desires};
+use crate::souls::goods_company::unload_truck;
+use crate::souls::schedule::WorkSchedule;
 use crate::vehicles::VehicleID;
-use common::{GameTime, RecTimeInterval, SECONDS_PER_HOUR};
-use legion::system;
+use crate::{ParCommandBuffer, SoulID};
+use common::GameTime;
+use legion::{system, Entity};
 use map_model::BuildingID;
 use serde::{Deserialize, Serialize};
 
@@ -11,6 +14,8 @@ pub enum DriverState {
     GoingToWork,
     WaitingForDelivery,
     Delivering(BuildingID),
+    /// At a stop, waiting for the goods to be handed to the buyer
+    Unloading,
     DeliveryBack,
 }
 
@@ -25,32 +30,42 @@ pub enum WorkKind {
 
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct Work {
-    workplace: BuildingID,
-    work_inter: RecTimeInterval,
+    pub workplace: BuildingID,
+    pub schedule: WorkSchedule,
     pub kind: WorkKind,
     on_mission: bool,
 }
 
 impl Work {
-    pub fn new(workplace: BuildingID, kind: WorkKind, offset: f32) -> Self {
+    pub fn new(workplace: BuildingID, kind: WorkKind, schedule: WorkSchedule) -> Self {
         Work {
             workplace,
-            work_inter: RecTimeInterval::new(
-                (8, (offset * SECONDS_PER_HOUR as f32) as i32),
-                (18, (offset * SECONDS_PER_HOUR as f32) as i32),
-            ),
+            schedule,
             kind,
             on_mission: false,
         }
     }
 }
 
+impl DesireKind for Work {
+    const NAME: &'static str = "work";
+}
+
 register_system!(desire_work);
 #[system(par_for_each)]
-pub fn desire_work(#[resource] time: &GameTime, router: &mut Router, d: &mut Desire<Work>) {
+pub fn desire_work(
+    #[resource] time: &GameTime,
+    #[resource] cbuf: &ParCommandBuffer,
+    me: &Entity,
+    router: &mut Router,
+    desires: &mut Desires,
+    d: &mut Desire<Work>,
+) {
+    let soul = SoulID(*me);
     d.score_and_apply(
+        desires,
         |work| {
-            if work.on_mission || work.work_inter.dist_until(time.daytime) == 0 {
+            if work.on_mission || work.schedule.is_working(time.daytime) {
                 0.5
             } else {
                 0.0
@@ -78,10 +93,12 @@ pub fn desire_work(#[resource] time: &GameTime, router: &mut Router, d: &mut Des
                 DriverState::Delivering(b) => {
                     router.use_vehicle(Some(truck));
                     if router.go_to(Destination::Building(b)) {
-                        log::info!("finished delivering to {:?} from {:?}", b, work.workplace);
-                        *state = DriverState::DeliveryBack
+                        log::info!("delivering to {:?} from {:?}", b, work.workplace);
+                        *state = DriverState::Unloading;
+                        cbuf.exec_ent(soul.0, move |goria| unload_truck(goria, soul, truck, b));
                     }
                 }
+                DriverState::Unloading => {}
                 DriverState::DeliveryBack => {
                     router.use_vehicle(Some(truck));
                     if router.go_to(Destination::Building(work.workplace)) {
@@ -1,20 +1,37 @@
 use super::desire::Desire;
 use super::desire::Work;
-use crate::economy::{CommodityKind, Market, Sold, Workers};
+use crate::economy::{Cargo, CommodityKind, Market, Money, Sold, Store, Stores, Trade, Workers};
 use crate::map_dynamic::BuildingInfos;
 use crate::souls::desire::{DriverState, WorkKind};
+use crate::souls::household::household_of;
+use crate::souls::human::fire_human;
+use crate::souls::schedule::WorkSchedule;
+use crate::utils::rand_provider::RandProvider;
 use crate::vehicles::VehicleID;
 use crate::{Egregoria, ParCommandBuffer, SoulID};
-use common::GameTime;
+use common::{GameTime, SECONDS_PER_HOUR};
 use geom::Vec2;
 use legion::world::SubWorld;
 use legion::{system, Entity, EntityStore};
 use map_model::{BuildingGen, BuildingID, BuildingKind, Map};
+use serde::Deserialize;
 
-#[derive(Copy, Clone)]
+/// Money a company starts with
+pub const COMPANY_STARTING_MONEY: Money = Money::from_units(1000);
+
+/// Paid to each worker every time the recipe is executed
+pub const WAGE: Money = Money::from_units(1);
+
+/// Units of goods a truck carries. A single bigger trade is still loaded alone.
+pub const TRUCK_CAPACITY: i32 = 10;
+
+/// Deliveries whose buyers are closer than this to the first one are done in the same trip, in meters
+pub const DELIVERY_BATCH_RADIUS: f32 = 500.0;
+
+#[derive(Clone)]
 pub struct Recipe {
-    pub consumption: &'static [(CommodityKind, i32)],
-    pub production: &'static [(CommodityKind, i32)],
+    pub consumption: Vec<(CommodityKind, i32)>,
+    pub production: Vec<(CommodityKind, i32)>,
 
     /// Time to execute the recipe when the facility is at full capacity, in seconds
     pub complexity: i32,
@@ -25,435 +42,23 @@ pub struct Recipe {
     pub storage_multiplier: i32,
 }
 
+/// Loaded from the economy data file, see [crate::economy::economy_data]
 pub struct GoodsCompanyDescription {
-    pub name: &'static str,
+    pub name: String,
     pub bkind: BuildingKind,
     pub bgen: BuildingGen,
     pub kind: CompanyKind,
     pub recipe: Recipe,
+    /// Goods a store sells on site with the stock it keeps of them
+    pub shelf: Vec<(CommodityKind, i32)>,
     pub n_workers: i32,
     pub size: f32,
-    pub asset_location: &'static str,
+    pub asset_location: String,
 }
 
-pub const GOODS_BUILDINGS: &[GoodsCompanyDescription] = &[
-    GoodsCompanyDescription {
-        name: "Supermarket",
-        bkind: BuildingKind::Company(23),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Store,
-        recipe: Recipe {
-            consumption: &[
-                (CommodityKind::Meat, 1),
-                (CommodityKind::Vegetable, 1),
-                (CommodityKind::Cereal, 1),
-            ], // TODO: actually implement stores
-            production: &[
-                (CommodityKind::Meat, 1),
-                (CommodityKind::Vegetable, 1),
-                (CommodityKind::Cereal, 1),
-            ],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/supermarket.png",
-    },
-    GoodsCompanyDescription {
-        name: "Clothes store",
-        bkind: BuildingKind::Company(22),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Store,
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Cloth, 1)], // TODO: actually implement stores
-            production: &[(CommodityKind::Cloth, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 10.0,
-        asset_location: "assets/clothes_store.png",
-    },
-    GoodsCompanyDescription {
-        name: "Cloth factory",
-        bkind: BuildingKind::Company(21),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Polyester, 1), (CommodityKind::Wool, 1)],
-            production: &[(CommodityKind::Cloth, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/cloth_factory.png",
-    },
-    GoodsCompanyDescription {
-        name: "Polyester refinery",
-        bkind: BuildingKind::Company(20),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Oil, 1)],
-            production: &[(CommodityKind::Polyester, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 5,
-        size: 80.0,
-        asset_location: "assets/polyester_refinery.png",
-    },
-    GoodsCompanyDescription {
-        name: "Oil pump",
-        bkind: BuildingKind::Company(19),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[],
-            production: &[(CommodityKind::Oil, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 5,
-        size: 20.0,
-        asset_location: "assets/oil_pump.png",
-    },
-    GoodsCompanyDescription {
-        name: "Textile processing facility",
-        bkind: BuildingKind::Company(18),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Wool, 1)],
-            production: &[(CommodityKind::Cloth, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/textile_processing_facility.png",
-    },
-    GoodsCompanyDescription {
-        name: "Wool farm",
-        bkind: BuildingKind::Company(17),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[],
-            production: &[(CommodityKind::Wool, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/wool_farm.png",
-    },
-    GoodsCompanyDescription {
-        name: "Florist",
-        bkind: BuildingKind::Company(16),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Store,
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Flower, 1)], // TODO: actually implement stores
-            production: &[(CommodityKind::Flower, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 10.0,
-        asset_location: "assets/florist.png",
-    },
-    GoodsCompanyDescription {
-        name: "Horticulturalist",
-        bkind: BuildingKind::Company(15),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[],
-            production: &[(CommodityKind::Flower, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 5,
-        size: 80.0,
-        asset_location: "assets/horticulturalist.png",
-    },
-    GoodsCompanyDescription {
-        name: "High tech store",
-        bkind: BuildingKind::Company(14),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Store,
-        recipe: Recipe {
-            consumption: &[(CommodityKind::HighTechProduct, 1)], // TODO: actually implement stores
-            production: &[(CommodityKind::HighTechProduct, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/hightech_store.png",
-    },
-    GoodsCompanyDescription {
-        name: "High tech facility",
-        bkind: BuildingKind::Company(13),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::RareMetal, 1), (CommodityKind::Metal, 1)],
-            production: &[(CommodityKind::HighTechProduct, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/hightech_facility.png",
-    },
-    GoodsCompanyDescription {
-        name: "Rare metal mine",
-        bkind: BuildingKind::Company(12),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[],
-            production: &[(CommodityKind::RareMetal, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/rare_metal_mine.png",
-    },
-    GoodsCompanyDescription {
-        name: "Furniture store",
-        bkind: BuildingKind::Company(11),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Store,
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Metal, 1), (CommodityKind::WoodPlank, 1)],
-            production: &[(CommodityKind::Furniture, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/furniture_store.png",
-    },
-    GoodsCompanyDescription {
-        name: "Foundry",
-        bkind: BuildingKind::Company(10),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::IronOre, 1)],
-            production: &[(CommodityKind::Metal, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/foundry.png",
-    },
-    GoodsCompanyDescription {
-        name: "Iron mine",
-        bkind: BuildingKind::Company(9),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[],
-            production: &[(CommodityKind::IronOre, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/iron_mine.png",
-    },
-    GoodsCompanyDescription {
-        name: "Woodmill",
-        bkind: BuildingKind::Company(8),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::TreeLog, 1)],
-            production: &[(CommodityKind::WoodPlank, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/woodmill.png",
-    },
-    GoodsCompanyDescription {
-        name: "Lumber yard",
-        bkind: BuildingKind::Company(7),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[],
-            production: &[(CommodityKind::TreeLog, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/lumber_yard.png",
-    },
-    GoodsCompanyDescription {
-        name: "Meat facility",
-        bkind: BuildingKind::Company(6),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 0.6,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::RawMeat, 1)],
-            production: &[(CommodityKind::Meat, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/meat_facility.png",
-    },
-    GoodsCompanyDescription {
-        name: "Slaughterhouse",
-        bkind: BuildingKind::Company(5),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Carcass, 1)],
-            production: &[(CommodityKind::RawMeat, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 5,
-        size: 50.0,
-        asset_location: "assets/slaughterhouse.png",
-    },
-    GoodsCompanyDescription {
-        name: "Animal Farm",
-        bkind: BuildingKind::Company(4),
-        bgen: BuildingGen::Farm,
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Cereal, 1)],
-            production: &[(CommodityKind::Carcass, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 5,
-        size: 80.0,
-        asset_location: "assets/animal_farm.png",
-    },
-    GoodsCompanyDescription {
-        name: "Vegetable Farm",
-        bkind: BuildingKind::Company(3),
-        bgen: BuildingGen::Farm,
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[],
-            production: &[(CommodityKind::Vegetable, 2)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 70.0,
-        asset_location: "assets/vegetable_farm.png",
-    },
-    GoodsCompanyDescription {
-        name: "Cereal Farm",
-        bkind: BuildingKind::Company(2),
-        bgen: BuildingGen::Farm,
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[],
-            production: &[(CommodityKind::Cereal, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 120.0,
-        asset_location: "assets/cereal_farm.png",
-    },
-    GoodsCompanyDescription {
-        name: "Cereal Factory",
-        bkind: BuildingKind::Company(1),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 0.6,
-        },
-        kind: CompanyKind::Factory { n_trucks: 1 },
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Cereal, 1)],
-            production: &[(CommodityKind::Flour, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 10,
-        size: 80.0,
-        asset_location: "assets/flour_factory.png",
-    },
-    GoodsCompanyDescription {
-        name: "Bakery",
-        bkind: BuildingKind::Company(0),
-        bgen: BuildingGen::CenteredDoor {
-            vertical_factor: 1.0,
-        },
-        kind: CompanyKind::Store,
-        recipe: Recipe {
-            consumption: &[(CommodityKind::Flour, 1)],
-            production: &[(CommodityKind::Bread, 1)],
-            complexity: 1000,
-            storage_multiplier: 5,
-        },
-        n_workers: 3,
-        size: 10.0,
-        asset_location: "assets/bakery.png",
-    },
-];
-
 impl Recipe {
-    pub fn init(&self, soul: SoulID, near: Vec2, market: &mut Market) {
-        for &(kind, qty) in self.consumption {
-            market.buy_until(soul, near, kind, qty)
-        }
+    pub fn produces(&self, kind: CommodityKind) -> bool {
+        self.production.iter().any(|&(k, _)| k == kind)
     }
 
     pub fn should_produce(&self, soul: SoulID, market: &Market) -> bool {
@@ -468,19 +73,17 @@ impl Recipe {
             })
     }
 
-    pub fn act(&self, soul: SoulID, near: Vec2, market: &mut Market) {
-        for &(kind, qty) in self.consumption {
+    pub fn act(&self, soul: SoulID, market: &mut Market) {
+        for &(kind, qty) in &self.consumption {
             market.produce(soul, kind, -qty);
-            market.buy_until(soul, near, kind, qty);
         }
-        for &(kind, qty) in self.production {
+        for &(kind, qty) in &self.production {
             market.produce(soul, kind, qty);
-            market.sell_all(soul, near, kind);
         }
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Deserialize)]
 pub enum CompanyKind {
     // Buyers come to get their goods
     Store,
@@ -495,14 +98,131 @@ pub struct GoodsCompany {
     pub building: BuildingID,
     pub workers: i32,
     pub work_seconds: f32,
-    pub driver: Option<SoulID>,
+    /// The i-th driver drives the i-th truck
+    pub drivers: Vec<SoulID>,
     pub trucks: Vec<VehicleID>,
+    /// Goods a store sells on site with the stock it keeps of them
+    pub shelf: Vec<(CommodityKind, i32)>,
+    /// A company goes bankrupt when it runs out of money, it fires its workers and stops producing
+    pub bankrupt: bool,
 }
 
 impl GoodsCompany {
     pub fn progress(&self) -> f32 {
         self.work_seconds / (self.workers * self.recipe.complexity) as f32
     }
+
+    /// Called when a driver leaves the company, its truck goes after the ones that still have a
+    /// driver so that the i-th driver keeps driving the i-th truck
+    pub fn remove_driver(&mut self, driver: SoulID) {
+        let i = unwrap_or!(self.drivers.iter().position(|&d| d == driver), return);
+        self.drivers.remove(i);
+        let truck = self.trucks.remove(i);
+        self.trucks.push(truck);
+    }
+
+    pub fn sells_on_site(&self, kind: CommodityKind) -> bool {
+        self.shelf.iter().any(|&(k, _)| k == kind)
+    }
+
+    /// Places the buy orders for the consumed goods and for the goods a store sells without making
+    /// them, and the sell orders for the produced goods, at the current prices.
+    /// Stores keep the goods on their shelf to sell them to the customers coming by.
+    pub fn place_orders(&self, soul: SoulID, near: Vec2, market: &mut Market) {
+        for &(kind, qty) in &self.recipe.consumption {
+            market.buy_until(soul, near, kind, qty, market.bid_price(kind))
+        }
+        for &(kind, stock) in &self.shelf {
+            if !self.recipe.produces(kind) {
+                market.buy_until(soul, near, kind, stock, market.bid_price(kind))
+            }
+        }
+        for &(kind, _) in &self.recipe.production {
+            if !self.sells_on_site(kind) {
+                market.sell_all(soul, near, kind, market.ask_price(kind));
+            }
+        }
+    }
+}
+
+/// Takes the oldest sold trade and the ones going near it, as long as they fit in a truck.
+/// Trades whose buyer has no building are returned separately, they are delivered right away.
+fn batch_deliveries(
+    sold: &mut Vec<Trade>,
+    binfos: &BuildingInfos,
+) -> (Vec<(BuildingID, Trade)>, Vec<Trade>) {
+    let mut load = vec![];
+    let mut nowhere = vec![];
+    let mut units = 0;
+    let anchor = unwrap_or!(sold.first(), return (load, nowhere)).buy_pos;
+
+    sold.retain(|&trade| {
+        if !load.is_empty()
+            && (units + trade.qty > TRUCK_CAPACITY
+                || trade.buy_pos.distance(anchor) > DELIVERY_BATCH_RADIUS)
+        {
+            return true;
+        }
+        match binfos.building_owned_by(trade.buyer) {
+            Some(b) => {
+                units += trade.qty;
+                load.push((b, trade));
+            }
+            None => nowhere.push(trade),
+        }
+        false
+    });
+
+    (load, nowhere)
+}
+
+/// Sends the driver to the closest stop left in its truck, or back to work once it is empty
+fn send_to_next_stop(goria: &mut Egregoria, driver: SoulID, truck: VehicleID) {
+    let pos = goria.pos(truck.0).unwrap_or_default();
+    let next = goria
+        .comp::<Cargo>(truck.0)
+        .and_then(|cargo| cargo.closest_stop(pos));
+
+    let w = unwrap_or!(goria.comp_mut::<Desire<Work>>(driver.0), return);
+    if let WorkKind::Driver { ref mut state, .. } = w.v.kind {
+        *state = match next {
+            Some(b) => DriverState::Delivering(b),
+            None => DriverState::DeliveryBack,
+        };
+    }
+}
+
+fn load_truck(
+    goria: &mut Egregoria,
+    driver: SoulID,
+    truck: VehicleID,
+    load: Vec<(BuildingID, Trade)>,
+) {
+    let cargo = unwrap_or!(goria.comp_mut::<Cargo>(truck.0), return);
+    cargo.0.extend(load);
+    send_to_next_stop(goria, driver, truck);
+}
+
+/// Hands all the goods in the truck to their buyers, when nobody is left to drive it
+pub fn deliver_cargo_now(goria: &mut Egregoria, truck: VehicleID) {
+    let cargo = unwrap_or!(goria.comp_mut::<Cargo>(truck.0), return);
+    let trades: Vec<Trade> = cargo.0.drain(..).map(|(_, trade)| trade).collect();
+    let mut market = goria.write::<Market>();
+    for trade in &trades {
+        market.deliver(trade);
+    }
+}
+
+/// Called when a driver reaches a stop, the goods for this building reach their buyers.
+pub fn unload_truck(goria: &mut Egregoria, driver: SoulID, truck: VehicleID, at: BuildingID) {
+    let unloaded = unwrap_or!(goria.comp_mut::<Cargo>(truck.0), return).unload(at);
+    {
+        let mut market = goria.write::<Market>();
+        for trade in &unloaded {
+            market.deliver(trade);
+        }
+    }
+    send_to_next_stop(goria, driver, truck);
 }
 
 pub fn company_soul(goria: &mut Egregoria, company: GoodsCompany) -> SoulID {
@@ -514,16 +234,32 @@ pub fn company_soul(goria: &mut Egregoria, company: GoodsCompany) -> SoulID {
 
     {
         let m = &mut *goria.write::<Market>();
-        m.produce(soul, CommodityKind::JobOpening, company.workers);
-        m.sell_all(soul, bpos, CommodityKind::JobOpening);
+        m.produce(soul, CommodityKind::JOB_OPENING, company.workers);
+        m.sell_all(soul, bpos, CommodityKind::JOB_OPENING, Money::ZERO);
+        m.give_money(soul, COMPANY_STARTING_MONEY);
 
-        company.recipe.init(soul, bpos, m);
+        company.place_orders(soul, bpos, m);
+
+        if matches!(company.kind, CompanyKind::Factory { .. }) {
+            m.set_delivering(soul);
+        }
     }
 
     goria
         .write::<BuildingInfos>()
         .set_owner(company.building, soul);
 
+    if !company.shelf.is_empty() {
+        let store = Store {
+            soul,
+            building: company.building,
+            door: bpos,
+        };
+        goria
+            .write::<Stores>()
+            .add(store, company.shelf.iter().map(|&(kind, _)| kind));
+    }
+
     goria
         .world
         .push_with_id(e, (company, Workers::default(), Sold::default()));
@@ -543,79 +279,127 @@ pub fn company(
     me: &Entity,
     company: &mut GoodsCompany,
     sold: &mut Sold,
-    workers: &Workers,
+    workers: &mut Workers,
     sw: &SubWorld,
 ) {
     let n_workers = workers.0.len();
     let soul = SoulID(*me);
 
+    if company.bankrupt {
+        return;
+    }
+
+    if market.money(soul) < Money::ZERO {
+        log::info!("{:?} went bankrupt", soul);
+        company.bankrupt = true;
+        company.drivers.clear();
+        let undelivered = std::mem::take(&mut sold.0);
+        let fired = std::mem::take(&mut workers.0);
+        let trucks = company.trucks.clone();
+
+        cbuf.exec_ent(soul.0, move |goria| {
+            // The goods already sold are handed over without a truck
+            for truck in trucks {
+                deliver_cargo_now(goria, truck);
+            }
+            let mut market = goria.write::<Market>();
+            for trade in &undelivered {
+                market.deliver(trade);
+            }
+            market.cancel_orders(soul);
+            drop(market);
+
+            goria.write::<Stores>().remove(soul);
+            for worker in fired {
+                fire_human(goria, worker);
+            }
+        });
+        return;
+    }
+
+    let bpos = map.buildings()[company.building].door_pos;
+
     if company.recipe.should_produce(soul, market) {
         company.work_seconds += n_workers as f32 * time.delta;
     }
 
     if company.work_seconds >= (company.recipe.complexity * company.workers) as f32 {
         company.work_seconds = 0.0;
-        let recipe = company.recipe;
-        let bpos = map.buildings()[company.building].door_pos;
+        let paid = workers.0.clone();
 
-        cbuf.exec(move |goria| {
-            recipe.act(soul, bpos, &mut *goria.write::<Market>());
+        cbuf.exec_ent(soul.0, move |goria| {
+            let company = unwrap_or!(goria.comp::<GoodsCompany>(soul.0), return);
+            let mut market = goria.write::<Market>();
+            company.recipe.act(soul, &mut market);
+            company.place_orders(soul, bpos, &mut market);
+            for worker in paid {
+                market.pay(soul, household_of(goria, worker), WAGE);
+            }
+        });
+    } else if time.tick(SECONDS_PER_HOUR as u32) {
+        // Follow the prices
+        cbuf.exec_ent(soul.0, move |goria| {
+            let company = unwrap_or!(goria.comp::<GoodsCompany>(soul.0), return);
+            company.place_orders(soul, bpos, &mut goria.write::<Market>());
         });
     }
 
-    if let Some(driver) = company.driver {
-        let driver_work_kind = sw
-            .entry_ref(driver.0)
-            .unwrap()
-            .get_component::<Desire<Work>>()
-            .unwrap()
-            .v
-            .kind;
-
-        if let WorkKind::Driver {
-            state: DriverState::WaitingForDelivery,
-            ..
-        } = driver_work_kind
-        {
-            if let Some(trade) = sold.0.drain(..1.min(sold.0.len())).next() {
-                let owner_build = binfos.building_owned_by(trade.buyer).unwrap();
+    for (&driver, &truck) in company.drivers.iter().zip(&company.trucks) {
+        if sold.0.is_empty() {
+            break;
+        }
 
-                log::info!("asked driver to deliver");
+        let idle = matches!(
+            sw.entry_ref(driver.0)
+                .ok()
+                .and_then(|e| e.get_component::<Desire<Work>>().ok().map(|d| d.v.kind)),
+            Some(WorkKind::Driver {
+                state: DriverState::WaitingForDelivery,
+                ..
+            })
+        );
+        if !idle {
+            continue;
+        }
 
-                cbuf.exec(move |goria| {
-                    let w = goria.comp_mut::<Desire<Work>>(driver.0).unwrap();
-                    if let WorkKind::Driver { ref mut state, .. } = w.v.kind {
-                        *state = DriverState::Delivering(owner_build)
-                    }
-                })
-            }
+        let (load, nowhere) = batch_deliveries(&mut sold.0, binfos);
+        if !nowhere.is_empty() {
+            cbuf.exec_on(soul.0, move |market: &mut Market| {
+                for trade in &nowhere {
+                    market.deliver(trade);
+                }
+            });
         }
+        if load.is_empty() {
+            continue;
+        }
+
+        log::info!("asked driver to deliver {} trades", load.len());
+        cbuf.exec_ent(driver.0, move |goria| {
+            load_truck(goria, driver, truck, load)
+        });
     }
 
     for &worker in workers.0.iter() {
-        if sw
-            .entry_ref(worker.0)
-            .unwrap()
-            .get_component::<Desire<Work>>()
-            .is_err()
-        {
+        let entry = unwrap_or!(sw.entry_ref(worker.0).ok(), continue);
+        if entry.get_component::<Desire<Work>>().is_err() {
             let mut kind = WorkKind::Worker;
             if matches!(company.kind, CompanyKind::Factory { .. })
-                && company.driver.is_none()
-                && !company.trucks.is_empty()
+                && company.drivers.len() < company.trucks.len()
             {
                 kind = WorkKind::Driver {
                     state: DriverState::GoingToWork,
-                    truck: company.trucks[0],
+                    truck: company.trucks[company.drivers.len()],
                 };
 
-                company.driver = Some(worker);
+                company.drivers.push(worker);
             }
 
-            cbuf.add_component(
-                worker.0,
-                Desire::<Work>::new(Work::new(company.building, kind, 0.0)),
-            )
+            let workplace = company.building;
+            cbuf.exec_ent(worker.0, move |goria| {
+                let schedule = WorkSchedule::random(&mut goria.write::<RandProvider>());
+                goria.add_comp(worker.0, Desire::new(Work::new(workplace, kind, schedule)));
+            });
         }
     }
 }
@@ -0,0 +1,98 @@
+use crate::economy::{CommodityKind, Market};
+use crate::map_dynamic::{BuildingInfos, Router};
+use crate::souls::demographics::{Person, ADULT_AGE};
+use crate::souls::desire::NeedKind;
+use crate::vehicles::{spawn_parked_vehicle, VehicleID, VehicleKind};
+use crate::{Egregoria, SoulID};
+use common::GameTime;
+use map_model::{BuildingID, Map};
+use serde::{Deserialize, Serialize};
+
+/// Units of food bought at once when a member goes to the store, one is eaten right away and
+/// the rest is stocked at home
+pub const FOOD_BATCH: i32 = 3;
+
+/// The humans living together in a house, the members are the residents of the house in
+/// [BuildingInfos]. The household soul owns the house and the cars, and its money in the
+/// market is the budget the members earn wages into and pay the stores from.
+/// Food bought for later is kept as capital of the household soul.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Household {
+    pub house: BuildingID,
+    pub cars: Vec<VehicleID>,
+}
+
+/// Creates an empty household owning the house and some cars parked in front of it
+pub fn household_soul(goria: &mut Egregoria, house: BuildingID, n_cars: usize) -> SoulID {
+    let housepos = goria.read::<Map>().buildings()[house].door_pos;
+    let cars = (0..n_cars)
+        .filter_map(|_| spawn_parked_vehicle(goria, VehicleKind::Car, housepos))
+        .collect();
+
+    let soul = SoulID(goria.world.push((Household { house, cars },)));
+    goria.write::<BuildingInfos>().set_owner(house, soul);
+    soul
+}
+
+/// The soul paying for the human, its household if it has one
+pub fn household_of(goria: &Egregoria, human: SoulID) -> SoulID {
+    goria
+        .comp::<Person>(human.0)
+        .map(|p| p.household)
+        .unwrap_or(human)
+}
+
+/// Adults without a car get one of the household cars that nobody drives, if any is left
+pub fn share_cars(goria: &mut Egregoria, household: SoulID) {
+    let h = unwrap_or!(goria.comp::<Household>(household.0), return).clone();
+    let today = goria.read::<GameTime>().daytime.day;
+    let residents = goria.read::<BuildingInfos>().residents(h.house).to_vec();
+
+    let mut driven = vec![];
+    let mut walking = vec![];
+    for human in residents {
+        let car = unwrap_or!(goria.comp::<Router>(human.0), continue).personal_car;
+        match car {
+            Some(car) => driven.push(car),
+            None => {
+                let adult = goria
+                    .comp::<Person>(human.0)
+                    .filter(|p| p.age(today) >= ADULT_AGE)
+                    .is_some();
+                if adult {
+                    walking.push(human);
+                }
+            }
+        }
+    }
+
+    let mut free = h.cars.into_iter().filter(|car| !driven.contains(car));
+    for human in walking {
+        let car = unwrap_or!(free.next(), return);
+        let router = unwrap_or!(goria.comp_mut::<Router>(human.0), continue);
+        router.personal_car = Some(car);
+        if router.vehicle().is_none() {
+            router.use_vehicle(Some(car));
+        }
+    }
+}
+
+/// Gives back the car of a member leaving the household to the other adults
+pub fn return_car(goria: &mut Egregoria, household: SoulID, human: SoulID) {
+    if let Some(router) = goria.comp_mut::<Router>(human.0) {
+        if router.vehicle() == router.personal_car {
+            router.use_vehicle(None);
+        }
+        router.personal_car = None;
+    }
+    share_cars(goria, household);
+}
+
+/// A food commodity stocked at home, if any
+pub fn food_in_stock(market: &Market, household: SoulID) -> Option<CommodityKind> {
+    CommodityKind::values()
+        .iter()
+        .copied()
+        .filter(|kind| kind.description().need == Some(NeedKind::Food))
+        .find(|&kind| market.capital(household, kind) > 0)
+}
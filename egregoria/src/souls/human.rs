@@ -1,35 +1,162 @@
-use crate::economy::CommodityKind::JobOpening;
-use crate::economy::{Bought, Market};
+use crate::economy::{Bought, CommodityKind, Market, Money, Workers};
 use crate::map_dynamic::{BuildingInfos, Router};
-use crate::pedestrians::{spawn_pedestrian, Pedestrian};
-use crate::souls::desire::{BuyFood, Home, Work};
-use crate::vehicles::{spawn_parked_vehicle, VehicleKind};
+use crate::pedestrians::spawn_pedestrian;
+use crate::souls::demographics::{Person, ADULT_AGE, RETIREMENT_AGE};
+use crate::souls::desire::{
+    BuyFood, Desire, Desires, Home, Leisure, Needs, Shopping, VisitFriend, Work, WorkKind,
+};
+use crate::souls::goods_company::{deliver_cargo_now, GoodsCompany};
+use crate::utils::rand_world;
+use crate::vehicles::spawn_bike;
 use crate::{Egregoria, SoulID};
 use common::GameTime;
-use map_model::{BuildingID, Map};
+use legion::IntoQuery;
+use map_model::{BuildingID, BuildingKind, Map};
 
-pub fn spawn_human(goria: &mut Egregoria, house: BuildingID) {
-    let map = goria.read::<Map>();
-    let housepos = map.buildings()[house].door_pos;
-    drop(map);
+/// Money a household starts with for each adult
+pub const HUMAN_STARTING_MONEY: Money = Money::from_units(100);
+
+/// Share of the humans old enough to ride one that own a bike
+const BIKE_OWNERSHIP: f32 = 0.4;
+const BIKE_AGE: i32 = 8;
 
+/// Spawns a human of the given age living in the house of the household, adults look for a job.
+/// The human walks until it is given one of the household cars, see [share_cars], some of them
+/// have a bike for short trips.
+///
+/// [share_cars]: crate::souls::household::share_cars
+pub fn spawn_human(
+    goria: &mut Egregoria,
+    household: SoulID,
+    house: BuildingID,
+    age: i32,
+) -> SoulID {
     let human = SoulID(spawn_pedestrian(goria, house));
-    let car = spawn_parked_vehicle(goria, VehicleKind::Car, housepos);
 
-    let mut m = goria.write::<Market>();
-    m.buy(human, housepos, JobOpening, 1);
-    drop(m);
+    let friend_house = pick_friend_house(goria, house);
+
+    let r: f32 = rand_world(goria);
+    let bike = if age >= BIKE_AGE && r < BIKE_OWNERSHIP {
+        let housepos = goria.read::<Map>().buildings()[house].door_pos;
+        Some(spawn_bike(goria, housepos))
+    } else {
+        None
+    };
 
-    goria.write::<BuildingInfos>().set_owner(house, human);
+    goria.write::<BuildingInfos>().add_resident(house, human);
 
-    let time = goria.read::<GameTime>().instant();
+    let today = goria.read::<GameTime>().daytime.day;
 
     let mut e = goria.world.entry(human.0).unwrap();
 
+    e.add_component(Person::new(today, age, household));
+    e.add_component(Needs::default());
+    e.add_component(Desires::default());
     e.add_component(Desire::new(Home::new(house)));
-    e.add_component(Desire::new(BuyFood::new(time)));
+    e.add_component(Desire::new(BuyFood::default()));
+    e.add_component(Desire::new(Shopping::default()));
+    e.add_component(Desire::new(Leisure::default()));
+    if let Some(friend_house) = friend_house {
+        e.add_component(Desire::new(VisitFriend::new(friend_house)));
+    }
     e.add_component(Bought::default());
-    e.add_component(Router::new(car));
+    let mut router = Router::new(None);
+    router.bike = bike;
+    e.add_component(router);
+
+    if (ADULT_AGE..RETIREMENT_AGE).contains(&age) {
+        look_for_job(goria, human);
+    }
+
+    human
+}
+
+/// Places a buy order for a job opening near the human's house
+pub fn look_for_job(goria: &mut Egregoria, human: SoulID) {
+    let house = unwrap_or!(goria.read::<BuildingInfos>().home_of(human), return);
+    let housepos = goria.read::<Map>().buildings()[house].door_pos;
+    goria
+        .write::<Market>()
+        .buy(human, housepos, CommodityKind::JOB_OPENING, 1, Money::ZERO);
+}
+
+/// A random house already lived in, other than this one
+fn pick_friend_house(goria: &mut Egregoria, house: BuildingID) -> Option<BuildingID> {
+    let r: f32 = rand_world(goria);
+
+    let map = goria.read::<Map>();
+    let binfos = goria.read::<BuildingInfos>();
+    let houses: Vec<BuildingID> = map
+        .buildings()
+        .iter()
+        .filter(|&(id, b)| {
+            id != house
+                && matches!(b.kind, BuildingKind::House)
+                && binfos.get(id).and_then(|info| info.owner).is_some()
+        })
+        .map(|(id, _)| id)
+        .collect();
+
+    houses.get((r * houses.len() as f32) as usize).copied()
 }
 
-desires_system!(human_desires, Pedestrian, Home;0 Work;1 BuyFood;2);
+fn remove_work(goria: &mut Egregoria, human: SoulID) {
+    let mut e = unwrap_or!(goria.world.entry(human.0), return);
+    e.remove_component::<Desire<Work>>();
+    if let Some(desires) = goria.comp_mut::<Desires>(human.0) {
+        desires.remove::<Work>();
+    }
+
+    if let Some(router) = goria.comp_mut::<Router>(human.0) {
+        router.use_vehicle(router.personal_car);
+    }
+}
+
+/// Called when a human lost its job, it goes back to its own car and looks for a new job
+pub fn fire_human(goria: &mut Egregoria, human: SoulID) {
+    remove_work(goria, human);
+    look_for_job(goria, human);
+}
+
+/// Called when a human stops working on its own, its company opens the position again.
+/// Also cancels its search for a job.
+pub fn leave_job(goria: &mut Egregoria, human: SoulID) {
+    goria.write::<Market>().cancel_orders(human);
+
+    // The company may have hired the human before its work desire was added
+    for (workers, company) in
+        <(&mut Workers, Option<&mut GoodsCompany>)>::query().iter_mut(&mut goria.world)
+    {
+        workers.0.retain(|&w| w != human);
+        if let Some(company) = company {
+            company.remove_driver(human);
+        }
+    }
+
+    let work = unwrap_or!(goria.comp::<Desire<Work>>(human.0), return).v;
+    remove_work(goria, human);
+
+    if let WorkKind::Driver { truck, .. } = work.kind {
+        deliver_cargo_now(goria, truck);
+    }
+
+    let company = unwrap_or!(
+        goria
+            .read::<BuildingInfos>()
+            .get(work.workplace)
+            .and_then(|info| info.owner),
+        return
+    );
+
+    let hiring = goria
+        .comp::<GoodsCompany>(company.0)
+        .filter(|c| !c.bankrupt)
+        .is_some();
+    if !hiring {
+        return;
+    }
+    let pos = goria.read::<Map>().buildings()[work.workplace].door_pos;
+    let mut market = goria.write::<Market>();
+    market.produce(company, CommodityKind::JOB_OPENING, 1);
+    market.sell_all(company, pos, CommodityKind::JOB_OPENING, Money::ZERO);
+}
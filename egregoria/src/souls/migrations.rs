@@ -0,0 +1,188 @@
+use crate::souls::desire::{
+    BuyFood, Desire, Desires, DriverState, Home, Leisure, Needs, Shopping, Work, WorkKind,
+};
+use crate::souls::schedule::{ShiftKind, WorkSchedule};
+use crate::vehicles::VehicleID;
+use common::{GameInstant, RecTimeInterval};
+use legion::{Entity, IntoQuery, Registry, World};
+use map_model::BuildingID;
+use serde::{Deserialize, Serialize};
+
+/// Desires used to be chosen by the system of the human, marking the chosen one
+#[derive(Serialize, Deserialize)]
+struct DesireV0<T> {
+    score: f32,
+    was_max: bool,
+    v: T,
+}
+
+#[derive(Serialize, Deserialize)]
+enum BuyFoodStateV0 {
+    Empty,
+    WaitingForTrade,
+    BoughtAt(BuildingID),
+}
+
+#[derive(Serialize, Deserialize)]
+struct BuyFoodV0 {
+    last_ate: GameInstant,
+    state: BuyFoodStateV0,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+enum DriverStateV0 {
+    GoingToWork,
+    WaitingForDelivery,
+    Delivering(BuildingID),
+    DeliveryBack,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+enum WorkKindV0 {
+    Driver {
+        state: DriverStateV0,
+        truck: VehicleID,
+    },
+    Worker,
+}
+
+#[derive(Copy, Clone, Serialize, Deserialize)]
+struct WorkV0 {
+    workplace: BuildingID,
+    work_inter: RecTimeInterval,
+    kind: WorkKindV0,
+    on_mission: bool,
+}
+
+impl WorkV0 {
+    /// Everybody used to work the same hours every day
+    fn convert(self) -> Work {
+        let kind = match self.kind {
+            WorkKindV0::Driver { state, truck } => WorkKind::Driver {
+                state: match state {
+                    DriverStateV0::GoingToWork => DriverState::GoingToWork,
+                    DriverStateV0::WaitingForDelivery => DriverState::WaitingForDelivery,
+                    DriverStateV0::Delivering(b) => DriverState::Delivering(b),
+                    DriverStateV0::DeliveryBack => DriverState::DeliveryBack,
+                },
+                truck,
+            },
+            WorkKindV0::Worker => WorkKind::Worker,
+        };
+        let schedule = WorkSchedule {
+            kind: ShiftKind::Day,
+            hours: self.work_inter,
+            work_days: 0b1111111,
+        };
+        Work::new(self.workplace, kind, schedule)
+    }
+}
+
+/// Reads the human components with their previous layout, `key` gives the registry key of a
+/// component from its name
+pub(crate) fn register_world_v0(registry: &mut Registry<u64>, key: impl Fn(&'static str) -> u64) {
+    registry.register::<DesireV0<Home>>(key("Desire<Home>"));
+    registry.register::<DesireV0<BuyFoodV0>>(key("Desire<BuyFood>"));
+    registry.register::<DesireV0<WorkV0>>(key("Desire<Work>"));
+}
+
+/// Replaces the human components read by [register_world_v0] with the current ones. The humans
+/// get fully satisfied needs and the desires that came with them, their scores are computed
+/// again on the next tick.
+pub(crate) fn convert_world_v0(world: &mut World) {
+    let humans: Vec<(Entity, Home)> = <(Entity, &DesireV0<Home>)>::query()
+        .iter(world)
+        .map(|(&e, d)| (e, d.v.clone()))
+        .collect();
+    for (e, home) in humans {
+        let mut entry = unwrap_or!(world.entry(e), continue);
+        entry.remove_component::<DesireV0<Home>>();
+        entry.add_component(Desire::new(home));
+        entry.add_component(Needs::default());
+        entry.add_component(Desires::default());
+        entry.add_component(Desire::new(Shopping::default()));
+        entry.add_component(Desire::new(Leisure::default()));
+    }
+
+    // Food is bought again from the stores when the humans get hungry
+    let hungry: Vec<Entity> = <(Entity, &DesireV0<BuyFoodV0>)>::query()
+        .iter(world)
+        .map(|(&e, _)| e)
+        .collect();
+    for e in hungry {
+        let mut entry = unwrap_or!(world.entry(e), continue);
+        entry.remove_component::<DesireV0<BuyFoodV0>>();
+        entry.add_component(Desire::new(BuyFood::default()));
+    }
+
+    let workers: Vec<(Entity, Work)> = <(Entity, &DesireV0<WorkV0>)>::query()
+        .iter(world)
+        .map(|(&e, d)| (e, d.v.convert()))
+        .collect();
+    for (e, work) in workers {
+        let mut entry = unwrap_or!(world.entry(e), continue);
+        entry.remove_component::<DesireV0<WorkV0>>();
+        entry.add_component(Desire::new(work));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BuyFoodStateV0, BuyFoodV0, DesireV0, WorkKindV0, WorkV0};
+    use crate::souls::desire::{BuyFood, Desire, Desires, Home, Needs, Work};
+    use common::saveload::Section;
+    use common::{GameInstant, RecTimeInterval};
+    use legion::serialize::Canon;
+    use legion::{any, EntityStore, World};
+    use map_model::BuildingID;
+
+    #[test]
+    fn test_migrate_humans() {
+        let mut world = World::default();
+        let e = world.push((
+            DesireV0 {
+                score: 0.2,
+                was_max: true,
+                v: Home::new(BuildingID::default()),
+            },
+            DesireV0 {
+                score: 1.0,
+                was_max: false,
+                v: BuyFoodV0 {
+                    last_ate: GameInstant { timestamp: 3.0 },
+                    state: BuyFoodStateV0::WaitingForTrade,
+                },
+            },
+            DesireV0 {
+                score: 0.0,
+                was_max: false,
+                v: WorkV0 {
+                    workplace: BuildingID::default(),
+                    work_inter: RecTimeInterval::new((8, 0), (18, 0)),
+                    kind: WorkKindV0::Worker,
+                    on_mission: false,
+                },
+            },
+        ));
+
+        let canon = Canon::default();
+        let data = bincode::serialize(&world.as_serializable(any(), &crate::registry_v0(), &canon))
+            .unwrap();
+        let mut section = Section::new("world", 0, data);
+        assert!(crate::migrate(&mut section));
+        let world: World = section
+            .read_seed(crate::registry().as_deserialize(&canon))
+            .unwrap();
+
+        let entry = world.entry_ref(e).unwrap();
+        let home = entry.get_component::<Desire<Home>>().unwrap();
+        assert_eq!(home.v.house(), BuildingID::default());
+        assert!(entry.get_component::<Desire<BuyFood>>().is_ok());
+        assert!(entry.get_component::<Needs>().is_ok());
+        assert_eq!(entry.get_component::<Desires>().unwrap().chosen(), None);
+
+        let work = entry.get_component::<Desire<Work>>().unwrap();
+        assert_eq!(work.v.schedule.hours.start_hour, 8);
+        assert!((0..7).all(|day| work.v.schedule.works_on(day)));
+    }
+}
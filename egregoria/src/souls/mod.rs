@@ -1,18 +1,25 @@
+use crate::economy::{economy_data, Cargo};
 use crate::map_dynamic::BuildingInfos;
-use crate::souls::goods_company::{company_soul, CompanyKind, GoodsCompany, GOODS_BUILDINGS};
-use crate::souls::human::spawn_human;
+use crate::souls::demographics::immigration;
+use crate::souls::goods_company::{company_soul, CompanyKind, GoodsCompany};
 use crate::vehicles::{spawn_parked_vehicle, VehicleKind};
 use crate::Egregoria;
 use geom::Vec2;
 use map_model::{BuildingID, BuildingKind, Map};
 use std::collections::HashMap;
 
-#[macro_use]
+pub mod demographics;
 pub mod desire;
 
 pub mod goods_company;
+pub mod household;
 pub mod human;
+pub(crate) mod migrations;
+pub mod schedule;
+pub mod state_log;
 
+/// Creates the companies of the empty company buildings, humans come by themselves when
+/// there are jobs, see [immigration].
 pub fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
     let map = goria.read::<Map>();
     let infos = goria.read::<BuildingInfos>();
@@ -33,17 +40,7 @@ pub fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
 
     let mut n_souls_added = 0;
 
-    for &(build_id, _) in empty_buildings
-        .get(&BuildingKind::House)
-        .unwrap_or(&vec![])
-        .iter()
-        .take(100)
-    {
-        spawn_human(goria, build_id);
-        n_souls_added += 1;
-    }
-
-    for des in GOODS_BUILDINGS {
+    for des in &economy_data().companies {
         for &(build_id, pos) in empty_buildings.get(&des.bkind).unwrap_or(&vec![]) {
             let mut trucks = vec![];
 
@@ -51,6 +48,9 @@ pub fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
                 for _ in 0..n_trucks {
                     trucks.extend(spawn_parked_vehicle(goria, VehicleKind::Truck, pos))
                 }
+                for truck in &trucks {
+                    goria.add_comp(truck.0, Cargo::default());
+                }
                 if trucks.is_empty() {
                     continue;
                 }
@@ -59,11 +59,13 @@ pub fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
             let comp = GoodsCompany {
                 kind: des.kind,
                 building: build_id,
-                recipe: des.recipe,
+                recipe: des.recipe.clone(),
                 workers: des.n_workers,
                 work_seconds: 0.0,
-                driver: None,
+                drivers: vec![],
                 trucks,
+                shelf: des.shelf.clone(),
+                bankrupt: false,
             };
 
             company_soul(goria, comp);
@@ -75,4 +77,6 @@ pub fn add_souls_to_empty_buildings(goria: &mut Egregoria) {
     if n_souls_added > 0 {
         log::info!("{} souls added", n_souls_added);
     }
+
+    immigration(goria);
 }
@@ -0,0 +1,106 @@
+use crate::utils::rand_provider::RandProvider;
+use common::{DayTime, RecTimeInterval, DAYS_PER_WEEK, SECONDS_PER_HOUR};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShiftKind {
+    Day,
+    PartTime,
+    Evening,
+    Night,
+}
+
+/// When a soul works, sampled when it is hired so that the city doesn't all go to work at once
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct WorkSchedule {
+    pub kind: ShiftKind,
+    pub hours: RecTimeInterval,
+    /// Bit i is set if the shift starts on the i-th day of the week
+    pub work_days: u8,
+}
+
+impl WorkSchedule {
+    pub fn random(r: &mut RandProvider) -> Self {
+        let kind = match r.rand_range(0, 100) {
+            0..=59 => ShiftKind::Day,
+            60..=74 => ShiftKind::PartTime,
+            75..=89 => ShiftKind::Evening,
+            _ => ShiftKind::Night,
+        };
+
+        let (start_hour, length) = match kind {
+            ShiftKind::Day => (r.rand_range(7, 10), r.rand_range(8, 10)),
+            ShiftKind::PartTime => (r.rand_range(8, 15), r.rand_range(4, 6)),
+            ShiftKind::Evening => (r.rand_range(14, 17), 8),
+            ShiftKind::Night => (r.rand_range(21, 24), 8),
+        };
+        let second = r.rand_range(0, SECONDS_PER_HOUR as i64) as i32;
+        let start_hour = start_hour as i32;
+        let end_hour = (start_hour + length as i32) % 24;
+
+        let work_days = match kind {
+            // Weekends off
+            ShiftKind::Day => 0b0011111,
+            ShiftKind::PartTime => Self::consecutive_days(3, r.rand_range(0, 5) as i32),
+            ShiftKind::Evening | ShiftKind::Night => {
+                Self::consecutive_days(5, r.rand_range(0, DAYS_PER_WEEK as i64) as i32)
+            }
+        };
+
+        Self {
+            kind,
+            hours: RecTimeInterval::new((start_hour, second), (end_hour, second)),
+            work_days,
+        }
+    }
+
+    fn consecutive_days(n: i32, first: i32) -> u8 {
+        (first..first + n).fold(0, |days, d| days | 1 << d.rem_euclid(DAYS_PER_WEEK))
+    }
+
+    pub fn works_on(&self, weekday: i32) -> bool {
+        self.work_days & (1 << weekday.rem_euclid(DAYS_PER_WEEK)) != 0
+    }
+
+    /// Whether the soul should be at work at this time. A shift going through midnight
+    /// belongs to the day it started on.
+    pub fn is_working(&self, t: DayTime) -> bool {
+        if self.hours.dist_until(t) != 0 {
+            return false;
+        }
+        let started_yesterday = t.hour < self.hours.start_hour
+            || (t.hour == self.hours.start_hour && t.second < self.hours.start_second);
+        let weekday = if started_yesterday {
+            t.weekday() - 1
+        } else {
+            t.weekday()
+        };
+        self.works_on(weekday)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ShiftKind, WorkSchedule};
+    use common::{DayTime, RecTimeInterval, SECONDS_PER_DAY, SECONDS_PER_HOUR};
+
+    #[test]
+    fn test_night_shift() {
+        // Friday night to Saturday morning
+        let s = WorkSchedule {
+            kind: ShiftKind::Night,
+            hours: RecTimeInterval::new((22, 0), (6, 0)),
+            work_days: 1 << 4,
+        };
+
+        let at =
+            |day: i32, hour: i32| DayTime::new(day * SECONDS_PER_DAY + hour * SECONDS_PER_HOUR);
+
+        assert!(!s.is_working(at(4, 3)));
+        assert!(s.is_working(at(4, 23)));
+        assert!(s.is_working(at(5, 3)));
+        assert!(!s.is_working(at(5, 7)));
+        assert!(!s.is_working(at(5, 23)));
+        assert!(s.is_working(at(11, 23)));
+    }
+}
@@ -0,0 +1,111 @@
+use crate::pedestrians::Location;
+use crate::souls::desire::{
+    BuyFood, Desire, DesireKind, Desires, Leisure, Shopping, Work, WorkKind,
+};
+use common::{DayTime, GameTime};
+use legion::system;
+use std::collections::{BTreeMap, VecDeque};
+
+/// State changes kept in the log
+const LOG_SIZE: usize = 30;
+
+/// The recent state changes of a soul, like the desire it acts on or where it is in a trip.
+/// Only added to the souls being inspected, as states are compared to the previous ones
+/// every tick.
+#[derive(Default)]
+pub struct StateLog {
+    last: BTreeMap<&'static str, String>,
+    entries: VecDeque<(DayTime, String)>,
+}
+
+impl StateLog {
+    fn update(&mut self, time: DayTime, what: &'static str, state: Option<String>) {
+        let state = state.unwrap_or_else(|| "none".to_string());
+        if self.last.get(what) == Some(&state) {
+            return;
+        }
+        if self.entries.len() == LOG_SIZE {
+            self.entries.pop_front();
+        }
+        self.entries
+            .push_back((time, format!("{}: {}", what, state)));
+        self.last.insert(what, state);
+    }
+
+    /// Oldest first
+    pub fn entries(&self) -> impl Iterator<Item = &(DayTime, String)> {
+        self.entries.iter()
+    }
+}
+
+register_system!(state_log);
+#[system(for_each)]
+pub fn state_log(
+    #[resource] time: &GameTime,
+    log: &mut StateLog,
+    desires: Option<&Desires>,
+    loc: Option<&Location>,
+    food: Option<&Desire<BuyFood>>,
+    shopping: Option<&Desire<Shopping>>,
+    leisure: Option<&Desire<Leisure>>,
+    work: Option<&Desire<Work>>,
+) {
+    let t = time.daytime;
+    log.update(
+        t,
+        "desire",
+        desires.and_then(|d| d.chosen()).map(str::to_string),
+    );
+    log.update(t, "location", loc.map(|l| format!("{:?}", l)));
+    log.update(
+        t,
+        BuyFood::NAME,
+        food.map(|d| {
+            if d.v.eating {
+                "eating at home".to_string()
+            } else {
+                format!("{:?}", d.v.trip)
+            }
+        }),
+    );
+    log.update(
+        t,
+        Shopping::NAME,
+        shopping.map(|d| format!("{:?}", d.v.trip)),
+    );
+    log.update(t, Leisure::NAME, leisure.map(|d| format!("{:?}", d.v.trip)));
+    log.update(
+        t,
+        Work::NAME,
+        work.map(|d| match d.v.kind {
+            WorkKind::Driver { state, .. } => format!("driver {:?}", state),
+            WorkKind::Worker => "worker".to_string(),
+        }),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StateLog, LOG_SIZE};
+    use common::DayTime;
+
+    #[test]
+    fn test_state_log() {
+        let mut log = StateLog::default();
+        let t = DayTime::new(0);
+
+        log.update(t, "desire", Some("home".to_string()));
+        log.update(t, "desire", Some("home".to_string()));
+        log.update(t, "work", None);
+        log.update(t, "desire", Some("work".to_string()));
+
+        let entries: Vec<&str> = log.entries().map(|(_, s)| &**s).collect();
+        assert_eq!(entries, vec!["desire: home", "work: none", "desire: work"]);
+
+        for i in 0..LOG_SIZE {
+            log.update(t, "desire", Some(i.to_string()));
+        }
+        assert_eq!(log.entries().count(), LOG_SIZE);
+        assert_eq!(log.entries().next().unwrap().1, "desire: 0");
+    }
+}
@@ -0,0 +1,137 @@
+use crate::map_dynamic::Itinerary;
+use crate::transit::{BusLineID, BusLines, BusStop};
+use crate::vehicles::{make_vehicle_entity, Vehicle, VehicleID, VehicleKind};
+use crate::{Egregoria, ParCommandBuffer};
+use common::GameTime;
+use geom::Transform;
+use legion::{system, Entity};
+use map_model::{CarPath, Map};
+use serde::{Deserialize, Serialize};
+
+/// Time a bus waits at each stop for the passengers to get in and out, in seconds
+pub const BUS_STOP_TIME: f64 = 10.0;
+
+/// How close to the stop a bus has to be to open its doors
+const BUS_STOP_DIST: f32 = 5.0;
+
+/// Time a bus waits before looking for a path again when its next stop can't be reached,
+/// in seconds
+const BUS_RETRY_TIME: f64 = 30.0;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum BusState {
+    Driving,
+    /// Waiting at the stop until the given timestamp
+    Stopped(f64),
+    /// The next stop can't be reached, looking for a path again at the given timestamp
+    Stuck(f64),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Bus {
+    pub line: BusLineID,
+    /// Index of the stop the bus is driving to or stopped at
+    pub stop: usize,
+    pub state: BusState,
+}
+
+impl Bus {
+    /// Whether the passengers can get in and out at this stop
+    pub fn stopped_at(&self, stop: usize) -> bool {
+        matches!(self.state, BusState::Stopped(_)) && self.stop == stop
+    }
+}
+
+/// Spawns a bus of the line at one of its stops
+pub fn spawn_bus(
+    goria: &mut Egregoria,
+    line: BusLineID,
+    stops: &[BusStop],
+    at: usize,
+) -> VehicleID {
+    let stop = stops[at];
+    let map = goria.read::<Map>();
+    let mut trans = Transform::new(stop.pos);
+    if let Some(lane) = map.lanes().get(stop.lane) {
+        let (_, _, dir) = lane.points.project_segment_dir(stop.pos);
+        trans.set_direction(dir);
+    }
+    drop(map);
+
+    let e = make_vehicle_entity(
+        goria,
+        trans,
+        Vehicle::driving(VehicleKind::Bus),
+        Itinerary::none(),
+        true,
+    );
+    goria.add_comp(
+        e,
+        Bus {
+            line,
+            stop: at,
+            state: BusState::Driving,
+        },
+    );
+    VehicleID(e)
+}
+
+register_system!(bus_update);
+/// Buses drive to their next stop, wait there for the passengers then go to the one after.
+#[system(par_for_each)]
+pub fn bus_update(
+    #[resource] time: &GameTime,
+    #[resource] map: &Map,
+    #[resource] lines: &BusLines,
+    #[resource] cbuf: &ParCommandBuffer,
+    me: &Entity,
+    trans: &Transform,
+    bus: &mut Bus,
+    it: &mut Itinerary,
+) {
+    let line = unwrap_or!(lines.get(bus.line), {
+        cbuf.kill(*me);
+        return;
+    });
+    let n_stops = line.stops.len();
+    let stop = line.stops[bus.stop % n_stops];
+
+    match bus.state {
+        BusState::Driving => {
+            if !it.has_ended(time.timestamp) {
+                return;
+            }
+            let pos = trans.position();
+            if pos.is_close(stop.pos, BUS_STOP_DIST) {
+                bus.state = BusState::Stopped(time.timestamp + BUS_STOP_TIME);
+                return;
+            }
+            match Itinerary::route(pos, stop.pos, map, &CarPath) {
+                Some(route) => *it = route,
+                None => {
+                    log::warn!("bus of {:?} can't reach stop {}", bus.line, bus.stop);
+                    bus.state = BusState::Stuck(time.timestamp + BUS_RETRY_TIME);
+                }
+            }
+        }
+        BusState::Stuck(retry) => {
+            if time.timestamp < retry {
+                return;
+            }
+            match Itinerary::route(trans.position(), stop.pos, map, &CarPath) {
+                Some(route) => {
+                    log::info!("bus of {:?} can reach stop {} again", bus.line, bus.stop);
+                    *it = route;
+                    bus.state = BusState::Driving;
+                }
+                None => bus.state = BusState::Stuck(time.timestamp + BUS_RETRY_TIME),
+            }
+        }
+        BusState::Stopped(until) => {
+            if time.timestamp >= until {
+                bus.stop = (bus.stop + 1) % n_stops;
+                bus.state = BusState::Driving;
+            }
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use crate::map_dynamic::DETOUR;
+use crate::vehicles::{VehicleID, VehicleKind};
+use crate::{Egregoria, ParCommandBuffer};
+use geom::Vec2;
+use map_model::{LaneID, LaneKind, Map};
+use ordered_float::OrderedFloat;
+use serde::{Deserialize, Serialize};
+use slotmap::{new_key_type, SlotMap};
+
+mod bus;
+
+pub use bus::*;
+
+new_key_type! {
+    pub struct BusLineID;
+}
+
+debug_inspect_impl!(BusLineID);
+
+/// Stops served by each bus of a line
+const STOPS_PER_BUS: usize = 4;
+
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct BusStop {
+    pub lane: LaneID,
+    /// Where the buses stop, on the lane
+    pub pos: Vec2,
+    /// Where the passengers wait for the bus, on the closest sidewalk
+    pub sidewalk: Vec2,
+}
+
+impl BusStop {
+    /// A stop on the driving lane closest to the position, if any
+    pub fn new(map: &Map, near: Vec2) -> Option<Self> {
+        let lane = map.nearest_lane(near, LaneKind::Driving)?;
+        let pos = map.lanes()[lane].points.project(near);
+        let sidewalk = map
+            .nearest_lane(pos, LaneKind::Walking)
+            .map(|l| map.lanes()[l].points.project(pos))
+            .unwrap_or(pos);
+        Some(Self {
+            lane,
+            pos,
+            sidewalk,
+        })
+    }
+}
+
+/// Buses drive from stop to stop in order, going back to the first stop after the last one
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BusLine {
+    pub stops: Vec<BusStop>,
+    pub buses: Vec<VehicleID>,
+}
+
+/// Where to get on and off a bus line
+#[derive(Copy, Clone, Debug)]
+pub struct BusTrip {
+    pub line: BusLineID,
+    pub board: usize,
+    pub alight: usize,
+}
+
+register_resource!(BusLines, "bus_lines");
+#[derive(Default, Serialize, Deserialize)]
+pub struct BusLines {
+    lines: SlotMap<BusLineID, BusLine>,
+}
+
+impl BusLines {
+    pub fn get(&self, id: BusLineID) -> Option<&BusLine> {
+        self.lines.get(id)
+    }
+
+    pub fn stop(&self, id: BusLineID, stop: usize) -> Option<&BusStop> {
+        self.lines.get(id)?.stops.get(stop)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (BusLineID, &BusLine)> {
+        self.lines.iter()
+    }
+
+    /// The line with the shortest estimated trip, walking to and from the stops closest to the
+    /// positions, with its estimated time in seconds
+    pub fn best_trip(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        walk_time: impl Fn(Vec2, Vec2) -> f32,
+    ) -> Option<(BusTrip, f32)> {
+        let closest = |stops: &[BusStop], p: Vec2| {
+            stops
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, s)| OrderedFloat(s.sidewalk.distance(p)))
+                .map(|(i, s)| (i, s.sidewalk))
+        };
+
+        self.lines
+            .iter()
+            .filter(|(_, line)| !line.buses.is_empty())
+            .filter_map(|(id, line)| {
+                let (board, board_pos) = closest(&line.stops, from)?;
+                let (alight, alight_pos) = closest(&line.stops, to)?;
+                if board == alight {
+                    return None;
+                }
+                let trip = BusTrip {
+                    line: id,
+                    board,
+                    alight,
+                };
+                let cost = walk_time(from, board_pos)
+                    + line.wait_time()
+                    + line.ride_time(board, alight)
+                    + walk_time(alight_pos, to);
+                Some((trip, cost))
+            })
+            .min_by_key(|&(_, cost)| OrderedFloat(cost))
+    }
+}
+
+impl BusLine {
+    /// Estimated time to ride from one stop to another, stopping at the stops in between
+    pub fn ride_time(&self, from: usize, to: usize) -> f32 {
+        let n = self.stops.len();
+        let mut time = 0.0;
+        let mut i = from;
+        while i != to {
+            let next = (i + 1) % n;
+            time += self.stops[i].pos.distance(self.stops[next].pos) * DETOUR
+                / VehicleKind::Bus.cruising_speed();
+            if next != to {
+                time += BUS_STOP_TIME as f32;
+            }
+            i = next;
+        }
+        time
+    }
+
+    /// Estimated time waiting at a stop for the next bus, half the time between two buses
+    pub fn wait_time(&self) -> f32 {
+        if self.buses.is_empty() {
+            return f32::INFINITY;
+        }
+        let lap = self.ride_time(0, self.stops.len() - 1)
+            + self.ride_time(self.stops.len() - 1, 0)
+            + BUS_STOP_TIME as f32 * 2.0;
+        lap / self.buses.len() as f32 * 0.5
+    }
+}
+
+/// Creates a line going through the stops closest to the points, with buses spread along it.
+/// Returns None if there are less than two stops.
+pub fn add_bus_line(goria: &mut Egregoria, points: &[Vec2]) -> Option<BusLineID> {
+    let map = goria.read::<Map>();
+    let stops: Vec<BusStop> = points
+        .iter()
+        .filter_map(|&p| BusStop::new(&map, p))
+        .collect();
+    drop(map);
+    if stops.len() < 2 {
+        return None;
+    }
+
+    let id = goria.write::<BusLines>().lines.insert(BusLine {
+        stops: stops.clone(),
+        buses: vec![],
+    });
+
+    let n_buses = (stops.len() / STOPS_PER_BUS).max(1);
+    let buses: Vec<VehicleID> = (0..n_buses)
+        .map(|i| spawn_bus(goria, id, &stops, i * stops.len() / n_buses))
+        .collect();
+
+    if let Some(line) = goria.write::<BusLines>().lines.get_mut(id) {
+        line.buses = buses;
+    }
+
+    log::info!("bus line {:?} created with {} stops", id, stops.len());
+    Some(id)
+}
+
+/// Removes the line, its buses leave the map with their passengers getting off where they are
+pub fn remove_bus_line(goria: &mut Egregoria, id: BusLineID) {
+    let line = unwrap_or!(goria.write::<BusLines>().lines.remove(id), return);
+    let cbuf = goria.read::<ParCommandBuffer>();
+    for bus in line.buses {
+        cbuf.kill(bus.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BusLine, BusLines, BusStop};
+    use crate::utils::mk_ent;
+    use crate::vehicles::VehicleID;
+    use geom::{vec2, Vec2};
+    use map_model::LaneID;
+
+    fn stop(x: f32) -> BusStop {
+        BusStop {
+            lane: LaneID::default(),
+            pos: vec2(x, 0.0),
+            sidewalk: vec2(x, 5.0),
+        }
+    }
+
+    #[test]
+    fn test_best_trip() {
+        let mut lines = BusLines::default();
+        let id = lines.lines.insert(BusLine {
+            stops: vec![stop(0.0), stop(500.0), stop(1000.0)],
+            buses: vec![VehicleID(mk_ent(1))],
+        });
+        let walk = |a: Vec2, b: Vec2| a.distance(b);
+
+        let (trip, cost) = lines
+            .best_trip(vec2(980.0, 20.0), vec2(10.0, 10.0), walk)
+            .unwrap();
+        assert_eq!(trip.line, id);
+        assert_eq!(trip.board, 2);
+        assert_eq!(trip.alight, 0);
+
+        let line = &lines.lines[id];
+        assert!(cost > line.ride_time(2, 0) + line.wait_time());
+        assert!(line.ride_time(0, 2) > line.ride_time(2, 0));
+
+        lines.lines[id].buses.clear();
+        assert!(lines
+            .best_trip(vec2(980.0, 20.0), vec2(10.0, 10.0), walk)
+            .is_none());
+    }
+}
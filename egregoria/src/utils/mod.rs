@@ -7,6 +7,17 @@ pub mod par_command_buffer;
 pub mod rand_provider;
 pub mod scheduler;
 
+/// Entities are allocated with increasing ids, so ordering by this key is ordering by creation.
+pub fn entity_key(e: legion::Entity) -> u64 {
+    unsafe { std::mem::transmute(e) }
+}
+
+/// Inverse of [entity_key], makes up entities for tests that don't need a world.
+#[cfg(test)]
+pub(crate) fn mk_ent(id: u64) -> legion::Entity {
+    unsafe { std::mem::transmute(id) }
+}
+
 pub fn rand_world<T>(world: &mut Egregoria) -> T
 where
     rand_distr::Standard: rand_distr::Distribution<T>,
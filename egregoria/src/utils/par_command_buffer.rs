@@ -1,4 +1,6 @@
+use crate::map_dynamic::Reservations;
 use crate::physics::Collider;
+use crate::utils::entity_key;
 use crate::vehicles::Vehicle;
 use crate::Egregoria;
 use legion::storage::Component;
@@ -22,10 +24,14 @@ impl<T> Deleted<T> {
 type ExecType = Box<dyn for<'a> FnOnce(&'a mut Egregoria) + Send>;
 
 register_resource_noserialize!(ParCommandBuffer);
+/// Commands are pushed from parallel systems so they arrive in an arbitrary order.
+/// Every command is tied to an entity and they are applied sorted by entity so the
+/// simulation stays deterministic, except the ones pushed by systems that don't run in parallel.
 #[derive(Default)]
 pub struct ParCommandBuffer {
     to_kill: Mutex<Vec<Entity>>,
-    execs: Mutex<Vec<ExecType>>,
+    execs: Mutex<Vec<(Entity, ExecType)>>,
+    globals: Mutex<Vec<ExecType>>,
 }
 
 impl ParCommandBuffer {
@@ -36,16 +42,27 @@ impl ParCommandBuffer {
         self.to_kill.lock().unwrap().extend_from_slice(e);
     }
 
+    /// Executes f at the end of the frame. Commands tied to the same entity are run in the order they were pushed.
+    pub fn exec_ent(&self, e: Entity, f: impl for<'a> FnOnce(&'a mut Egregoria) + 'static + Send) {
+        self.execs.lock().unwrap().push((e, Box::new(f)));
+    }
+
+    /// Executes f at the end of the frame, after the commands tied to entities. Only for systems
+    /// that don't run in parallel, commands are run in the order they were pushed.
     pub fn exec(&self, f: impl for<'a> FnOnce(&'a mut Egregoria) + 'static + Send) {
-        self.execs.lock().unwrap().push(Box::new(f));
+        self.globals.lock().unwrap().push(Box::new(f));
     }
 
-    pub fn exec_on<T: Resource>(&self, f: impl for<'a> FnOnce(&'a mut T) + 'static + Send) {
-        self.exec(|goria| f(&mut *goria.write::<T>()))
+    pub fn exec_on<T: Resource>(
+        &self,
+        e: Entity,
+        f: impl for<'a> FnOnce(&'a mut T) + 'static + Send,
+    ) {
+        self.exec_ent(e, |goria| f(&mut *goria.write::<T>()))
     }
 
     pub fn add_component<T: Component>(&self, e: Entity, c: T) {
-        self.exec(move |w| {
+        self.exec_ent(e, move |w| {
             if let Some(mut x) = w.world.entry(e) {
                 x.add_component(c)
             }
@@ -53,7 +70,7 @@ impl ParCommandBuffer {
     }
 
     pub fn remove_component<T: Component + Clone>(&self, e: Entity) {
-        self.exec(move |w| {
+        self.exec_ent(e, move |w| {
             Self::parse_del::<T>(w, e);
             if let Some(mut x) = w.world.entry(e) {
                 x.remove_component::<T>();
@@ -69,31 +86,56 @@ impl ParCommandBuffer {
         }
     }
 
+    /// Removes the entities to kill, then runs the commands. Entities killed by the commands
+    /// are removed right after so that no system sees them on the next frame.
     pub fn apply(goria: &mut Egregoria) {
-        let deleted: Vec<Entity> = std::mem::take(
+        Self::apply_kills(goria);
+
+        let mut funs: Vec<(Entity, ExecType)> = std::mem::take(
             goria
                 .write::<ParCommandBuffer>()
-                .to_kill
+                .execs
                 .lock()
                 .unwrap()
                 .as_mut(),
         );
-        for entity in deleted {
-            Self::parse_del::<Collider>(goria, entity);
-            Self::parse_del::<Vehicle>(goria, entity);
-            goria.world.remove(entity);
+        // stable sort to keep the order of commands of a single entity
+        funs.sort_by_key(|(e, _)| entity_key(*e));
+        for (_, fun) in funs {
+            fun(goria);
         }
 
-        let funs: Vec<ExecType> = std::mem::take(
+        let globals: Vec<ExecType> = std::mem::take(
             goria
                 .write::<ParCommandBuffer>()
-                .execs
+                .globals
                 .lock()
                 .unwrap()
                 .as_mut(),
         );
-        for fun in funs {
+        for fun in globals {
             fun(goria);
         }
+
+        Self::apply_kills(goria);
+    }
+
+    fn apply_kills(goria: &mut Egregoria) {
+        let mut deleted: Vec<Entity> = std::mem::take(
+            goria
+                .write::<ParCommandBuffer>()
+                .to_kill
+                .lock()
+                .unwrap()
+                .as_mut(),
+        );
+        deleted.sort_unstable_by_key(|&e| entity_key(e));
+        deleted.dedup();
+        for entity in deleted {
+            Self::parse_del::<Collider>(goria, entity);
+            Self::parse_del::<Vehicle>(goria, entity);
+            goria.write::<Reservations>().release(entity);
+            goria.world.remove(entity);
+        }
     }
 }
@@ -38,6 +38,7 @@ pub enum VehicleKind {
     Car,
     Truck,
     Bus,
+    Bike,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Inspect)]
@@ -73,6 +74,7 @@ impl VehicleKind {
             VehicleKind::Car => 4.5,
             VehicleKind::Truck => 6.0,
             VehicleKind::Bus => 9.0,
+            VehicleKind::Bike => 2.0,
         }
     }
 
@@ -81,12 +83,14 @@ impl VehicleKind {
             VehicleKind::Car => 3.0,
             VehicleKind::Truck => 2.5,
             VehicleKind::Bus => 2.0,
+            VehicleKind::Bike => 1.5,
         }
     }
 
     pub fn deceleration(self) -> f32 {
         match self {
             VehicleKind::Car | VehicleKind::Bus | VehicleKind::Truck => 9.0,
+            VehicleKind::Bike => 5.0,
         }
     }
 
@@ -95,6 +99,7 @@ impl VehicleKind {
             VehicleKind::Car => 3.0,
             VehicleKind::Truck => 4.0,
             VehicleKind::Bus => 5.0,
+            VehicleKind::Bike => 1.5,
         }
     }
 
@@ -102,6 +107,7 @@ impl VehicleKind {
         match self {
             VehicleKind::Car => 12.0,
             VehicleKind::Truck | VehicleKind::Bus => 10.0,
+            VehicleKind::Bike => 5.0,
         }
     }
 
@@ -110,6 +116,7 @@ impl VehicleKind {
             VehicleKind::Car => 1.0,
             VehicleKind::Truck => 0.9,
             VehicleKind::Bus => 0.8,
+            VehicleKind::Bike => 2.0,
         }
     }
 }
@@ -144,6 +151,21 @@ pub fn spawn_parked_vehicle(
     )))
 }
 
+/// Spawns a bike stowed with its owner, hidden and out of the collision world until ridden
+pub fn spawn_bike(goria: &mut Egregoria, pos: Vec2) -> VehicleID {
+    let e = make_vehicle_entity(
+        goria,
+        Transform::new(pos),
+        Vehicle::driving(VehicleKind::Bike),
+        Itinerary::none(),
+        false,
+    );
+    if let Some(ar) = goria.comp_mut::<AssetRender>(e) {
+        ar.hide = true;
+    }
+    VehicleID(e)
+}
+
 pub fn make_vehicle_entity(
     goria: &mut Egregoria,
     trans: Transform,
@@ -152,14 +174,17 @@ pub fn make_vehicle_entity(
     mk_collider: bool,
 ) -> Entity {
     let asset_id = match vehicle.kind {
-        VehicleKind::Car => AssetID::CAR,
-        VehicleKind::Truck => AssetID::TRUCK,
-        VehicleKind::Bus => unreachable!(),
+        // Bikes look like tiny cars until they get their own asset
+        VehicleKind::Car | VehicleKind::Bike => AssetID::CAR,
+        // Buses look like yellow trucks until they get their own asset
+        VehicleKind::Truck | VehicleKind::Bus => AssetID::TRUCK,
     };
 
     let tint = match vehicle.kind {
         VehicleKind::Car => get_random_car_color(&mut *goria.write::<RandProvider>()),
-        _ => Color::WHITE,
+        VehicleKind::Bus => Color::from_hex(0xf2_c2_1b),
+        VehicleKind::Bike => Color::from_hex(0x2e_8b_57),
+        VehicleKind::Truck => Color::WHITE,
     };
 
     let w = vehicle.kind.width();
@@ -222,6 +247,17 @@ impl Vehicle {
             flag: 0,
         }
     }
+
+    /// A vehicle already on the road, like a bus that never parks
+    pub fn driving(kind: VehicleKind) -> Vehicle {
+        Self {
+            ang_velocity: 0.0,
+            wait_time: 0.0,
+            state: VehicleState::Driving,
+            kind,
+            flag: 0,
+        }
+    }
 }
 
 debug_inspect_impl!(VehicleKind);
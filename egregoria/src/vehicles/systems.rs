@@ -1,4 +1,6 @@
-use crate::map_dynamic::{Itinerary, ParkingManagement, OBJECTIVE_OK_DIST};
+use crate::map_dynamic::{
+    Itinerary, ParkingManagement, Reservations, RightOfWay, OBJECTIVE_OK_DIST,
+};
 use crate::physics::Kinematics;
 use crate::physics::{Collider, CollisionWorld, PhysicsGroup, PhysicsObject};
 use crate::utils::Restrict;
@@ -8,7 +10,7 @@ use common::GameTime;
 use geom::{angle_lerp, Ray, Transform, Vec2};
 use legion::system;
 use legion::Entity;
-use map_model::{Map, TrafficBehavior, Traversable, TraverseKind};
+use map_model::{Map, TrafficBehavior, TraverseKind};
 
 register_system!(vehicle_cleanup);
 #[system]
@@ -29,6 +31,8 @@ pub fn vehicle_decision(
     #[resource] map: &Map,
     #[resource] time: &GameTime,
     #[resource] cow: &CollisionWorld,
+    #[resource] row: &RightOfWay,
+    #[resource] reservations: &Reservations,
     me: &Entity,
     it: &mut Itinerary,
     trans: &mut Transform,
@@ -44,10 +48,15 @@ pub fn vehicle_decision(
         let danger_length =
             (self_obj.speed.powi(2) / (2.0 * vehicle.kind.deceleration())).min(40.0);
         let neighbors = cow.query_around(trans.position(), 12.0 + danger_length);
-        let objs =
-            neighbors.map(|(id, pos)| (pos, cow.get(id).expect("Handle not in collision world").1));
-
-        let (s, d) = calc_decision(*me, vehicle, map, time, trans, self_obj, it, objs);
+        let objs: Vec<_> = neighbors
+            .map(|(id, pos)| (pos, cow.get(id).expect("Handle not in collision world").1))
+            .collect();
+
+        let must_yield = row.must_yield(*me) || reservations.waiting(*me);
+        let reserved = reservations.holds(*me);
+        let (s, d) = calc_decision(
+            *me, vehicle, map, time, trans, self_obj, it, &objs, must_yield, reserved,
+        );
         desired_speed = s;
         desired_dir = d;
     }
@@ -141,7 +150,7 @@ fn physics(
 }
 
 /// Decide the appropriate velocity and direction to aim for.
-pub fn calc_decision<'a>(
+pub fn calc_decision(
     me: Entity,
     vehicle: &mut Vehicle,
     map: &Map,
@@ -149,7 +158,9 @@ pub fn calc_decision<'a>(
     trans: &Transform,
     self_obj: &PhysicsObject,
     it: &Itinerary,
-    neighs: impl Iterator<Item = (Vec2, &'a PhysicsObject)>,
+    neighs: &[(Vec2, &PhysicsObject)],
+    must_yield: bool,
+    reserved: bool,
 ) -> (f32, Vec2) {
     let default_return = (0.0, self_obj.dir);
     if vehicle.wait_time > 0.0 {
@@ -164,7 +175,15 @@ pub fn calc_decision<'a>(
 
     let cutoff = (0.8 + stop_dist).min(1.5);
 
-    let (front_dist, flag) = calc_front_dist(vehicle, trans, self_obj, it, neighs, cutoff);
+    let (front_dist, flag) = calc_front_dist(
+        vehicle,
+        trans,
+        self_obj,
+        it,
+        neighs.iter().copied(),
+        cutoff,
+        reserved,
+    );
 
     let position = trans.position();
     let dir_to_pos = unwrap_or!(
@@ -204,14 +223,21 @@ pub fn calc_decision<'a>(
         }
     }
 
-    if let Some(Traversable {
-        kind: TraverseKind::Lane(l_id),
-        ..
-    }) = it.get_travers()
-    {
-        if let Some(l) = map.lanes().get(*l_id) {
+    if let Some(travers) = it.get_travers() {
+        let lane = match travers.kind {
+            TraverseKind::Lane(l_id) => map.lanes().get(l_id),
+            TraverseKind::Turn(_) => None,
+        };
+        if let Some(l) = lane {
             let light = l.control_point();
-            match l.control.get_behavior(time.seconds) {
+
+            // Give way to the vehicles with the right of way
+            if must_yield && light.is_close(position, OBJECTIVE_OK_DIST * 1.05 + 2.0 + stop_dist) {
+                return (0.0, dir_to_pos);
+            }
+
+            let next = it.peek();
+            match travers.signal(next, time.seconds, map) {
                 TrafficBehavior::RED | TrafficBehavior::ORANGE => {
                     if light.is_close(
                         position,
@@ -228,7 +254,16 @@ pub fn calc_decision<'a>(
                         return (0.0, dir_to_pos);
                     }
                 }
-                _ => {}
+                TrafficBehavior::GREEN => {
+                    // Permissive lefts wait for a gap in the oncoming traffic
+                    let yields = matches!(next.and_then(|n| n.turn(map)), Some(t) if t.yields());
+                    if yields
+                        && light.is_close(position, OBJECTIVE_OK_DIST * 1.05 + 2.0 + stop_dist)
+                        && oncoming_traffic(position, trans.direction(), neighs)
+                    {
+                        return (0.0, dir_to_pos);
+                    }
+                }
             }
         }
     }
@@ -241,6 +276,20 @@ pub fn calc_decision<'a>(
     (vehicle.kind.cruising_speed(), dir_to_pos)
 }
 
+/// How far away oncoming vehicles are yielded to when turning left without an arrow
+const YIELD_DIST: f32 = 30.0;
+
+/// Whether a vehicle is coming from the front, that a permissive left turn must yield to
+fn oncoming_traffic(position: Vec2, dir: Vec2, neighs: &[(Vec2, &PhysicsObject)]) -> bool {
+    neighs.iter().any(|&(his_pos, his_obj)| {
+        matches!(his_obj.group, PhysicsGroup::Vehicles)
+            && his_obj.speed > 0.5
+            && his_obj.dir.dot(dir) < -0.7
+            && (his_pos - position).dot(dir) > 0.0
+            && his_pos.is_close(position, YIELD_DIST)
+    })
+}
+
 /// Calculates the distance to the closest problematic object in front of the car.
 /// It can be another car or a pedestrian, or it can be a potential collision point from a
 /// car coming perpendicularly.
@@ -251,6 +300,7 @@ fn calc_front_dist<'a>(
     it: &Itinerary,
     neighs: impl Iterator<Item = (Vec2, &'a PhysicsObject)>,
     cutoff: f32,
+    reserved: bool,
 ) -> (f32, u64) {
     let position = trans.position();
     let direction = trans.direction();
@@ -310,8 +360,9 @@ fn calc_front_dist<'a>(
             continue;
         }
 
-        // don't do ray checks for other things than cars
-        if !is_vehicle {
+        // don't do ray checks for other things than cars, nor when the vehicle reserved its
+        // turn as the crossing traffic waits for it
+        if !is_vehicle || reserved {
             continue;
         }
 
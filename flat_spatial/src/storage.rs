@@ -219,9 +219,45 @@ impl<T: Default> Storage<T> for DenseStorage<T> {
 #[derive(Clone, Deserialize, Serialize)]
 pub struct SparseStorage<T: Default> {
     cell_size: i32,
+    #[serde(
+        with = "cells_serde",
+        bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>")
+    )]
     cells: HashMap<CellIdx, T>,
 }
 
+/// Formats like json only allow string keys for maps, so cells are written as a sorted list of pairs there.
+mod cells_serde {
+    use super::CellIdx;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer, T: Serialize>(
+        cells: &HashMap<CellIdx, T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let mut cells: Vec<_> = cells.iter().collect();
+            cells.sort_unstable_by_key(|(&idx, _)| idx);
+            serializer.collect_seq(cells)
+        } else {
+            serializer.collect_map(cells.iter())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<CellIdx, T>, D::Error> {
+        if deserializer.is_human_readable() {
+            Ok(Vec::<(CellIdx, T)>::deserialize(deserializer)?
+                .into_iter()
+                .collect())
+        } else {
+            HashMap::deserialize(deserializer)
+        }
+    }
+}
+
 impl<T: Default> SparseStorage<T> {
     pub fn cells(&self) -> &HashMap<CellIdx, T> {
         &self.cells
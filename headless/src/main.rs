@@ -0,0 +1,155 @@
+use common::DayTime;
+use egregoria::economy::Workers;
+use egregoria::pedestrians::Pedestrian;
+use egregoria::save_slots::DEFAULT_SLOT;
+use egregoria::vehicles::Vehicle;
+use egregoria::Egregoria;
+use geom::{vec3, Camera};
+use legion::IntoQuery;
+use log::{Level, LevelFilter, Metadata, Record};
+use map_model::Map;
+use serde::Serialize;
+use std::time::Instant;
+
+/// Default length of a tick in in-game seconds
+const DEFAULT_DELTA: f64 = 1.0 / 30.0;
+const DEFAULT_TICKS: u32 = 1000;
+
+struct HeadlessLog;
+
+impl log::Log for HeadlessLog {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= Level::Warn
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "[{}] {}: {}",
+                record.level(),
+                record.module_path_static().unwrap_or_default(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[derive(Serialize)]
+struct Summary {
+    ticks: u32,
+    delta: f64,
+    start: DayTime,
+    end: DayTime,
+    real_time_secs: f64,
+    entities: usize,
+    pedestrians: usize,
+    vehicles: usize,
+    companies: usize,
+    roads: usize,
+    buildings: usize,
+}
+
+fn parse_arg<T: std::str::FromStr>(arg: Option<String>, default: T) -> T {
+    match arg {
+        Some(x) => x.parse().unwrap_or_else(|_| {
+            eprintln!("couldn't parse argument {}", x);
+            std::process::exit(1);
+        }),
+        None => default,
+    }
+}
+
+fn main() {
+    log::set_logger(&HeadlessLog).unwrap();
+    log::set_max_level(LevelFilter::Warn);
+
+    let mut args = std::env::args().skip(1).peekable();
+
+    match args.peek().map(String::as_str) {
+        Some("export") | Some("import") => {
+            let command = args.next().unwrap();
+            let name: String = parse_arg(args.next(), "export".to_string());
+            let slot: String = parse_arg(args.next(), DEFAULT_SLOT.to_string());
+            convert(&command, &name, &slot);
+            return;
+        }
+        _ => {}
+    }
+
+    let ticks: u32 = parse_arg(args.next(), DEFAULT_TICKS);
+    let delta: f64 = parse_arg(args.next(), DEFAULT_DELTA);
+    let slot: String = parse_arg(args.next(), DEFAULT_SLOT.to_string());
+
+    let mut goria = Egregoria::init();
+    // Some systems (like tree generation) expect a camera to exist, give them one that sees nothing.
+    goria.insert(Camera::new(1.0, 1.0, vec3(0.0, 0.0, 1.0)));
+    egregoria::load_from_disk(&mut goria, &slot);
+
+    let start = goria.read::<common::GameTime>().daytime;
+    let t = Instant::now();
+
+    for _ in 0..ticks {
+        goria.tick(delta);
+    }
+
+    let real_time_secs = t.elapsed().as_secs_f64();
+
+    egregoria::save_to_disk(&mut goria, &slot);
+
+    let map = goria.read::<Map>();
+    let summary = Summary {
+        ticks,
+        delta,
+        start,
+        end: goria.read::<common::GameTime>().daytime,
+        real_time_secs,
+        entities: goria.world.len(),
+        pedestrians: <&Pedestrian>::query().iter(&goria.world).count(),
+        vehicles: <&Vehicle>::query().iter(&goria.world).count(),
+        companies: <&Workers>::query().iter(&goria.world).count(),
+        roads: map.roads().len(),
+        buildings: map.buildings().len(),
+    };
+    drop(map);
+
+    common::saveload::save_silent_json(&summary, "headless_summary");
+
+    println!(
+        "ran {} ticks ({:.1} in-game seconds) in {:.2}s: day {} {} -> day {} {}",
+        ticks,
+        ticks as f64 * delta,
+        real_time_secs,
+        summary.start.day,
+        summary.start,
+        summary.end.day,
+        summary.end,
+    );
+    println!(
+        "{} entities, {} pedestrians, {} vehicles, {} companies",
+        summary.entities, summary.pedestrians, summary.vehicles, summary.companies
+    );
+}
+
+/// Converts between a save slot and a json export in world/<name>.json
+fn convert(command: &str, name: &str, slot: &str) {
+    let mut goria = Egregoria::init();
+    goria.insert(Camera::new(1.0, 1.0, vec3(0.0, 0.0, 1.0)));
+
+    if command == "export" {
+        egregoria::load_from_disk(&mut goria, slot);
+        if egregoria::export_json(&goria, name).is_none() {
+            eprintln!("couldn't export slot {}", slot);
+            std::process::exit(1);
+        }
+        println!("exported slot {} to world/{}.json", slot, name);
+    } else {
+        if egregoria::import_json(&mut goria, name).is_none() {
+            eprintln!("couldn't import world/{}.json", name);
+            std::process::exit(1);
+        }
+        egregoria::save_to_disk(&mut goria, slot);
+        println!("imported world/{}.json into slot {}", name, slot);
+    }
+}
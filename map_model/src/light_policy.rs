@@ -1,4 +1,7 @@
-use crate::{Intersection, LaneID, Lanes, Roads, TrafficControl, TrafficLightSchedule};
+use crate::{
+    ActuatedLights, Intersection, LaneID, Lanes, RoadID, Roads, TrafficControl,
+    TrafficLightSchedule, TurnControl, TurnKind,
+};
 use imgui_inspect::{
     imgui::{im_str, Ui},
     InspectArgsDefault, InspectRenderDefault,
@@ -12,6 +15,10 @@ pub enum LightPolicy {
     StopSigns,
     Lights,
     Smart,
+    /// Lights changing depending on the vehicles waiting, see [ActuatedLights]
+    Actuated,
+    /// Lights with a left arrow phase before the vehicles going straight get green
+    ProtectedLefts,
 }
 
 impl Default for LightPolicy {
@@ -20,81 +27,177 @@ impl Default for LightPolicy {
     }
 }
 
+/// Length of the green and orange of a group of roads, in seconds
+const CYCLE_SIZE: usize = 14;
+const ORANGE_LENGTH: usize = 4;
+/// Length of the left arrow phase with protected lefts, orange included
+const LEFT_PHASE: usize = 8;
+const LEFT_ORANGE: usize = 3;
+
+/// The incoming lanes needing a light of a road
+type InRoads = Vec<(RoadID, Vec<LaneID>)>;
+
 impl LightPolicy {
-    pub fn apply(self, inter: &Intersection, lanes: &mut Lanes, roads: &Roads) {
-        let in_road_lanes: Vec<Vec<LaneID>> = inter
+    /// Sets the control of the incoming lanes and of the turns, and the controller of actuated
+    /// lights
+    pub fn apply(self, inter: &mut Intersection, lanes: &mut Lanes, roads: &Roads) {
+        let in_roads: InRoads = inter
             .roads
             .iter()
             .map(|&x| {
-                roads[x]
+                let lanes = roads[x]
                     .incoming_lanes_to(inter.id)
                     .iter()
                     .filter(|(_, kind)| kind.needs_light())
                     .map(|&(id, _)| id)
-                    .collect::<Vec<_>>()
+                    .collect::<Vec<_>>();
+                (x, lanes)
             })
-            .filter(|v| !v.is_empty())
+            .filter(|(_, v)| !v.is_empty())
             .collect();
 
-        for incoming_lanes in &in_road_lanes {
+        for (_, incoming_lanes) in &in_roads {
             for &lane in incoming_lanes {
                 lanes[lane].control = TrafficControl::Always;
             }
         }
+        for turn in inter.turns_mut() {
+            turn.control = TurnControl::Lane;
+        }
+        inter.actuated = None;
 
         match self {
             LightPolicy::NoLights => {}
             LightPolicy::StopSigns => {
-                Self::stop_signs(in_road_lanes, lanes);
+                Self::stop_signs(in_roads, lanes);
             }
             LightPolicy::Lights => {
-                Self::lights(in_road_lanes, inter, lanes);
+                Self::lights(&in_roads, inter, 0, lanes);
+            }
+            LightPolicy::ProtectedLefts => {
+                Self::lights(&in_roads, inter, LEFT_PHASE, lanes);
+            }
+            LightPolicy::Actuated => {
+                Self::permissive_lefts(&in_roads, inter, lanes);
+                inter.actuated = Some(Self::actuated(in_roads, lanes));
             }
             LightPolicy::Smart => {
-                if in_road_lanes.len() <= 2 {
+                if in_roads.len() <= 2 {
                     return;
                 }
 
                 if inter.turn_policy.left_turns {
-                    Self::lights(in_road_lanes, inter, lanes);
+                    Self::lights(&in_roads, inter, 0, lanes);
                 } else {
-                    Self::stop_signs(in_road_lanes, lanes);
+                    Self::stop_signs(in_roads, lanes);
                 }
             }
         }
     }
 
-    fn stop_signs(in_road_lanes: Vec<Vec<LaneID>>, lanes: &mut Lanes) {
-        for incoming_lanes in in_road_lanes {
+    fn stop_signs(in_roads: InRoads, lanes: &mut Lanes) {
+        for (_, incoming_lanes) in in_roads {
             for lane in incoming_lanes {
                 lanes[lane].control = TrafficControl::StopSign;
             }
         }
     }
 
-    fn lights(in_road_lanes: Vec<Vec<LaneID>>, inter: &Intersection, lanes: &mut Lanes) {
-        let n_cycles = (in_road_lanes.len() + 1) / 2;
-        let cycle_size = 14;
-        let orange_length = 4;
+    /// Groups of roads get green one after the other, opposite roads together. With a left
+    /// phase, the left turns of a group get a protected arrow before the lanes get green,
+    /// otherwise they yield to the oncoming traffic. Crosswalks get a pedestrian phase while the
+    /// next group of roads has green.
+    /// The cycle starts at a random time unless the intersection is part of a green wave.
+    fn lights(in_roads: &InRoads, inter: &mut Intersection, left_phase: usize, lanes: &mut Lanes) {
+        // Half the roads, rounded up
+        let n_cycles = in_roads.len() - in_roads.len() / 2;
+        let block = CYCLE_SIZE + left_phase;
+        let total_length = block * n_cycles;
 
-        let total_length = cycle_size * n_cycles;
+        let wave = inter.green_wave.and_then(|w| {
+            let i = in_roads.iter().position(|&(r, _)| r == w.road)?;
+            Some((i, w.start))
+        });
 
-        let offset = inter.id.as_ffi();
-        let inter_offset: usize =
-            rand::rngs::SmallRng::seed_from_u64(offset as u64).gen_range(0..total_length);
+        let inter_offset: usize = match wave {
+            // The lanes of the road are green when (seconds + offset) % total_length == 0
+            Some((i, start)) => {
+                (2 * total_length + left_phase
+                    - start as usize % total_length
+                    - block * (i % n_cycles))
+                    % total_length
+            }
+            None => {
+                let offset = inter.id.as_ffi();
+                rand::rngs::SmallRng::seed_from_u64(offset as u64).gen_range(0..total_length)
+            }
+        };
 
-        for (i, incoming_lanes) in in_road_lanes.into_iter().enumerate() {
-            let light = TrafficControl::Light(TrafficLightSchedule::from_basic(
-                cycle_size - orange_length,
-                orange_length,
-                total_length - cycle_size,
-                cycle_size * (i % n_cycles) + inter_offset,
-            ));
+        let lane_light = |group: usize| {
+            TrafficLightSchedule::from_basic(
+                CYCLE_SIZE - ORANGE_LENGTH,
+                ORANGE_LENGTH,
+                total_length - CYCLE_SIZE,
+                block * group + inter_offset + total_length - left_phase,
+            )
+        };
+        let left_arrow = |group: usize| {
+            TrafficLightSchedule::from_basic(
+                left_phase - LEFT_ORANGE,
+                LEFT_ORANGE,
+                total_length - left_phase,
+                block * group + inter_offset,
+            )
+        };
 
-            for lane in incoming_lanes {
+        for (i, (_, incoming_lanes)) in in_roads.iter().enumerate() {
+            let light = TrafficControl::Light(lane_light(i % n_cycles));
+            for &lane in incoming_lanes {
                 lanes[lane].control = light;
             }
         }
+
+        let group_of_road = |road| Some(in_roads.iter().position(|&(r, _)| r == road)? % n_cycles);
+        for turn in inter.turns_mut() {
+            let road = lanes[turn.id.src].parent;
+            let group = unwrap_or!(group_of_road(road), continue);
+            turn.control = match turn.kind {
+                TurnKind::Driving if turn.is_left(lanes) => {
+                    if left_phase > 0 {
+                        TurnControl::Light(left_arrow(group))
+                    } else {
+                        TurnControl::Permissive
+                    }
+                }
+                TurnKind::Crosswalk if n_cycles >= 2 => {
+                    TurnControl::Light(lane_light((group + 1) % n_cycles))
+                }
+                _ => TurnControl::Lane,
+            };
+        }
+    }
+
+    fn permissive_lefts(in_roads: &InRoads, inter: &mut Intersection, lanes: &Lanes) {
+        for turn in inter.turns_mut() {
+            let has_light = in_roads.iter().any(|(_, l)| l.contains(&turn.id.src));
+            if has_light && turn.is_left(lanes) {
+                turn.control = TurnControl::Permissive;
+            }
+        }
+    }
+
+    /// Opposite roads get green together, like with fixed lights
+    fn actuated(in_roads: InRoads, lanes: &mut Lanes) -> ActuatedLights {
+        // Half the roads, rounded up
+        let n_phases = in_roads.len() - in_roads.len() / 2;
+        let mut phases = vec![vec![]; n_phases];
+        for (i, (_, incoming_lanes)) in in_roads.into_iter().enumerate() {
+            phases[i % n_phases].extend(incoming_lanes);
+        }
+
+        let lights = ActuatedLights::new(phases);
+        lights.apply(lanes);
+        lights
     }
 }
 
@@ -118,6 +221,8 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
             LightPolicy::StopSigns => 1,
             LightPolicy::Lights => 2,
             LightPolicy::Smart => 3,
+            LightPolicy::Actuated => 4,
+            LightPolicy::ProtectedLefts => 5,
         };
 
         let changed = imgui_inspect::imgui::ComboBox::new(&im_str!("{}", label))
@@ -129,6 +234,8 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
                     &im_str!("Stop signs"),
                     &im_str!("Lights"),
                     &im_str!("Smart"),
+                    &im_str!("Actuated"),
+                    &im_str!("Protected lefts"),
                 ],
             );
 
@@ -138,6 +245,8 @@ impl InspectRenderDefault<LightPolicy> for LightPolicy {
                 1 => **p = LightPolicy::StopSigns,
                 2 => **p = LightPolicy::Lights,
                 3 => **p = LightPolicy::Smart,
+                4 => **p = LightPolicy::Actuated,
+                5 => **p = LightPolicy::ProtectedLefts,
                 _ => unreachable!(),
             }
         }
@@ -1,14 +1,17 @@
 use crate::procgen::Trees;
 use crate::{
-    Building, BuildingGen, BuildingID, BuildingKind, Intersection, IntersectionID, Lane, LaneID,
-    LaneKind, LanePattern, Lot, LotID, LotKind, ParkingSpotID, ParkingSpots, ProjectKind, Road,
-    RoadID, RoadSegmentKind, SpatialMap,
+    ActuatedLights, Building, BuildingGen, BuildingID, BuildingKind, GreenWave, Intersection,
+    IntersectionID, Lane, LaneID, LaneKind, LanePattern, Lot, LotID, LotKind, ParkingSpotID,
+    ParkingSpots, ProjectKind, Road, RoadID, RoadSegmentKind, SpatialMap,
 };
 use geom::{Intersect, Shape, Vec2};
-use geom::{Spline, OBB};
+use geom::{Spline, AABB, OBB};
 use ordered_float::OrderedFloat;
 use slotmap::DenseSlotMap;
 
+/// Intersections coordinated at most by a green wave
+const MAX_CORRIDOR_LENGTH: usize = 30;
+
 pub type Roads = DenseSlotMap<RoadID, Road>;
 pub type Lanes = DenseSlotMap<LaneID, Lane>;
 pub type Intersections = DenseSlotMap<IntersectionID, Intersection>;
@@ -60,8 +63,8 @@ impl Map {
         f(inter);
 
         let inter = &mut self.intersections[id];
-        inter.update_traffic_control(&mut self.lanes, &self.roads);
         inter.update_turns(&self.lanes, &self.roads);
+        inter.update_traffic_control(&mut self.lanes, &self.roads);
         self.dirty = true;
     }
 
@@ -84,8 +87,8 @@ impl Map {
         }
 
         let inter = &mut self.intersections[id];
-        inter.update_traffic_control(&mut self.lanes, &self.roads);
         inter.update_turns(&self.lanes, &self.roads);
+        inter.update_traffic_control(&mut self.lanes, &self.roads);
         inter.update_polygon(&self.roads);
 
         self.spatial_map.update(inter.id, inter.polygon.bbox());
@@ -388,6 +391,17 @@ impl Map {
         self.roads.is_empty() && self.lanes.is_empty() && self.intersections.is_empty()
     }
 
+    /// Bounding box of all the roads and buildings, None if there are none
+    pub fn bounds(&self) -> Option<AABB> {
+        self.roads
+            .values()
+            .map(|r| r.bbox())
+            .chain(self.buildings.values().map(|b| b.obb.bbox()))
+            .fold(None, |acc: Option<AABB>, bbox| {
+                Some(acc.map_or(bbox, |acc| acc.union(bbox)))
+            })
+    }
+
     pub fn roads(&self) -> &Roads {
         &self.roads
     }
@@ -417,6 +431,98 @@ impl Map {
         None
     }
 
+    /// The intersections met when driving along the road from its source, going on through the
+    /// road straight ahead at each intersection
+    pub fn corridor(&self, road: RoadID) -> Vec<IntersectionID> {
+        let road = unwrap_or!(self.roads.get(road), return vec![]);
+        let mut corridor = vec![road.src, road.dst];
+        let mut heading = -road.basic_orientation_from(road.dst);
+        let mut cur = road.id;
+
+        while corridor.len() < MAX_CORRIDOR_LENGTH {
+            let inter = &self.intersections[*corridor.last().unwrap()];
+            let next = inter
+                .roads
+                .iter()
+                .filter(|&&r| r != cur)
+                .map(|&r| (r, self.roads[r].basic_orientation_from(inter.id)))
+                .filter(|(_, dir)| dir.dot(heading) > 0.7)
+                .max_by_key(|(_, dir)| OrderedFloat(dir.dot(heading)));
+            let (next, _) = unwrap_or!(next, break);
+            let next_inter = self.roads[next].other_end(inter.id);
+            if corridor.contains(&next_inter) {
+                break;
+            }
+            heading = -self.roads[next].basic_orientation_from(next_inter);
+            corridor.push(next_inter);
+            cur = next;
+        }
+        corridor
+    }
+
+    /// Offsets the lights along the corridor so that vehicles driving at the given speed from
+    /// the first intersection get green at the next ones
+    pub fn green_wave(&mut self, corridor: &[IntersectionID], speed: f32) {
+        info!("green_wave {:?} {}", corridor, speed);
+        let mut t = 0.0;
+        for (k, &id) in corridor.iter().enumerate() {
+            // The first intersection starts its cycle with the road the platoon leaves on
+            let road = match k {
+                0 => corridor
+                    .get(1)
+                    .and_then(|&next| self.road_between(id, next)),
+                _ => self.road_between(corridor[k - 1], id),
+            };
+            let road = unwrap_or!(road, break);
+            if k > 0 {
+                t += self.roads[road].length / speed;
+            }
+
+            let inter = &mut self.intersections[id];
+            inter.green_wave = Some(GreenWave {
+                road,
+                start: t as u32,
+            });
+            inter.update_traffic_control(&mut self.lanes, &self.roads);
+        }
+        self.dirty = true;
+    }
+
+    fn road_between(&self, a: IntersectionID, b: IntersectionID) -> Option<RoadID> {
+        self.intersections
+            .get(a)?
+            .roads
+            .iter()
+            .copied()
+            .find(|&r| self.roads[r].other_end(a) == b)
+    }
+
+    /// Returns the actuated lights that change phase given the number of vehicles queued on each
+    /// lane, to be set with [Map::set_actuated_lights]. The map is left untouched.
+    pub fn actuated_lights_changes(
+        &self,
+        seconds: u32,
+        queue: impl Fn(LaneID) -> u32,
+    ) -> Vec<(IntersectionID, ActuatedLights)> {
+        self.intersections
+            .iter()
+            .filter_map(|(id, inter)| {
+                let mut lights = inter.actuated.clone()?;
+                if lights.update(seconds, &queue) {
+                    Some((id, lights))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn set_actuated_lights(&mut self, id: IntersectionID, lights: ActuatedLights) {
+        let inter = unwrap_or!(self.intersections.get_mut(id), return);
+        lights.apply(&mut self.lanes);
+        inter.actuated = Some(lights);
+    }
+
     pub fn nearest_lane(&self, p: Vec2, kind: LaneKind) -> Option<LaneID> {
         self.lanes
             .iter()
@@ -444,3 +550,123 @@ impl Map {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::procgen::add_four_way;
+    use crate::{
+        LanePatternBuilder, LightPolicy, Map, RoadSegmentKind, TrafficBehavior, TurnControl,
+        TurnKind,
+    };
+    use geom::{vec2, Vec2};
+
+    #[test]
+    fn test_green_wave() {
+        let mut map = Map::empty();
+        let a = map.add_intersection(vec2(0.0, 0.0));
+        let b = map.add_intersection(vec2(200.0, 0.0));
+        let c = map.add_intersection(vec2(400.0, 10.0));
+        let side = map.add_intersection(vec2(200.0, 200.0));
+        let pattern = LanePatternBuilder::new().build();
+        let ab = map.connect(a, b, &pattern, RoadSegmentKind::Straight);
+        let bc = map.connect(b, c, &pattern, RoadSegmentKind::Straight);
+        map.connect(b, side, &pattern, RoadSegmentKind::Straight);
+        for &id in &[a, b, c] {
+            map.update_intersection(id, |i| i.light_policy = LightPolicy::Lights);
+        }
+
+        let corridor = map.corridor(ab);
+        assert_eq!(corridor, vec![a, b, c]);
+
+        map.green_wave(&corridor, 10.0);
+        let wave = map.intersections()[c].green_wave.unwrap();
+        assert_eq!(wave.road, bc);
+        let expected = (map.roads()[ab].length + map.roads()[bc].length) / 10.0;
+        assert_eq!(wave.start, expected as u32);
+
+        let lane = map.roads()[bc].incoming_lanes_to(c)[0].0;
+        let control = map.lanes()[lane].control;
+        assert!(matches!(
+            control.get_behavior(wave.start),
+            TrafficBehavior::GREEN
+        ));
+        assert!(!matches!(
+            control.get_behavior(wave.start - 1),
+            TrafficBehavior::GREEN
+        ));
+    }
+
+    #[test]
+    fn test_protected_lefts() {
+        let mut map = Map::empty();
+        let pattern = LanePatternBuilder::new().build();
+        let center = add_four_way(Vec2::ZERO, &mut map, &pattern, &pattern).center;
+        map.update_intersection(center, |i| i.light_policy = LightPolicy::ProtectedLefts);
+
+        let inter = &map.intersections()[center];
+        let lanes = map.lanes();
+        let lefts: Vec<_> = inter.turns().iter().filter(|t| t.is_left(lanes)).collect();
+        assert!(!lefts.is_empty());
+
+        for turn in lefts {
+            assert!(matches!(turn.control, TurnControl::Light(_)));
+            let src = lanes[turn.id.src].control;
+            let arrow = (0..100)
+                .find(|&s| matches!(turn.behavior(lanes, s), TrafficBehavior::GREEN))
+                .unwrap();
+            // The lane is red while its left arrow is green, and gets green afterwards
+            assert!(src.get_behavior(arrow).is_red());
+            assert!(
+                (arrow..arrow + 20).any(|s| matches!(src.get_behavior(s), TrafficBehavior::GREEN))
+            );
+        }
+
+        for turn in inter.turns() {
+            if matches!(turn.kind, TurnKind::Crosswalk) {
+                assert!(matches!(turn.control, TurnControl::Light(_)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_right_of_way() {
+        let mk_map = |main_lanes: u32| {
+            let mut map = Map::empty();
+            let main = LanePatternBuilder::new().n_lanes(main_lanes).build();
+            let side = LanePatternBuilder::new().build();
+            let four_way = add_four_way(Vec2::ZERO, &mut map, &main, &side);
+            (map, four_way)
+        };
+
+        // Going north from the south, a vehicle going west comes from the right
+        let (map, fw) = mk_map(1);
+        let (r, turn) = (fw.roads, |from, to| fw.turn(&map, from, to));
+        let inter = &map.intersections()[fw.center];
+        let (lanes, roads) = (map.lanes(), map.roads());
+        let north = turn(r[0], r[1]);
+        let west = turn(r[2], r[3]);
+        assert!(north.conflicts(west));
+        assert!(!north.conflicts(turn(r[0], r[2])));
+        assert!(inter.main_roads(roads).is_none());
+        assert!(inter.has_priority(west, north, lanes, roads));
+        assert!(!inter.has_priority(north, west, lanes, roads));
+
+        // Left turns yield to the oncoming traffic
+        let left = turn(r[0], r[3]);
+        let south = turn(r[1], r[0]);
+        assert!(left.conflicts(south));
+        assert!(inter.has_priority(south, left, lanes, roads));
+        assert!(!inter.has_priority(left, south, lanes, roads));
+
+        // The wider road has the right of way
+        let (map, fw) = mk_map(2);
+        let (r, turn) = (fw.roads, |from, to| fw.turn(&map, from, to));
+        let inter = &map.intersections()[fw.center];
+        let (lanes, roads) = (map.lanes(), map.roads());
+        let north = turn(r[0], r[1]);
+        let west = turn(r[2], r[3]);
+        assert!(inter.main_roads(roads).is_some());
+        assert!(inter.has_priority(north, west, lanes, roads));
+        assert!(!inter.has_priority(west, north, lanes, roads));
+    }
+}
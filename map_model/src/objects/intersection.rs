@@ -1,8 +1,9 @@
 use crate::{
-    Intersections, LaneID, Lanes, LightPolicy, RoadID, Roads, SpatialMap, TraverseDirection, Turn,
-    TurnID, TurnPolicy,
+    ActuatedLights, GreenWave, Intersections, LaneID, Lanes, LightPolicy, RoadID, Roads,
+    SpatialMap, TraverseDirection, Turn, TurnID, TurnKind, TurnPolicy, TurnV0,
 };
 use geom::pseudo_angle;
+use geom::vec2;
 use geom::Polygon;
 use geom::Spline;
 use geom::Vec2;
@@ -33,10 +34,43 @@ pub struct Intersection {
 
     pub turn_policy: TurnPolicy,
     pub light_policy: LightPolicy,
+    pub actuated: Option<ActuatedLights>,
+    pub green_wave: Option<GreenWave>,
+    /// Vehicles reserve their turn before entering instead of looking out for the others
+    pub reservations: bool,
 
     pub polygon: Polygon,
 }
 
+/// An intersection as saved before it had actuated lights, green waves and reservations
+#[derive(Serialize, Deserialize)]
+pub(crate) struct IntersectionV0 {
+    pub(crate) id: IntersectionID,
+    pub(crate) pos: Vec2,
+    pub(crate) turns: Vec<TurnV0>,
+    pub(crate) roads: Vec<RoadID>,
+    pub(crate) turn_policy: TurnPolicy,
+    pub(crate) light_policy: LightPolicy,
+    pub(crate) polygon: Polygon,
+}
+
+impl From<IntersectionV0> for Intersection {
+    fn from(old: IntersectionV0) -> Self {
+        Self {
+            id: old.id,
+            pos: old.pos,
+            turns: old.turns.into_iter().map(Turn::from).collect(),
+            roads: old.roads,
+            turn_policy: old.turn_policy,
+            light_policy: old.light_policy,
+            actuated: None,
+            green_wave: None,
+            reservations: false,
+            polygon: old.polygon,
+        }
+    }
+}
+
 impl Intersection {
     pub fn make(store: &mut Intersections, spatial: &mut SpatialMap, pos: Vec2) -> IntersectionID {
         let id = store.insert_with_key(|id| Intersection {
@@ -46,6 +80,9 @@ impl Intersection {
             roads: Default::default(),
             turn_policy: Default::default(),
             light_policy: Default::default(),
+            actuated: None,
+            green_wave: None,
+            reservations: false,
             polygon: Polygon::centered_rect(pos, 5.0, 5.0),
         });
         spatial.insert(id, AABB::new(pos, pos));
@@ -75,10 +112,53 @@ impl Intersection {
         for turn in self.turns.iter_mut() {
             turn.make_points(lanes);
         }
+
+        self.update_conflicts();
+    }
+
+    /// Computes the driving turns each turn conflicts with, the turns must be up to date
+    pub(crate) fn update_conflicts(&mut self) {
+        let driving = |t: &&Turn| matches!(t.kind, TurnKind::Driving);
+        let conflicting: Vec<Vec<TurnID>> = self
+            .turns
+            .iter()
+            .map(|a| {
+                if !driving(&a) {
+                    return vec![];
+                }
+                self.turns
+                    .iter()
+                    .filter(driving)
+                    .filter(|b| a.conflicts(b))
+                    .map(|b| b.id)
+                    .collect()
+            })
+            .collect();
+        for (turn, conflicting) in self.turns.iter_mut().zip(conflicting) {
+            turn.conflicting = conflicting;
+        }
     }
 
-    pub fn update_traffic_control(&self, lanes: &mut Lanes, roads: &Roads) {
-        self.light_policy.apply(self, lanes, roads);
+    /// Whether the driving turns conflict, from the table computed with the turns
+    pub fn turns_conflict(&self, a: TurnID, b: TurnID) -> bool {
+        matches!(self.find_turn(a), Some(t) if t.conflicting.contains(&b))
+    }
+
+    /// Sets the control of the incoming lanes and of the turns, the turns must be up to date
+    pub fn update_traffic_control(&mut self, lanes: &mut Lanes, roads: &Roads) {
+        let policy = self.light_policy;
+        policy.apply(self, lanes, roads);
+    }
+
+    /// Sets the control of the lanes and of the turns again after loading, the actuated lights
+    /// keep their phase
+    pub(crate) fn restore_traffic_control(&mut self, lanes: &mut Lanes, roads: &Roads) {
+        let actuated = self.actuated.take();
+        self.update_traffic_control(lanes, roads);
+        if let Some(lights) = actuated {
+            lights.apply(lanes);
+            self.actuated = Some(lights);
+        }
     }
 
     pub fn update_interface_radius(&self, roads: &mut Roads) {
@@ -181,7 +261,53 @@ impl Intersection {
         })
     }
 
+    /// The two roads with more lanes than all the others, they have the right of way over the
+    /// side roads when there are no lights
+    pub fn main_roads(&self, roads: &Roads) -> Option<(RoadID, RoadID)> {
+        if self.roads.len() < 3 {
+            return None;
+        }
+        let mut by_lanes = self.roads.clone();
+        by_lanes.sort_by_key(|&r| std::cmp::Reverse(roads[r].n_lanes()));
+        if roads[by_lanes[1]].n_lanes() > roads[by_lanes[2]].n_lanes() {
+            return Some((by_lanes[0], by_lanes[1]));
+        }
+        None
+    }
+
+    /// Whether a vehicle taking the turn `a` goes before one taking the conflicting turn `b`
+    /// when there are no lights. Vehicles coming from the main roads go first, then the ones
+    /// coming from the right, and left turns yield to the oncoming traffic.
+    /// Both have priority when coming from the same road.
+    pub fn has_priority(&self, a: &Turn, b: &Turn, lanes: &Lanes, roads: &Roads) -> bool {
+        let (src_a, src_b) = (&lanes[a.id.src], &lanes[b.id.src]);
+        if let Some((r1, r2)) = self.main_roads(roads) {
+            let main = |r| r == r1 || r == r2;
+            if main(src_a.parent) != main(src_b.parent) {
+                return main(src_a.parent);
+            }
+        }
+
+        let travel_a = -src_a.orientation_from(self.id);
+        let from_b = src_b.orientation_from(self.id);
+        let cos = travel_a.dot(-from_b);
+        if cos > 0.7 {
+            return true;
+        }
+        // Oncoming
+        if cos < -0.7 {
+            return !a.is_left(lanes) || b.is_left(lanes);
+        }
+
+        let right_a = vec2(travel_a.y, -travel_a.x);
+        from_b.dot(right_a) < 0.0
+    }
+
     pub fn turns(&self) -> &Vec<Turn> {
         &self.turns
     }
+
+    pub(crate) fn turns_mut(&mut self) -> &mut Vec<Turn> {
+        &mut self.turns
+    }
 }
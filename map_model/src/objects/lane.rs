@@ -30,7 +30,8 @@ impl LaneKind {
 
     pub fn width(self) -> f32 {
         match self {
-            LaneKind::Driving | LaneKind::Biking | LaneKind::Bus => 8.0,
+            LaneKind::Driving | LaneKind::Bus => 8.0,
+            LaneKind::Biking => 4.0,
             LaneKind::Parking => 4.0,
             LaneKind::Construction => 4.0,
             LaneKind::Walking => 4.0,
@@ -89,6 +90,7 @@ pub struct LanePatternBuilder {
     pub n_lanes: u32,
     pub sidewalks: bool,
     pub parking: bool,
+    pub bike_lanes: bool,
     pub one_way: bool,
 }
 
@@ -98,6 +100,7 @@ impl Default for LanePatternBuilder {
             n_lanes: 1,
             sidewalks: true,
             parking: true,
+            bike_lanes: false,
             one_way: false,
         }
     }
@@ -124,6 +127,11 @@ impl LanePatternBuilder {
         self
     }
 
+    pub fn bike_lanes(mut self, bike_lanes: bool) -> Self {
+        self.bike_lanes = bike_lanes;
+        self
+    }
+
     pub fn one_way(mut self, one_way: bool) -> Self {
         self.one_way = one_way;
         self
@@ -137,6 +145,9 @@ impl LanePatternBuilder {
         if self.parking {
             w += LaneKind::Parking.width() * 2.0;
         }
+        if self.bike_lanes {
+            w += LaneKind::Biking.width() * 2.0;
+        }
         w += self.n_lanes as f32 * 2.0 * LaneKind::Driving.width();
         w + 0.5
     }
@@ -150,6 +161,13 @@ impl LanePatternBuilder {
 
         let mut forward: Vec<_> = (0..self.n_lanes).map(|_| LaneKind::Driving).collect();
 
+        if self.bike_lanes {
+            if !self.one_way {
+                backward.push(LaneKind::Biking);
+            }
+            forward.push(LaneKind::Biking);
+        }
+
         if self.parking {
             if !self.one_way {
                 backward.push(LaneKind::Parking);
@@ -1,7 +1,8 @@
-use crate::{IntersectionID, LaneID, Lanes};
+use crate::{IntersectionID, LaneID, Lanes, TrafficBehavior, TrafficControl, TrafficLightSchedule};
 use geom::PolyLine;
+use geom::Segment;
 use geom::Spline;
-use geom::Vec2;
+use geom::{vec2, Vec2};
 use serde::{Deserialize, Serialize};
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Hash)]
@@ -36,11 +37,52 @@ impl TurnKind {
     }
 }
 
+/// The signal of a turn, set by the light policy of its intersection
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum TurnControl {
+    /// Follows the control of the lane it comes from
+    Lane,
+    /// Goes on the green of the lane, yielding to the oncoming traffic
+    Permissive,
+    /// Its own light, for protected left arrows and pedestrian phases on crosswalks
+    Light(TrafficLightSchedule),
+}
+
+#[allow(clippy::derivable_impls)]
+impl Default for TurnControl {
+    fn default() -> Self {
+        TurnControl::Lane
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Turn {
     pub id: TurnID,
     pub points: PolyLine,
     pub kind: TurnKind,
+    /// Not saved, set by the light policy when the map is loaded
+    #[serde(skip)]
+    pub control: TurnControl,
+    /// The driving turns of the intersection this one conflicts with, see [Turn::conflicts].
+    /// Not saved, computed again when the map is loaded.
+    #[serde(skip)]
+    pub conflicting: Vec<TurnID>,
+}
+
+/// A turn as saved before it had a control
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TurnV0 {
+    pub(crate) id: TurnID,
+    pub(crate) points: PolyLine,
+    pub(crate) kind: TurnKind,
+}
+
+impl From<TurnV0> for Turn {
+    fn from(old: TurnV0) -> Self {
+        let mut turn = Turn::new(old.id, old.kind);
+        turn.points = old.points;
+        turn
+    }
 }
 
 const TURN_ANG_ADD: f32 = 0.29;
@@ -48,15 +90,64 @@ const TURN_ANG_MUL: f32 = 0.36;
 const TURN_MUL: f32 = 0.46;
 const N_SPLINE: usize = 6;
 
+/// Turns going this much towards the left of the incoming lane cross the oncoming traffic
+const LEFT_TURN_DOT: f32 = 0.3;
+
 impl Turn {
     pub fn new(id: TurnID, kind: TurnKind) -> Self {
         Self {
             id,
             points: PolyLine::new(vec![Vec2::ZERO; N_SPLINE + 2]),
             kind,
+            control: TurnControl::Lane,
+            conflicting: vec![],
         }
     }
 
+    /// Whether the turn crosses the path of the oncoming traffic, the turns forbidden when
+    /// left turns are disabled in the [TurnPolicy](crate::TurnPolicy)
+    pub fn is_left(&self, lanes: &Lanes) -> bool {
+        let incoming_dir = lanes[self.id.src].orientation_from(self.id.parent);
+        let outgoing_dir = lanes[self.id.dst].orientation_from(self.id.parent);
+        let incoming_right = vec2(incoming_dir.y, -incoming_dir.x);
+        matches!(self.kind, TurnKind::Driving) && incoming_right.dot(outgoing_dir) > LEFT_TURN_DOT
+    }
+
+    pub fn behavior(&self, lanes: &Lanes, seconds: u32) -> TrafficBehavior {
+        match self.control {
+            TurnControl::Lane | TurnControl::Permissive => lanes
+                .get(self.id.src)
+                .map_or(TrafficBehavior::GREEN, |l| l.control.get_behavior(seconds)),
+            TurnControl::Light(schedule) => TrafficControl::Light(schedule).get_behavior(seconds),
+        }
+    }
+
+    pub fn yields(&self) -> bool {
+        matches!(self.control, TurnControl::Permissive)
+    }
+
+    /// Whether vehicles on the two turns can collide, because they merge into the same lane or
+    /// their paths cross
+    pub fn conflicts(&self, other: &Turn) -> bool {
+        if self.id.src == other.id.src {
+            return false;
+        }
+        if self.id.dst == other.id.dst {
+            return true;
+        }
+        let segments = |t: &Turn| {
+            t.points
+                .as_slice()
+                .windows(2)
+                .map(|w| Segment::new(w[0], w[1]))
+                .collect::<Vec<_>>()
+        };
+        let others = segments(other);
+        segments(self)
+            .iter()
+            .any(|a| others.iter().any(|b| a.intersection_point(b).is_some()))
+    }
+
     pub fn make_points(&mut self, lanes: &Lanes) {
         let src_lane = &lanes[self.id.src];
         let dst_lane = &lanes[self.id.dst];
@@ -1,5 +1,5 @@
 #![allow(clippy::or_fun_call)]
-use crate::{LaneID, LaneKind, Map, Traversable, TraverseDirection, TraverseKind, TurnID};
+use crate::{Lane, LaneID, LaneKind, Map, Traversable, TraverseDirection, TraverseKind, TurnID};
 use geom::{PolyLine, Vec2};
 use ordered_float::OrderedFloat;
 use slotmap::Key;
@@ -8,6 +8,25 @@ pub trait Pathfinder {
     fn path(&self, map: &Map, start: Traversable, end: LaneID) -> Option<Vec<Traversable>>;
     fn nearest_lane(&self, map: &Map, pos: Vec2) -> Option<LaneID>;
     fn local_route(&self, map: &Map, lane: LaneID, start: Vec2, end: Vec2) -> Option<PolyLine>;
+
+    /// Length of the path between the lanes closest to the positions, counting the whole
+    /// lanes at both ends. Only meant as an estimate to compare routes.
+    fn path_length(&self, map: &Map, start: Vec2, end: Vec2) -> Option<f32> {
+        let start_lane = self.nearest_lane(map, start)?;
+        let end_lane = self.nearest_lane(map, end)?;
+        if start_lane == end_lane {
+            return Some(start.distance(end));
+        }
+
+        let start = Traversable::new(TraverseKind::Lane(start_lane), TraverseDirection::Forward);
+        let path = self.path(map, start, end_lane)?;
+        Some(
+            path.iter()
+                .filter_map(|t| t.raw_points(map))
+                .map(|p| p.length())
+                .sum(),
+        )
+    }
 }
 
 pub struct PedestrianPath;
@@ -80,82 +99,129 @@ pub struct CarPath;
 
 impl Pathfinder for CarPath {
     fn path(&self, map: &Map, start: Traversable, end: LaneID) -> Option<Vec<Traversable>> {
-        let inters = &map.intersections;
-        let lanes = &map.lanes;
-
-        let start_lane = start.destination_lane();
-
-        let end_pos = inters[lanes[end].dst].pos;
-
-        let dummy = LaneID::null();
-
-        let heuristic = |&p: &LaneID| {
-            let pos = inters[lanes[p].dst].pos;
-            OrderedFloat(pos.distance(end_pos) * 1.2) // Inexact but (much) faster
-        };
+        vehicle_path(map, start, end, |l| match l.kind {
+            LaneKind::Biking => None,
+            _ => Some(l.length),
+        })
+    }
 
-        let successors = |&p: &LaneID| {
-            let l;
-            let p = if p == dummy {
-                l = &lanes[start_lane];
-                start_lane
-            } else {
-                l = &lanes[p];
-                p
-            };
-            let inter = &inters[l.dst];
-            inter
-                .turns_from(p)
-                .map(|(x, _)| (x.dst, OrderedFloat(lanes[x.dst].length)))
-        };
+    fn nearest_lane(&self, map: &Map, pos: Vec2) -> Option<LaneID> {
+        map.nearest_lane(pos, LaneKind::Driving)
+    }
 
-        let (v, _) =
-            pathfinding::directed::astar::astar(&dummy, successors, heuristic, |p| *p == end)?;
+    fn local_route(&self, map: &Map, lane: LaneID, start: Vec2, end: Vec2) -> Option<PolyLine> {
+        forward_local_route(map, lane, start, end)
+    }
+}
 
-        let mut path = Vec::with_capacity(v.len() * 2);
-        path.push(start);
+/// Bikes ride on bike lanes, and share the road with cars where there are none
+pub struct BikePath;
 
-        let mut last_id = start_lane;
+/// How much longer a driving lane feels than a bike lane to cyclists
+const BIKE_ON_ROAD_PENALTY: f32 = 2.0;
 
-        for lane in v.into_iter().skip(1) {
-            let inter_end = &inters[lanes[lane].src];
-            let id = TurnID::new(inter_end.id, last_id, lane, false);
-            path.push(Traversable::new(
-                TraverseKind::Turn(id),
-                TraverseDirection::Forward,
-            ));
-            path.push(Traversable::new(
-                TraverseKind::Lane(lane),
-                TraverseDirection::Forward,
-            ));
+/// Bikes start on a bike lane if there is one this close to where they are
+const BIKE_LANE_REACH: f32 = 30.0;
 
-            last_id = lane;
-        }
-        Some(path)
+impl Pathfinder for BikePath {
+    fn path(&self, map: &Map, start: Traversable, end: LaneID) -> Option<Vec<Traversable>> {
+        vehicle_path(map, start, end, |l| match l.kind {
+            LaneKind::Biking => Some(l.length),
+            LaneKind::Driving => Some(l.length * BIKE_ON_ROAD_PENALTY),
+            _ => None,
+        })
     }
 
     fn nearest_lane(&self, map: &Map, pos: Vec2) -> Option<LaneID> {
-        map.nearest_lane(pos, LaneKind::Driving)
+        map.nearest_lane(pos, LaneKind::Biking)
+            .filter(|&l| map.lanes[l].dist2_to(pos) < BIKE_LANE_REACH * BIKE_LANE_REACH)
+            .or_else(|| map.nearest_lane(pos, LaneKind::Driving))
     }
 
     fn local_route(&self, map: &Map, lane: LaneID, start: Vec2, end: Vec2) -> Option<PolyLine> {
-        let lane = &map.lanes[lane];
-        let (p_start, seg_start) = lane.points.project_segment(start);
-        let (p_end, seg_end) = lane.points.project_segment(end);
+        forward_local_route(map, lane, start, end)
+    }
+}
 
-        if seg_end < seg_start
-            || (seg_end == seg_start
-                && lane.points[seg_end].distance2(p_start) < lane.points[seg_end].distance2(p_end))
-        {
-            return None;
-        }
+/// A* over the lanes a vehicle can use, `cost` gives the cost of going through a lane or None
+/// if it can't be used
+fn vehicle_path(
+    map: &Map,
+    start: Traversable,
+    end: LaneID,
+    cost: impl Fn(&Lane) -> Option<f32>,
+) -> Option<Vec<Traversable>> {
+    let inters = &map.intersections;
+    let lanes = &map.lanes;
+
+    let start_lane = start.destination_lane();
+
+    let end_pos = inters[lanes[end].dst].pos;
+
+    let dummy = LaneID::null();
+
+    let heuristic = |&p: &LaneID| {
+        let pos = inters[lanes[p].dst].pos;
+        OrderedFloat(pos.distance(end_pos) * 1.2) // Inexact but (much) faster
+    };
+
+    let successors = |&p: &LaneID| {
+        let l;
+        let p = if p == dummy {
+            l = &lanes[start_lane];
+            start_lane
+        } else {
+            l = &lanes[p];
+            p
+        };
+        let inter = &inters[l.dst];
+        inter
+            .turns_from(p)
+            .filter_map(|(x, _)| Some((x.dst, OrderedFloat(cost(&lanes[x.dst])?))))
+    };
+
+    let (v, _) = pathfinding::directed::astar::astar(&dummy, successors, heuristic, |p| *p == end)?;
+
+    let mut path = Vec::with_capacity(v.len() * 2);
+    path.push(start);
+
+    let mut last_id = start_lane;
+
+    for lane in v.into_iter().skip(1) {
+        let inter_end = &inters[lanes[lane].src];
+        let id = TurnID::new(inter_end.id, last_id, lane, false);
+        path.push(Traversable::new(
+            TraverseKind::Turn(id),
+            TraverseDirection::Forward,
+        ));
+        path.push(Traversable::new(
+            TraverseKind::Lane(lane),
+            TraverseDirection::Forward,
+        ));
+
+        last_id = lane;
+    }
+    Some(path)
+}
 
-        let segs = &lane.points[seg_start..seg_end];
-        let mut v = Vec::with_capacity(3 + segs.len());
-        v.push(p_start);
-        v.extend_from_slice(segs);
-        v.push(p_end);
-        v.push(end);
-        Some(PolyLine::new(v))
+/// Route along the lane from the projection of start to end, None if end is behind start
+fn forward_local_route(map: &Map, lane: LaneID, start: Vec2, end: Vec2) -> Option<PolyLine> {
+    let lane = &map.lanes[lane];
+    let (p_start, seg_start) = lane.points.project_segment(start);
+    let (p_end, seg_end) = lane.points.project_segment(end);
+
+    if seg_end < seg_start
+        || (seg_end == seg_start
+            && lane.points[seg_end].distance2(p_start) < lane.points[seg_end].distance2(p_end))
+    {
+        return None;
     }
+
+    let segs = &lane.points[seg_start..seg_end];
+    let mut v = Vec::with_capacity(3 + segs.len());
+    v.push(p_start);
+    v.extend_from_slice(segs);
+    v.push(p_end);
+    v.push(end);
+    Some(PolyLine::new(v))
 }
@@ -1,4 +1,6 @@
-use crate::{IntersectionID, LanePatternBuilder, Map, RoadSegmentKind};
+use crate::{
+    IntersectionID, LanePattern, LanePatternBuilder, Map, RoadID, RoadSegmentKind, Turn, TurnKind,
+};
 use flat_spatial::SparseGrid;
 use geom::{vec2, Vec2};
 use std::collections::HashSet;
@@ -218,6 +220,45 @@ pub fn add_grid(pos: Vec2, m: &mut Map, size: usize, spacing: f32) {
     }
 }
 
+/// A crossing of four straight roads, all going towards the center
+pub struct FourWay {
+    pub center: IntersectionID,
+    /// The roads coming from the south, north, east and west
+    pub roads: [RoadID; 4],
+}
+
+impl FourWay {
+    /// The driving turn from a road to another
+    pub fn turn<'a>(&self, map: &'a Map, from: RoadID, to: RoadID) -> &'a Turn {
+        map.intersections[self.center]
+            .turns()
+            .iter()
+            .find(|t| {
+                matches!(t.kind, TurnKind::Driving)
+                    && map.lanes[t.id.src].parent == from
+                    && map.lanes[t.id.dst].parent == to
+            })
+            .expect("no turn between the roads")
+    }
+}
+
+/// Adds a crossing of a north-south road with the `main` pattern and an east-west road with the
+/// `side` pattern, 200 meters long on each side
+pub fn add_four_way(pos: Vec2, m: &mut Map, main: &LanePattern, side: &LanePattern) -> FourWay {
+    let center = m.add_intersection(pos);
+    let mut roads = [RoadID::default(); 4];
+    for (road, &(dir, pattern)) in roads.iter_mut().zip(&[
+        (vec2(0.0, -200.0), main),
+        (vec2(0.0, 200.0), main),
+        (vec2(200.0, 0.0), side),
+        (vec2(-200.0, 0.0), side),
+    ]) {
+        let other = m.add_intersection(pos + dir);
+        *road = m.connect(other, center, pattern, RoadSegmentKind::Straight);
+    }
+    FourWay { center, roads }
+}
+
 fn print_stats(map: &Map) {
     info!("{} intersections", map.intersections.len());
     info!("{} roads", map.roads.len());
@@ -3,7 +3,7 @@ use crate::{Map, RoadID};
 use flat_spatial::SparseGrid;
 use geom::{vec2, Vec2, AABB};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::BTreeSet;
 
 const CELL_SIZE: i32 = 100;
 
@@ -17,7 +17,7 @@ pub struct Tree {
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Trees {
     pub grid: SparseGrid<Tree>,
-    pub generated: HashSet<(i32, i32)>,
+    pub generated: BTreeSet<(i32, i32)>,
     pub dirty: bool,
 }
 
@@ -1,5 +1,8 @@
 use crate::procgen::Trees;
-use crate::{Buildings, Intersections, Lanes, Lots, Map, ParkingSpots, Roads, SpatialMap};
+use crate::{
+    Buildings, Intersection, IntersectionV0, Intersections, Lanes, Lots, Map, ParkingSpots, Roads,
+    SpatialMap,
+};
 use geom::Shape;
 use serde::{Deserialize, Serialize};
 
@@ -36,6 +39,8 @@ impl From<SerializedMap> for Map {
     fn from(mut sel: SerializedMap) -> Self {
         for inter in sel.intersections.values_mut() {
             inter.update_polygon(&sel.roads);
+            inter.update_conflicts();
+            inter.restore_traffic_control(&mut sel.lanes, &sel.roads);
         }
 
         let spatial_map = mk_spatial_map(&sel);
@@ -53,6 +58,66 @@ impl From<SerializedMap> for Map {
     }
 }
 
+/// A slot of a saved slotmap, so that its values can be migrated without changing their keys
+#[derive(Serialize, Deserialize)]
+struct SavedSlot<T> {
+    value: Option<T>,
+    version: u32,
+}
+
+impl<T> SavedSlot<T> {
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> SavedSlot<U> {
+        SavedSlot {
+            value: self.value.map(f),
+            version: self.version,
+        }
+    }
+}
+
+/// The map as saved before the intersections had actuated lights, green waves and reservations
+#[derive(Serialize, Deserialize)]
+pub struct SerializedMapV0 {
+    roads: Roads,
+    intersections: Vec<SavedSlot<IntersectionV0>>,
+    buildings: Buildings,
+    lanes: Lanes,
+    parking: ParkingSpots,
+    lots: Lots,
+    trees: Trees,
+}
+
+/// Same layout as [SerializedMap]
+#[derive(Serialize)]
+struct SerializedMapV1 {
+    roads: Roads,
+    intersections: Vec<SavedSlot<Intersection>>,
+    buildings: Buildings,
+    lanes: Lanes,
+    parking: ParkingSpots,
+    lots: Lots,
+    trees: Trees,
+}
+
+impl SerializedMapV0 {
+    /// The map to save again to read it as a [SerializedMap]. The intersections get their
+    /// traffic control back from their light policy on load.
+    pub fn migrate(self) -> impl Serialize {
+        SerializedMapV1 {
+            roads: self.roads,
+            intersections: self
+                .intersections
+                .into_iter()
+                .map(|slot| slot.map(Intersection::from))
+                .collect(),
+            buildings: self.buildings,
+            lanes: self.lanes,
+            parking: self.parking,
+            lots: self.lots,
+            trees: self.trees,
+        }
+    }
+}
+
 fn mk_spatial_map(m: &SerializedMap) -> SpatialMap {
     let mut sm = SpatialMap::default();
     for h in m.buildings.values() {
@@ -69,3 +134,96 @@ fn mk_spatial_map(m: &SerializedMap) -> SpatialMap {
     }
     sm
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{SavedSlot, SerializedMap, SerializedMapV0};
+    use crate::procgen::add_four_way;
+    use crate::{
+        Intersection, IntersectionID, IntersectionV0, LanePatternBuilder, LightPolicy, Map, TurnV0,
+    };
+    use geom::Vec2;
+
+    fn to_v0(i: Intersection) -> IntersectionV0 {
+        IntersectionV0 {
+            id: i.id,
+            pos: i.pos,
+            turns: i
+                .turns()
+                .iter()
+                .map(|t| TurnV0 {
+                    id: t.id,
+                    points: t.points.clone(),
+                    kind: t.kind,
+                })
+                .collect(),
+            roads: i.roads,
+            turn_policy: i.turn_policy,
+            light_policy: i.light_policy,
+            polygon: i.polygon,
+        }
+    }
+
+    fn four_way(policy: LightPolicy) -> (Map, IntersectionID) {
+        let mut map = Map::empty();
+        let pattern = LanePatternBuilder::new().build();
+        let center = add_four_way(Vec2::ZERO, &mut map, &pattern, &pattern).center;
+        map.update_intersection(center, |i| i.light_policy = policy);
+        (map, center)
+    }
+
+    fn assert_same_turns(a: &Map, b: &Map, inter: IntersectionID) {
+        let (ta, tb) = (
+            a.intersections()[inter].turns(),
+            b.intersections()[inter].turns(),
+        );
+        assert_eq!(ta.len(), tb.len());
+        for (a, b) in ta.iter().zip(tb) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.conflicting, b.conflicting);
+            assert_eq!(format!("{:?}", a.control), format!("{:?}", b.control));
+        }
+        for (id, lane) in a.lanes() {
+            let control = b.lanes()[id].control;
+            assert_eq!(format!("{:?}", control), format!("{:?}", lane.control));
+        }
+    }
+
+    #[test]
+    fn test_turns_on_load() {
+        let (map, center) = four_way(LightPolicy::ProtectedLefts);
+        let turns = map.intersections()[center].turns();
+        assert!(turns.iter().any(|t| !t.conflicting.is_empty()));
+        let data = bincode::serialize(&SerializedMap::from(&map)).unwrap();
+        let loaded = Map::from(bincode::deserialize::<SerializedMap>(&data).unwrap());
+        assert_same_turns(&map, &loaded, center);
+    }
+
+    #[test]
+    fn test_migrate_v0() {
+        let (map, center) = four_way(LightPolicy::Lights);
+
+        let saved = SerializedMap::from(&map);
+        let slots: Vec<SavedSlot<Intersection>> =
+            bincode::deserialize(&bincode::serialize(&saved.intersections).unwrap()).unwrap();
+        let old = SerializedMapV0 {
+            roads: saved.roads,
+            intersections: slots.into_iter().map(|s| s.map(to_v0)).collect(),
+            buildings: saved.buildings,
+            lanes: saved.lanes,
+            parking: saved.parking,
+            lots: saved.lots,
+            trees: saved.trees,
+        };
+
+        let old: SerializedMapV0 =
+            bincode::deserialize(&bincode::serialize(&old).unwrap()).unwrap();
+        let data = bincode::serialize(&old.migrate()).unwrap();
+        let loaded = Map::from(bincode::deserialize::<SerializedMap>(&data).unwrap());
+
+        let inter = &loaded.intersections()[center];
+        assert!(inter.actuated.is_none());
+        assert!(!inter.reservations);
+        assert_same_turns(&map, &loaded, center);
+    }
+}
@@ -1,6 +1,7 @@
+use crate::{LaneID, Lanes, RoadID};
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Serialize, Deserialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum TrafficBehavior {
     RED,
     ORANGE,
@@ -40,6 +41,8 @@ pub enum TrafficControl {
     Always,
     Light(TrafficLightSchedule),
     StopSign,
+    /// Light set by the actuated controller of the intersection, see [ActuatedLights]
+    Actuated(TrafficBehavior),
 }
 
 impl TrafficControl {
@@ -52,7 +55,7 @@ impl TrafficControl {
     }
 
     pub fn is_light(&self) -> bool {
-        matches!(self, TrafficControl::Light(_))
+        matches!(self, TrafficControl::Light(_) | TrafficControl::Actuated(_))
     }
 
     pub fn get_behavior(&self, seconds: u32) -> TrafficBehavior {
@@ -68,7 +71,139 @@ impl TrafficControl {
                     TrafficBehavior::RED
                 }
             }
+            TrafficControl::Actuated(behavior) => *behavior,
             TrafficControl::StopSign => TrafficBehavior::STOP,
         }
     }
 }
+
+/// When the lights of an intersection turn green for the vehicles coming from a road, so that
+/// platoons driving along a corridor get consecutive greens
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct GreenWave {
+    pub road: RoadID,
+    /// In seconds, modulo the light cycle
+    pub start: u32,
+}
+
+/// A phase stays green at least this long, in seconds
+const MIN_GREEN: u32 = 6;
+/// A phase stays green at most this long when vehicles are waiting elsewhere, in seconds
+const MAX_GREEN: u32 = 30;
+const ORANGE_TIME: u32 = 4;
+
+/// Lights giving green to the roads with vehicles queued instead of following a fixed cycle.
+/// A phase is extended while its lanes have vehicles waiting, and phases without any are skipped.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActuatedLights {
+    /// Incoming lanes getting green together
+    phases: Vec<Vec<LaneID>>,
+    phase: usize,
+    /// When the current phase or its orange started, in seconds
+    since: u32,
+    orange: bool,
+}
+
+impl ActuatedLights {
+    pub fn new(phases: Vec<Vec<LaneID>>) -> Self {
+        Self {
+            phases,
+            phase: 0,
+            since: 0,
+            orange: false,
+        }
+    }
+
+    /// Changes phase depending on the vehicles queued on each lane, returns true if the
+    /// lights changed
+    pub fn update(&mut self, seconds: u32, queue: impl Fn(LaneID) -> u32) -> bool {
+        let n = self.phases.len();
+        if n == 0 {
+            return false;
+        }
+        let demand = |phase: usize| -> u32 { self.phases[phase].iter().map(|&l| queue(l)).sum() };
+        let elapsed = seconds.saturating_sub(self.since);
+
+        if self.orange {
+            if elapsed < ORANGE_TIME {
+                return false;
+            }
+            self.phase = (1..=n)
+                .map(|k| (self.phase + k) % n)
+                .find(|&p| demand(p) > 0)
+                .unwrap_or((self.phase + 1) % n);
+            self.orange = false;
+            self.since = seconds;
+            return true;
+        }
+
+        if elapsed < MIN_GREEN {
+            return false;
+        }
+        let waiting_elsewhere = (0..n).any(|p| p != self.phase && demand(p) > 0);
+        if !waiting_elsewhere {
+            return false;
+        }
+        if demand(self.phase) > 0 && elapsed < MAX_GREEN {
+            return false;
+        }
+        self.orange = true;
+        self.since = seconds;
+        true
+    }
+
+    pub fn behavior(&self, phase: usize) -> TrafficBehavior {
+        if phase != self.phase {
+            TrafficBehavior::RED
+        } else if self.orange {
+            TrafficBehavior::ORANGE
+        } else {
+            TrafficBehavior::GREEN
+        }
+    }
+
+    pub fn apply(&self, lanes: &mut Lanes) {
+        for (i, phase) in self.phases.iter().enumerate() {
+            let control = TrafficControl::Actuated(self.behavior(i));
+            for &lane in phase {
+                if let Some(l) = lanes.get_mut(lane) {
+                    l.control = control;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ActuatedLights, TrafficBehavior, MAX_GREEN, MIN_GREEN, ORANGE_TIME};
+    use crate::LaneID;
+    use slotmap::SlotMap;
+
+    #[test]
+    fn test_actuated() {
+        let mut ids = SlotMap::<LaneID, ()>::with_key();
+        let (a, b, c) = (ids.insert(()), ids.insert(()), ids.insert(()));
+        let mut lights = ActuatedLights::new(vec![vec![a], vec![b], vec![c]]);
+
+        // Nobody waiting elsewhere, green stays
+        assert!(!lights.update(100, |_| 0));
+        assert_eq!(lights.behavior(0), TrafficBehavior::GREEN);
+
+        // Only c has vehicles, b is skipped
+        let queue_c = |l| if l == c { 3 } else { 0 };
+        assert!(lights.update(100, queue_c));
+        assert_eq!(lights.behavior(0), TrafficBehavior::ORANGE);
+        assert!(lights.update(100 + ORANGE_TIME, queue_c));
+        assert_eq!(lights.behavior(2), TrafficBehavior::GREEN);
+        assert_eq!(lights.behavior(1), TrafficBehavior::RED);
+
+        // Extended while c has vehicles, until the max when a is waiting too
+        let queue_ac = |l| if l == a || l == c { 2 } else { 0 };
+        let start = 100 + ORANGE_TIME;
+        assert!(!lights.update(start + MIN_GREEN, queue_ac));
+        assert!(lights.update(start + MAX_GREEN, queue_ac));
+        assert!(lights.update(start + MAX_GREEN + ORANGE_TIME, queue_ac));
+        assert_eq!(lights.behavior(0), TrafficBehavior::GREEN);
+    }
+}
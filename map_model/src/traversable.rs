@@ -1,4 +1,4 @@
-use crate::{IntersectionID, LaneID, Lanes, Map, TurnID};
+use crate::{IntersectionID, LaneID, Lanes, Map, TrafficBehavior, Turn, TurnControl, TurnID};
 use geom::PolyLine;
 use imgui_inspect::imgui;
 use imgui_inspect_derive::*;
@@ -49,16 +49,34 @@ impl Traversable {
         }
     }
 
-    pub fn can_pass(&self, time: u32, lanes: &Lanes) -> bool {
+    pub fn turn<'a>(&self, m: &'a Map) -> Option<&'a Turn> {
+        match self.kind {
+            TraverseKind::Lane(_) => None,
+            TraverseKind::Turn(id) => m.intersections.get(id.parent)?.find_turn(id),
+        }
+    }
+
+    /// The signal at the end of this traversable when going on to the next one: the light of
+    /// the turn if it has its own, else the control of the lane
+    pub fn signal(&self, next: Option<&Traversable>, time: u32, m: &Map) -> TrafficBehavior {
+        if let Some(turn) = next.and_then(|t| t.turn(m)) {
+            if let TurnControl::Light(_) = turn.control {
+                return turn.behavior(&m.lanes, time);
+            }
+        }
         match self.kind {
             TraverseKind::Lane(id) => {
-                let l = unwrap_or!(lanes.get(id), return true);
-                !l.control.get_behavior(time).is_red()
+                let l = unwrap_or!(m.lanes.get(id), return TrafficBehavior::GREEN);
+                l.control.get_behavior(time)
             }
-            TraverseKind::Turn(_) => true,
+            TraverseKind::Turn(_) => TrafficBehavior::GREEN,
         }
     }
 
+    pub fn can_pass(&self, next: Option<&Traversable>, time: u32, m: &Map) -> bool {
+        !self.signal(next, time, m).is_red()
+    }
+
     pub fn destination_intersection(&self, lanes: &Lanes) -> IntersectionID {
         match self.kind {
             TraverseKind::Lane(p) => match self.dir {
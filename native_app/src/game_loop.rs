@@ -6,13 +6,13 @@ use winit::window::{Fullscreen, Window};
 
 use common::{GameTime, History};
 use egregoria::rendering::immediate::{ImmediateDraw, ImmediateOrder, ImmediateSound, OrderKind};
-use egregoria::souls::add_souls_to_empty_buildings;
+use egregoria::save_slots::{latest_slot, DEFAULT_SLOT};
 use egregoria::{load_from_disk, Egregoria};
 use geom::Camera;
 use geom::{vec3, LinearColor, Vec2};
 use map_model::Map;
 use wgpu_engine::lighting::{LightInstance, LightRender};
-use wgpu_engine::{FrameContext, GfxContext, GuiRenderContext, SpriteBatch};
+use wgpu_engine::{FrameContext, GuiRenderContext, SpriteBatch};
 
 use crate::audio::GameAudio;
 use crate::context::Context;
@@ -25,6 +25,10 @@ use crate::rendering::{
     BackgroundRender, CameraHandler, InstancedRender, MeshRenderer, RoadRenderer,
 };
 
+/// Length of a tick in in-game seconds when running with a fixed timestep
+const FIXED_TIMESTEP: f64 = 1.0 / 30.0;
+const MAX_STEPS_PER_FRAME: u32 = 8;
+
 pub struct State {
     goria: Egregoria,
 
@@ -32,6 +36,7 @@ pub struct State {
 
     imgui_render: ImguiWrapper,
     last_time: Instant,
+    time_accumulator: f64,
 
     instanced_renderer: InstancedRender,
     road_renderer: RoadRenderer,
@@ -65,7 +70,8 @@ impl State {
 
         goria.insert(UiTextures::new(&ctx.gfx, &mut imgui_render.renderer));
 
-        load_from_disk(&mut goria);
+        let slot = latest_slot().unwrap_or_else(|| DEFAULT_SLOT.to_string());
+        load_from_disk(&mut goria, &slot);
 
         let gui: Gui = common::saveload::load_json("gui").unwrap_or_default();
 
@@ -81,6 +87,7 @@ impl State {
             camera,
             imgui_render,
             last_time: Instant::now(),
+            time_accumulator: 0.0,
             instanced_renderer: InstancedRender::new(&mut ctx.gfx),
             road_renderer: RoadRenderer::new(&mut ctx.gfx),
             bg_renderer: BackgroundRender::new(&mut ctx.gfx),
@@ -102,8 +109,6 @@ impl State {
         let settings = *self.goria.read::<Settings>();
         Self::manage_settings(ctx, &settings);
 
-        self.manage_time(delta, &mut ctx.gfx);
-
         self.manage_io(ctx);
 
         self.camera.movespeed = settings.camera_sensibility / 100.0;
@@ -122,12 +127,28 @@ impl State {
         }
 
         let t = std::time::Instant::now();
-        self.goria.run();
+        if settings.fixed_timestep {
+            self.time_accumulator += delta * settings.time_warp as f64;
+            let mut steps = 0;
+            while self.time_accumulator >= FIXED_TIMESTEP && steps < MAX_STEPS_PER_FRAME {
+                self.goria.tick(FIXED_TIMESTEP);
+                self.time_accumulator -= FIXED_TIMESTEP;
+                steps += 1;
+            }
+            // Don't try to catch up if we're falling behind
+            self.time_accumulator = self.time_accumulator.min(FIXED_TIMESTEP);
+        } else {
+            self.time_accumulator = 0.0;
+            self.goria.tick(Self::game_delta(delta, settings.time_warp));
+        }
         self.goria
             .write::<RenderStats>()
             .world_update
             .add_value(t.elapsed().as_secs_f32());
 
+        ctx.gfx
+            .set_time(self.goria.read::<GameTime>().timestamp as f32);
+
         {
             let immediate = self.goria.read::<ImmediateDraw>();
             for ImmediateOrder { kind, .. } in immediate
@@ -141,8 +162,6 @@ impl State {
             }
         }
 
-        add_souls_to_empty_buildings(&mut self.goria);
-
         for (sound, kind) in self.goria.write::<ImmediateSound>().orders.drain(..) {
             ctx.audio.play(sound, kind);
         }
@@ -320,17 +339,11 @@ impl State {
         ctx.audio.set_settings(settings);
     }
 
-    fn manage_time(&mut self, delta: f64, gfx: &mut GfxContext) {
+    /// Converts the real frame delta to the in-game delta, applying time warp
+    fn game_delta(delta: f64, warp: f32) -> f64 {
         const MAX_TIMESTEP: f64 = 1.0 / 15.0;
 
-        let mut time = self.goria.write::<GameTime>();
-        let warp = self.goria.read::<Settings>().time_warp;
-
-        let delta = (delta * warp as f64).min(MAX_TIMESTEP);
-
-        *time = GameTime::new(delta as f32, time.timestamp + delta);
-
-        gfx.set_time(time.timestamp as f32);
+        (delta * warp as f64).min(MAX_TIMESTEP)
     }
 
     fn manage_entity_follow(&mut self) {
@@ -0,0 +1,57 @@
+use super::Tool;
+use crate::input::{MouseButton, MouseInfo};
+use common::Z_TOOL;
+use egregoria::rendering::immediate::ImmediateDraw;
+use egregoria::transit::{BusLines, BusStop};
+use geom::Color;
+use legion::system;
+use map_model::Map;
+use serde::{Deserialize, Serialize};
+
+/// The stops of the line being drawn, it is created from the toolbox window
+register_resource!(BusLineResource, "bus_line");
+#[derive(Default, Serialize, Deserialize)]
+pub struct BusLineResource {
+    pub stops: Vec<BusStop>,
+}
+
+register_system!(bus_line_tool);
+#[system]
+pub fn bus_line_tool(
+    #[resource] res: &mut BusLineResource,
+    #[resource] lines: &BusLines,
+    #[resource] tool: &Tool,
+    #[resource] mouseinfo: &MouseInfo,
+    #[resource] map: &Map,
+    #[resource] draw: &mut ImmediateDraw,
+) {
+    if !matches!(tool, Tool::BusLine) {
+        return;
+    }
+
+    for (_, line) in lines.iter() {
+        for stop in &line.stops {
+            draw.circle(stop.pos, 2.0).color(Color::YELLOW).z(Z_TOOL);
+        }
+    }
+
+    let hover = BusStop::new(map, mouseinfo.unprojected);
+
+    let mut points: Vec<_> = res.stops.iter().map(|s| s.pos).collect();
+    points.extend(hover.map(|s| s.pos));
+    if points.len() >= 2 {
+        draw.polyline(points, 1.0).color(Color::ORANGE).z(Z_TOOL);
+    }
+    for stop in &res.stops {
+        draw.circle(stop.pos, 3.0).color(Color::ORANGE).z(Z_TOOL);
+    }
+
+    let hover = unwrap_or!(hover, return);
+    draw.circle(hover.pos, 3.0)
+        .color(common::config().special_building_col)
+        .z(Z_TOOL);
+
+    if mouseinfo.just_pressed.contains(&MouseButton::Left) {
+        res.stops.push(hover);
+    }
+}
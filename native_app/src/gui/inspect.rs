@@ -1,12 +1,18 @@
 use crate::gui::follow::FollowEntity;
 use crate::gui::roadeditor::IntersectionComponent;
-use egregoria::map_dynamic::Itinerary;
+use common::GameTime;
+use egregoria::economy::{CommodityKind, Market};
+use egregoria::map_dynamic::{BuildingInfos, Itinerary, Router};
 use egregoria::pedestrians::{Location, Pedestrian};
 use egregoria::physics::{Collider, Kinematics};
 use egregoria::rendering::assets::AssetRender;
 use egregoria::rendering::meshrender_component::MeshRender;
+use egregoria::souls::demographics::Person;
+use egregoria::souls::desire::Desires;
+use egregoria::souls::household::Household;
+use egregoria::souls::state_log::StateLog;
 use egregoria::vehicles::Vehicle;
-use egregoria::Egregoria;
+use egregoria::{Egregoria, SoulID};
 use geom::Transform;
 use imgui::im_str;
 use imgui::Ui;
@@ -46,6 +52,93 @@ impl InspectRenderer {
         .unwrap_or(false)
     }
 
+    /// The members, cars, budget and food stock of the household of the inspected human,
+    /// or of the inspected household itself
+    fn household(&self, goria: &Egregoria, ui: &Ui) {
+        let household = match goria.comp::<Person>(self.entity) {
+            Some(p) => p.household,
+            None => SoulID(self.entity),
+        };
+        let h = unwrap_or!(goria.comp::<Household>(household.0), return);
+        let today = goria.read::<GameTime>().daytime.day;
+        let binfos = goria.read::<BuildingInfos>();
+        let market = goria.read::<Market>();
+
+        ui.text("Household");
+        ui.text(im_str!("budget: {}", market.money(household)));
+        ui.text(im_str!("cars: {}", h.cars.len()));
+        ui.text("members:");
+        for member in binfos.residents(h.house) {
+            let age = goria.comp::<Person>(member.0).map(|p| p.age(today));
+            ui.text(im_str!(
+                "- {:?} (age {})",
+                member.0,
+                age.unwrap_or_default()
+            ));
+        }
+        for &kind in CommodityKind::values() {
+            let stock = market.capital(household, kind);
+            if stock > 0 {
+                ui.text(im_str!("stock: {} {}", stock, kind));
+            }
+        }
+    }
+
+    /// Why the soul does what it does: the scores of its desires, where it is going,
+    /// its orders on the market and its recent state changes
+    fn brain(&self, goria: &Egregoria, ui: &Ui) {
+        let soul = SoulID(self.entity);
+
+        if let Some(desires) = goria.comp::<Desires>(self.entity) {
+            ui.text("Desires");
+            for (name, score) in desires.scores() {
+                let chosen = if desires.chosen() == Some(name) {
+                    " <-"
+                } else {
+                    ""
+                };
+                ui.text(im_str!("- {}: {:.2}{}", name, score, chosen));
+            }
+        }
+
+        if let Some(router) = goria.comp::<Router>(self.entity) {
+            ui.text(im_str!("Going to {:?}", router.dest()));
+            if let Some(plan) = router.plan() {
+                ui.text(im_str!("by {:?}, about {:.0}s", plan.mode, plan.cost));
+            }
+            for step in router.steps() {
+                ui.text(im_str!("- {:?}", step));
+            }
+        }
+
+        let market = goria.read::<Market>();
+        let buy = market.buy_orders(soul);
+        let sell = market.sell_orders(soul);
+        if !buy.is_empty() || !sell.is_empty() {
+            ui.text("Orders");
+        }
+        for (kind, order) in buy {
+            ui.text(im_str!("- buy {} {} at {}", order.qty, kind, order.price));
+        }
+        for (kind, order) in sell {
+            ui.text(im_str!("- sell {} {} at {}", order.qty, kind, order.price));
+        }
+        drop(market);
+
+        if let Some(log) = goria.comp::<StateLog>(self.entity) {
+            ui.text("Recent changes");
+            for (t, change) in log.entries().rev() {
+                ui.text(im_str!(
+                    "- day {} {:02}:{:02} {}",
+                    t.day,
+                    t.hour,
+                    t.second,
+                    change
+                ));
+            }
+        }
+    }
+
     pub fn render(&self, goria: &mut Egregoria, ui: &Ui) -> bool {
         let mut dirty = false;
 
@@ -60,6 +153,9 @@ impl InspectRenderer {
         dirty |= self.inspect_component::<IntersectionComponent>(goria, ui);
         dirty |= self.inspect_component::<Itinerary>(goria, ui);
 
+        self.brain(goria, ui);
+        self.household(goria, ui);
+
         {
             let follow = &mut goria.write::<FollowEntity>().0;
             if follow.is_none() {
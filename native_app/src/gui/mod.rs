@@ -12,6 +12,7 @@ pub use topgui::*;
 use wgpu_engine::GfxContext;
 
 mod bulldozer;
+mod buslines;
 mod follow;
 mod inspect;
 mod inspected_aura;
@@ -30,6 +31,10 @@ pub struct InspectedEntity {
     pub e: Option<Entity>,
     pub dirty: bool, // Modified by inspection
     pub dist2: f32,
+    /// The entity keeping a [StateLog] for the inspector
+    ///
+    /// [StateLog]: egregoria::souls::state_log::StateLog
+    pub logged: Option<Entity>,
 }
 
 register_system!(hand_reset);
@@ -50,6 +55,7 @@ pub enum Tool {
     Bulldozer,
     LotBrush,
     SpecialBuilding,
+    BusLine,
 }
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
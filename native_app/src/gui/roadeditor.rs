@@ -2,7 +2,8 @@ use crate::gui::{InspectedEntity, Tool};
 use crate::input::{MouseButton, MouseInfo};
 use common::Z_TOOL;
 use egregoria::rendering::immediate::ImmediateDraw;
-use geom::Color;
+use egregoria::vehicles::VehicleKind;
+use geom::{Color, Vec2};
 use imgui_inspect_derive::*;
 use legion::systems::CommandBuffer;
 use legion::world::SubWorld;
@@ -17,6 +18,7 @@ pub struct IntersectionComponent {
     pub id: IntersectionID,
     pub turn_policy: TurnPolicy,
     pub light_policy: LightPolicy,
+    pub reservations: bool,
 }
 
 register_resource_noserialize!(RoadEditorResource);
@@ -55,6 +57,21 @@ pub fn roadeditor(
         .color(Color::BLUE)
         .z(Z_TOOL);
 
+    // Hovering a road shows the corridor its lights would be coordinated along, clicking it
+    // sets up a green wave for cars driving from the start of the road
+    if let ProjectKind::Road(road) = cur_proj.kind {
+        let corridor = map.corridor(road);
+        let points: Vec<Vec2> = corridor
+            .iter()
+            .map(|&id| map.intersections()[id].pos)
+            .collect();
+        imm_draw.polyline(points, 3.0).color(Color::GREEN).z(Z_TOOL);
+
+        if mouseinfo.just_pressed.contains(&MouseButton::Left) {
+            map.green_wave(&corridor, VehicleKind::Car.cruising_speed());
+        }
+    }
+
     if mouseinfo.just_pressed.contains(&MouseButton::Left) {
         if let ProjectKind::Inter(id) = cur_proj.kind {
             let inter = &map.intersections()[id];
@@ -62,6 +79,7 @@ pub fn roadeditor(
                 id,
                 turn_policy: inter.turn_policy,
                 light_policy: inter.light_policy,
+                reservations: inter.reservations,
             },)));
             inspected.e = state.inspect_e;
         }
@@ -73,6 +91,7 @@ pub fn roadeditor(
             map.update_intersection(selected_interc.id, |inter| {
                 inter.turn_policy = selected_interc.turn_policy;
                 inter.light_policy = selected_interc.light_policy;
+                inter.reservations = selected_interc.reservations;
             });
         }
     }
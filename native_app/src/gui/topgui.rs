@@ -1,3 +1,4 @@
+use crate::gui::buslines::BusLineResource;
 use crate::gui::lotbrush::LotBrushResource;
 use crate::gui::specialbuilding::SpecialBuildingResource;
 use crate::gui::windows::settings::Settings;
@@ -5,6 +6,10 @@ use crate::gui::windows::ImguiWindows;
 use crate::gui::{InspectedEntity, RoadBuildResource, Tool, UiTex, UiTextures};
 use crate::input::{KeyCode, KeyboardInfo};
 use common::GameTime;
+use egregoria::background_save::{save_to_disk_background, SaveProgress};
+use egregoria::save_slots::{next_autosave_slot, DEFAULT_SLOT};
+use egregoria::souls::state_log::StateLog;
+use egregoria::transit::{add_bus_line, remove_bus_line, BusLines};
 use egregoria::Egregoria;
 use imgui::{im_str, StyleColor, StyleVar, Ui, Window};
 use imgui_inspect::{InspectArgsStruct, InspectRenderStruct};
@@ -24,8 +29,13 @@ pub struct Gui {
     pub n_cars: i32,
     pub n_pedestrians: i32,
     pub depause_warp: f32,
+    /// Slot written to by the save button
+    pub slot: String,
 }
 
+/// Number of slots autosaves rotate through
+const AUTOSAVE_SLOTS: u32 = 3;
+
 impl Default for Gui {
     fn default() -> Self {
         Self {
@@ -35,6 +45,7 @@ impl Default for Gui {
             n_cars: 100,
             n_pedestrians: 100,
             depause_warp: 1.0,
+            slot: DEFAULT_SLOT.to_string(),
         }
     }
 }
@@ -65,7 +76,7 @@ impl Gui {
         let every = goria.read::<Settings>().auto_save_every.into();
         if let Some(every) = every {
             if self.last_save.elapsed() > every {
-                egregoria::save_to_disk(goria);
+                save_to_disk_background(goria, &next_autosave_slot(AUTOSAVE_SLOTS));
                 self.last_save = Instant::now();
             }
         }
@@ -127,6 +138,17 @@ impl Gui {
                     }
                     tok.pop(ui);
                 }
+
+                let tok =
+                    ui.push_style_var(StyleVar::Alpha(if matches!(cur_tool, Tool::BusLine) {
+                        1.0
+                    } else {
+                        0.6
+                    }));
+                if ui.button(im_str!("Bus lines"), [toolbox_w, 30.0]) {
+                    *cur_tool = Tool::BusLine;
+                }
+                tok.pop(ui);
             });
         if matches!(
             *goria.read::<Tool>(),
@@ -160,6 +182,7 @@ impl Gui {
                     if pattern.n_lanes == 0 {
                         pattern.sidewalks = true;
                         pattern.parking = false;
+                        pattern.bike_lanes = false;
                     }
 
                     goria.write::<RoadBuildResource>().pattern_builder = pattern;
@@ -206,8 +229,56 @@ impl Gui {
                 });
         }
 
+        if matches!(*goria.read::<Tool>(), Tool::BusLine) {
+            let blw = 150.0;
+            Window::new(im_str!("Bus Lines"))
+                .size_constraints([blw, 0.0], [blw, 1000.0])
+                .position(
+                    [w - toolbox_w - blw, h * 0.5 - 30.0],
+                    imgui::Condition::Always,
+                )
+                .title_bar(true)
+                .movable(false)
+                .collapsible(false)
+                .resizable(false)
+                .always_auto_resize(true)
+                .build(ui, || {
+                    let n_stops = goria.read::<BusLineResource>().stops.len();
+                    ui.text(im_str!("{} stops placed", n_stops));
+                    if n_stops >= 2 && ui.button(im_str!("Create line"), [blw, 25.0]) {
+                        let points: Vec<_> = goria
+                            .write::<BusLineResource>()
+                            .stops
+                            .drain(..)
+                            .map(|s| s.pos)
+                            .collect();
+                        add_bus_line(goria, &points);
+                    }
+                    if n_stops > 0 && ui.button(im_str!("Cancel"), [blw, 25.0]) {
+                        goria.write::<BusLineResource>().stops.clear();
+                    }
+
+                    let lines: Vec<_> = goria
+                        .read::<BusLines>()
+                        .iter()
+                        .map(|(id, line)| (id, line.stops.len(), line.buses.len()))
+                        .collect();
+                    for (i, (id, n_stops, n_buses)) in lines.into_iter().enumerate() {
+                        ui.text(im_str!(
+                            "Line {}: {} stops, {} buses",
+                            i + 1,
+                            n_stops,
+                            n_buses
+                        ));
+                        if ui.small_button(&im_str!("Remove line {}", i + 1)) {
+                            remove_bus_line(goria, id);
+                        }
+                    }
+                });
+        }
+
         let building_select_w = 160.0;
-        let gbuildings = egregoria::souls::goods_company::GOODS_BUILDINGS;
+        let gbuildings = &egregoria::economy::economy_data().companies;
 
         if matches!(*goria.read::<Tool>(), Tool::SpecialBuilding) {
             Window::new(im_str!("Buildings"))
@@ -225,8 +296,7 @@ impl Gui {
 
                     if cur_build.opt.is_none() {
                         let d = &gbuildings[0];
-                        cur_build.opt =
-                            Some((d.bkind, d.bgen, d.size, d.asset_location.to_string()))
+                        cur_build.opt = Some((d.bkind, d.bgen, d.size, d.asset_location.clone()))
                     }
 
                     let cur_kind = cur_build.opt.as_ref().unwrap().0;
@@ -248,7 +318,7 @@ impl Gui {
                                 descr.bkind,
                                 descr.bgen,
                                 descr.size,
-                                descr.asset_location.to_string(),
+                                descr.asset_location.clone(),
                             ));
                         }
                         tok.pop(ui);
@@ -281,18 +351,25 @@ impl Gui {
                                 ui.new_line();
                                 if !descr.recipe.consumption.is_empty() {
                                     ui.text("consumption:");
-                                    for (kind, n) in descr.recipe.consumption {
+                                    for (kind, n) in &descr.recipe.consumption {
                                         ui.text(im_str!("- {} x{}", kind, n));
                                     }
                                     ui.new_line();
                                 }
                                 if !descr.recipe.production.is_empty() {
                                     ui.text("production:");
-                                    for (kind, n) in descr.recipe.production {
+                                    for (kind, n) in &descr.recipe.production {
                                         ui.text(im_str!("- {} x{}", kind, n));
                                     }
                                     ui.new_line();
                                 }
+                                if !descr.shelf.is_empty() {
+                                    ui.text("sells on site:");
+                                    for (kind, n) in &descr.shelf {
+                                        ui.text(im_str!("- {} (stock {})", kind, n));
+                                    }
+                                    ui.new_line();
+                                }
                                 ui.text(im_str!("time: {}s", descr.recipe.complexity));
                                 ui.text(im_str!(
                                     "storage multiplier: {}",
@@ -310,6 +387,16 @@ impl Gui {
         let mut inspected = *goria.read::<InspectedEntity>();
         let e = unwrap_or!(inspected.e, return);
 
+        if inspected.logged != Some(e) {
+            if let Some(old) = inspected.logged.take() {
+                if let Some(mut entry) = goria.world.entry(old) {
+                    entry.remove_component::<StateLog>();
+                }
+            }
+            goria.add_comp(e, StateLog::default());
+            inspected.logged = Some(e);
+        }
+
         let mut is_open = true;
         Window::new(im_str!("Inspect"))
             .size([300.0, 300.0], imgui::Condition::FirstUseEver)
@@ -322,6 +409,10 @@ impl Gui {
         if !is_open {
             inspected.e = None;
             inspected.dirty = false;
+            if let Some(mut entry) = goria.world.entry(e) {
+                entry.remove_component::<StateLog>();
+            }
+            inspected.logged = None;
         }
         *goria.write::<InspectedEntity>() = inspected;
     }
@@ -359,7 +450,7 @@ impl Gui {
 
                 ui.same_line(115.0);
 
-                ui.text(im_str!("{:02}:{:02}", time.hour, time.second));
+                ui.text(im_str!("{}", time));
 
                 let red = ui.push_style_color(StyleColor::Header, [0.7, 0.2, 0.2, 0.5]);
 
@@ -414,8 +505,11 @@ impl Gui {
             self.windows.menu(ui);
 
             let h = ui.window_size()[1];
-            if ui.button(im_str!("Save"), [80.0, h]) {
-                egregoria::save_to_disk(goria);
+            let progress = goria.read::<SaveProgress>().progress();
+            if let Some(p) = progress {
+                ui.button(&im_str!("Saving {:.0}%", p * 100.0), [80.0, h]);
+            } else if ui.button(im_str!("Save"), [80.0, h]) {
+                save_to_disk_background(goria, &self.slot);
             }
 
             ui.menu(im_str!("Help"), true, || {
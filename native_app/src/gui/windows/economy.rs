@@ -0,0 +1,60 @@
+use crate::gui::windows::ImguiWindow;
+use common::History;
+use egregoria::economy::{CommodityKind, EconomyStats, STATS_HOURS};
+use egregoria::Egregoria;
+use imgui::{im_str, Condition, Ui};
+
+pub struct Economy {
+    kind: CommodityKind,
+}
+
+impl Default for Economy {
+    fn default() -> Self {
+        Self {
+            kind: CommodityKind::Bread,
+        }
+    }
+}
+
+fn plot(ui: &Ui, name: &str, h: &History) {
+    let w = ui.window_content_region_width();
+    ui.plot_lines(&im_str!("{}", name), &h.values)
+        .overlay_text(&im_str!("{:.1}", h.last()))
+        .scale_min(0.0)
+        .graph_size([w * 0.7, 50.0])
+        .build();
+}
+
+impl ImguiWindow for Economy {
+    fn render(&mut self, window: imgui::Window, ui: &Ui, goria: &mut Egregoria) {
+        window
+            .size([500.0, 450.0], Condition::FirstUseEver)
+            .build(ui, || {
+                if let Some(tok) = imgui::ComboBox::new(im_str!("Commodity"))
+                    .preview_value(&im_str!("{}", self.kind))
+                    .begin(ui)
+                {
+                    for &kind in CommodityKind::values() {
+                        if imgui::Selectable::new(&im_str!("{}", kind))
+                            .selected(kind == self.kind)
+                            .build(ui)
+                        {
+                            self.kind = kind;
+                        }
+                    }
+                    tok.end(ui);
+                }
+
+                ui.text(im_str!("Last {} hours", STATS_HOURS));
+
+                let stats = goria.read::<EconomyStats>();
+                let s = stats.get(self.kind);
+                plot(ui, "Traded volume", &s.volume);
+                plot(ui, "Unfilled buy orders", &s.unfilled_buy);
+                plot(ui, "Unfilled sell orders", &s.unfilled_sell);
+                plot(ui, "Trade distance (m)", &s.avg_distance);
+                plot(ui, "Stock", &s.stock);
+                plot(ui, "Price", &s.price);
+            });
+    }
+}
@@ -5,6 +5,7 @@ use egregoria::Egregoria;
 
 mod config;
 pub mod debug;
+mod economy;
 mod map;
 mod scenarios;
 pub mod settings;
@@ -50,6 +51,11 @@ impl Default for ImguiWindows {
         s.insert(imgui::im_str!("Config"), config::config, false);
         s.insert(imgui::im_str!("Debug"), debug::debug, false);
         s.insert(imgui::im_str!("Settings"), settings::settings, false);
+        s.insert(
+            imgui::im_str!("Economy"),
+            economy::Economy::default(),
+            false,
+        );
         s
     }
 }
@@ -4,9 +4,14 @@ use std::time::Duration;
 
 const SETTINGS_SAVE_NAME: &str = "settings";
 
-register_resource!(Settings, SETTINGS_SAVE_NAME);
+// Settings are not part of the save archive, they are kept in their own json file so that
+// settings added later fall back to their default
+init_func!(|goria| {
+    goria.insert(common::saveload::load_json::<Settings>(SETTINGS_SAVE_NAME).unwrap_or_default());
+});
 
 #[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub camera_sensibility: f32,
     pub camera_lock: bool,
@@ -21,6 +26,8 @@ pub struct Settings {
 
     pub time_warp: f32,
     pub auto_save_every: AutoSaveEvery,
+    /// Step the simulation with ticks of constant length so runs are reproducible
+    pub fixed_timestep: bool,
 }
 
 impl Default for Settings {
@@ -36,6 +43,7 @@ impl Default for Settings {
             vsync: VSyncOptions::Vsync,
             time_warp: 1.0,
             auto_save_every: AutoSaveEvery::Never,
+            fixed_timestep: false,
         }
     }
 }
@@ -122,6 +130,7 @@ pub fn settings(window: imgui::Window, ui: &Ui, goria: &mut Egregoria) {
                 }
                 tok.end(ui);
             }
+            ui.checkbox(im_str!("Fixed timestep"), &mut settings.fixed_timestep);
 
             ui.new_line();
             ui.text("Input");
@@ -173,6 +182,6 @@ pub fn settings(window: imgui::Window, ui: &Ui, goria: &mut Egregoria) {
                 .display_format(im_str!("%.0f"))
                 .build(ui, &mut settings.ui_volume_percent);
 
-            common::saveload::save_silent(&*settings, SETTINGS_SAVE_NAME);
+            common::saveload::save_silent_json(&*settings, SETTINGS_SAVE_NAME);
         });
 }
@@ -67,11 +67,11 @@ impl RoadRenderer {
 
         let mut buildings_builder = HashMap::new();
 
-        for descr in egregoria::souls::goods_company::GOODS_BUILDINGS {
+        for descr in &egregoria::economy::economy_data().companies {
             buildings_builder.insert(
                 descr.bkind,
                 SpriteBatchBuilder::new(
-                    gfx.texture(descr.asset_location, Some(descr.asset_location)),
+                    gfx.texture(&descr.asset_location, Some(descr.asset_location.as_str())),
                 ),
             );
         }
@@ -97,6 +97,7 @@ impl RoadRenderer {
         let mid_col: LinearColor = common::config().road_mid_col.into();
         let hig_col: LinearColor = common::config().road_hig_col.into();
         let line_col: LinearColor = common::config().road_line_col.into();
+        let bike_col: LinearColor = common::config().road_bike_col.into();
 
         let inters = map.intersections();
         let lanes = map.lanes();
@@ -118,6 +119,7 @@ impl RoadRenderer {
             tess.set_color(match l.kind {
                 LaneKind::Walking => hig_col,
                 LaneKind::Parking => low_col,
+                LaneKind::Biking => bike_col,
                 _ => mid_col,
             });
             let z = match l.kind {